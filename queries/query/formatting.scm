@@ -1,8 +1,9 @@
-; Ignore next node with `; format-ignore`
+; Ignore next node with `; format-ignore`. Regions between `; format-ignore-start` and
+; `; format-ignore-end` are handled separately
 ((comment) @_pattern
   .
   (_) @format.ignore
-  (#match? @_pattern "^;+\\s*format\\-ignore"))
+  (#match? @_pattern "^;+\\s*format\\-ignore([^\\-]|$)"))
 
 ; Add newlines to top level nodes
 ; Preserve inline comments
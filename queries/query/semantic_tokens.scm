@@ -0,0 +1,25 @@
+(named_node
+  (identifier) @node)
+
+(missing_node
+  name: (identifier) @node)
+
+(anonymous_node
+  name: (string) @string)
+
+(missing_node
+  name: (string) @string)
+
+(field_definition
+  name: (identifier) @field)
+
+(negated_field
+  (identifier) @field)
+
+(capture) @capture
+
+(predicate) @predicate
+
+(escape_sequence) @escape
+
+(comment) @comment
@@ -12,7 +12,7 @@ use crate::{
     LanguageData, Options,
     cli::lint::LintOptions,
     handlers::did_open::init_language_data,
-    util::{self, get_scm_files},
+    util::{self, ParserNamesCache, get_scm_files},
 };
 
 use super::{format::format_directories, lint::lint_file};
@@ -31,6 +31,7 @@ pub async fn check_directories(
         return 1;
     };
     let options_arc: Arc<tokio::sync::RwLock<Options>> = Arc::new(options.clone().into());
+    let parser_names = Arc::new(ParserNamesCache::default());
 
     let exit_code = Arc::new(AtomicI32::new(0));
     // If directories are not specified, check all files in the current directory
@@ -47,6 +48,7 @@ pub async fn check_directories(
     let scm_files = get_scm_files(directories);
     let tasks = scm_files.into_iter().filter_map(|path| {
         let options_arc = options_arc.clone();
+        let parser_names = parser_names.clone();
         let exit_code = exit_code.clone();
         let absolute_path = path.canonicalize().expect("Path should be valid");
         let uri = Url::from_file_path(&absolute_path).expect("Path should be absolute");
@@ -71,6 +73,7 @@ pub async fn check_directories(
                 &workspace,
                 &source,
                 options_arc.clone(),
+                &parser_names,
                 lint_opts,
                 language_data,
                 &exit_code,
@@ -84,7 +87,7 @@ pub async fn check_directories(
         }))
     });
     join_all(tasks).await;
-    if format && format_directories(directories, true).await != 0 {
+    if format && format_directories(directories, &options.format_options, true).await != 0 {
         exit_code.store(1, std::sync::atomic::Ordering::Relaxed);
     }
     exit_code.load(std::sync::atomic::Ordering::Relaxed)
@@ -7,13 +7,18 @@ use std::{
 use anstyle::{AnsiColor, Color, Style};
 use futures::future::join_all;
 use ropey::Rope;
+use ts_query_ls::FormatOptions;
 
 use crate::{
     handlers::formatting,
     util::{get_scm_files, parse},
 };
 
-pub async fn format_directories(directories: &[PathBuf], check: bool) -> i32 {
+pub async fn format_directories(
+    directories: &[PathBuf],
+    options: &FormatOptions,
+    check: bool,
+) -> i32 {
     if directories.is_empty() {
         eprintln!("No directories were specified to be formatted. No work was done.");
         return 1;
@@ -35,6 +40,7 @@ pub async fn format_directories(directories: &[PathBuf], check: bool) -> i32 {
 
     let tasks = scm_files.into_iter().map(|path| {
         let exit_code = exit_code.clone();
+        let options = options.clone();
         tokio::spawn(async move {
             let path_str = path.to_string_lossy();
             let Ok(contents) = fs::read_to_string(&path) else {
@@ -44,7 +50,8 @@ pub async fn format_directories(directories: &[PathBuf], check: bool) -> i32 {
             };
             let rope = Rope::from(contents.as_str());
             let tree = parse(&rope, None);
-            let Some(formatted) = formatting::format_document(&rope, &tree.root_node()) else {
+            let Some(formatted) = formatting::format_document(&rope, &tree.root_node(), &options)
+            else {
                 exit_code.store(1, std::sync::atomic::Ordering::Relaxed);
                 eprintln!("No formatting performed -- invalid syntax detected at {path_str:?}");
                 return;
@@ -7,16 +7,14 @@ use std::{
 use dashmap::DashMap;
 use futures::future::join_all;
 use ropey::Rope;
-use tower_lsp::lsp_types::{CodeAction, CodeActionOrCommand, DiagnosticSeverity, Url};
+use tower_lsp::lsp_types::{DiagnosticSeverity, Url};
 
 use crate::{
     DocumentData, LanguageData, Options,
     handlers::{
-        code_action::diag_to_code_action,
-        diagnostic::{DiagnosticCode, get_diagnostics},
-        did_open::populate_import_documents,
+        code_action::apply_fixes, diagnostic::get_diagnostics, did_open::populate_import_documents,
     },
-    util::{edit_rope, get_imported_uris, get_language_name, get_scm_files, parse},
+    util::{ParserNamesCache, get_imported_uris, get_language_name, get_scm_files, parse},
 };
 
 #[derive(Debug, Copy, Clone)]
@@ -35,11 +33,13 @@ impl LintOptions {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn lint_file(
     absolute_path: &Path,
     workspace: &Path,
     source: &str,
     server_options: Arc<tokio::sync::RwLock<Options>>,
+    parser_names: &ParserNamesCache,
     lint_options: LintOptions,
     language_data: Option<Arc<LanguageData>>,
     exit_code: &AtomicI32,
@@ -69,6 +69,7 @@ pub(super) async fn lint_file(
         doc.clone(),
         language_data,
         server_options,
+        parser_names,
         lint_options.ignore_missing_language,
         cache,
     )
@@ -77,87 +78,52 @@ pub(super) async fn lint_file(
         return None;
     }
 
-    let mut edits = Vec::with_capacity(if lint_options.fix {
-        diagnostics.len()
-    } else {
-        0
-    });
-    if !lint_options.fix {
-        exit_code.store(1, std::sync::atomic::Ordering::Relaxed);
+    if lint_options.fix {
+        let (fixed_rope, unfixed_issues) = apply_fixes(&doc.tree, &doc.rope, diagnostics, &uri);
+        if unfixed_issues > 0 {
+            let plurality = if unfixed_issues > 1 { "s" } else { "" };
+            println!(
+                "{}: {unfixed_issues} issue{plurality} could not be fixed automatically",
+                absolute_path.display()
+            );
+        }
+        return fixed_rope.map(|rope| rope.to_string());
     }
-    let mut unfixed_issues = 0;
+    exit_code.store(1, std::sync::atomic::Ordering::Relaxed);
     for diagnostic in diagnostics {
-        if lint_options.fix {
-            let is_module_diagnostic = diagnostic.code == DiagnosticCode::ImportIssues.into();
-            let Some(action) = diag_to_code_action(&doc.tree, &doc.rope, diagnostic, &uri) else {
-                if !is_module_diagnostic {
-                    unfixed_issues += 1;
-                }
-                continue;
-            };
-            let CodeActionOrCommand::CodeAction(CodeAction {
-                edit: Some(edit), ..
-            }) = action
-            else {
-                continue;
-            };
-            let Some(mut changes) = edit.changes.and_then(|mut changes| changes.remove(&uri))
-            else {
-                continue;
-            };
-            edits.append(&mut changes);
-        } else {
-            let kind = match diagnostic.severity {
-                Some(DiagnosticSeverity::ERROR) => "Error",
-                Some(DiagnosticSeverity::WARNING) => "Warning",
-                Some(DiagnosticSeverity::INFORMATION) => "Info",
-                Some(DiagnosticSeverity::HINT) => "Hint",
-                _ => "Diagnostic",
-            };
+        let kind = match diagnostic.severity {
+            Some(DiagnosticSeverity::ERROR) => "Error",
+            Some(DiagnosticSeverity::WARNING) => "Warning",
+            Some(DiagnosticSeverity::INFORMATION) => "Info",
+            Some(DiagnosticSeverity::HINT) => "Hint",
+            _ => "Diagnostic",
+        };
+        eprintln!(
+            "{} in \"{}\" on line {}, col {}:\n  {}",
+            kind,
+            absolute_path.display(),
+            diagnostic.range.start.line + 1,
+            diagnostic.range.start.character + 1,
+            diagnostic.message
+        );
+        for related_info in diagnostic.related_information.unwrap_or_default() {
             eprintln!(
-                "{} in \"{}\" on line {}, col {}:\n  {}",
-                kind,
-                absolute_path.display(),
-                diagnostic.range.start.line + 1,
-                diagnostic.range.start.character + 1,
-                diagnostic.message
+                "    ‣ {}:{}:{}: {}",
+                related_info
+                    .location
+                    .uri
+                    .to_file_path()
+                    .expect("Related information URI should be a valid file path")
+                    .strip_prefix(workspace)
+                    .expect("Related information URI should be within the workspace")
+                    .to_string_lossy(),
+                related_info.location.range.start.line + 1,
+                related_info.location.range.start.character + 1,
+                related_info.message
             );
-            for related_info in diagnostic.related_information.unwrap_or_default() {
-                eprintln!(
-                    "    ‣ {}:{}:{}: {}",
-                    related_info
-                        .location
-                        .uri
-                        .to_file_path()
-                        .expect("Related information URI should be a valid file path")
-                        .strip_prefix(workspace)
-                        .expect("Related information URI should be within the workspace")
-                        .to_string_lossy(),
-                    related_info.location.range.start.line + 1,
-                    related_info.location.range.start.character + 1,
-                    related_info.message
-                );
-            }
         }
     }
-    if unfixed_issues > 0 {
-        let plurality = if unfixed_issues > 1 { "s" } else { "" };
-        println!(
-            "{}: {unfixed_issues} issue{plurality} could not be fixed automatically",
-            absolute_path.display()
-        );
-    }
-    if !lint_options.fix || edits.is_empty() {
-        return None;
-    }
-    edits.sort_unstable_by(|a, b| b.range.start.cmp(&a.range.start));
-    let mut rope = doc.rope;
-    for edit in edits {
-        let range = edit.range;
-        let new_text = edit.new_text;
-        edit_rope(&mut rope, range, &new_text);
-    }
-    Some(rope.to_string())
+    None
 }
 
 /// Lint all the given directories according to the given configuration. Linting covers things like
@@ -174,6 +140,7 @@ pub async fn lint_directories(
         return 1;
     };
     let options: Arc<tokio::sync::RwLock<Options>> = Arc::new(options.into());
+    let parser_names = Arc::new(ParserNamesCache::default());
     let exit_code = Arc::new(AtomicI32::new(0));
     // If directories are not specified, lint all files in the current directory
     let directories = if directories.is_empty() {
@@ -191,6 +158,7 @@ pub async fn lint_directories(
         let absolute_path = path.canonicalize().expect("Path should be valid");
         let exit_code = exit_code.clone();
         let options = options.clone();
+        let parser_names = parser_names.clone();
         if let Ok(source) = fs::read_to_string(&path) {
             let workspace = workspace.clone();
             let lint_opts = LintOptions::new(fix, true);
@@ -200,6 +168,7 @@ pub async fn lint_directories(
                     &workspace,
                     &source,
                     options,
+                    &parser_names,
                     lint_opts,
                     None,
                     &exit_code,
@@ -1,21 +1,31 @@
-use std::{collections::HashMap, vec};
+use std::{collections::HashMap, fs, path::PathBuf, vec};
 
 use ropey::Rope;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Serializer, Value, ser::PrettyFormatter};
 use tower_lsp::lsp_types::{
     CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, CodeActionResponse,
-    Diagnostic, Position, Range, TextEdit, Url, WorkspaceEdit,
+    CreateFile, Diagnostic, DocumentChangeOperation, DocumentChanges, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, Range, ResourceOp, TextDocumentEdit,
+    TextEdit, Url, WorkspaceEdit,
 };
-use tree_sitter::{QueryCursor, Tree};
+use tree_sitter::{Node, QueryCursor, Tree};
+use ts_query_ls::{Options, PredicateParameterType, StringArgumentStyle};
 
 use crate::{
     Backend, LspClient,
     util::{
-        CAPTURES_QUERY, NodeUtil, PosUtil, RangeUtil, TextProviderRope, get_current_capture_node,
-        get_references,
+        ByteUtil, CAPTURES_QUERY, NodeUtil, PosUtil, RangeUtil, TextProviderRope, edit_rope,
+        get_current_capture_node, get_first_valid_file_config, get_references,
+        unescape_string_content, uri_to_basename,
     },
 };
 
+use super::{
+    diagnostic::{DiagnosticCode, IDENTIFIER_REGEX, get_diagnostics},
+    formatting::diffs,
+};
+
 #[repr(u8)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(into = "u8", try_from = "u8")]
@@ -25,6 +35,15 @@ pub enum CodeActions {
     Remove,
     Trim,
     Enquote,
+    ReplaceWithEq,
+    UseAnyPredicate,
+    Replace,
+    RenameCapture,
+    MergeIntoAnyOf,
+    RenamePredicate,
+    RemovePredicate,
+    RemoveDuplicateValue,
+    RemoveRedundantPattern,
 }
 
 impl From<CodeActions> for serde_json::Value {
@@ -33,6 +52,14 @@ impl From<CodeActions> for serde_json::Value {
     }
 }
 
+impl CodeActions {
+    /// Diagnostic data for a code action which needs arguments that cannot be recomputed from the
+    /// diagnostic range, such as replacement text.
+    pub fn with_arguments(self, arguments: &[String]) -> serde_json::Value {
+        serde_json::json!([self, arguments])
+    }
+}
+
 impl From<CodeActions> for u8 {
     fn from(e: CodeActions) -> Self {
         e as Self
@@ -49,19 +76,73 @@ impl TryFrom<u8> for CodeActions {
             2 => Ok(Self::Remove),
             3 => Ok(Self::Trim),
             4 => Ok(Self::Enquote),
+            5 => Ok(Self::ReplaceWithEq),
+            6 => Ok(Self::UseAnyPredicate),
+            7 => Ok(Self::Replace),
+            8 => Ok(Self::RenameCapture),
+            9 => Ok(Self::MergeIntoAnyOf),
+            10 => Ok(Self::RenamePredicate),
+            11 => Ok(Self::RemovePredicate),
+            12 => Ok(Self::RemoveDuplicateValue),
+            13 => Ok(Self::RemoveRedundantPattern),
             _ => Err("Invalid value"),
         }
     }
 }
 
+fn get_code_action_data(diagnostic: &Diagnostic) -> Option<(CodeActions, Vec<String>)> {
+    let data = diagnostic.data.clone()?;
+    serde_json::from_value::<(CodeActions, Vec<String>)>(data.clone())
+        .or_else(|_| serde_json::from_value::<CodeActions>(data).map(|action| (action, Vec::new())))
+        .ok()
+}
+
+/// Get every code action which fixes the given diagnostic, such as one for each suggested
+/// replacement of an invalid name.
+pub fn diag_to_code_actions(
+    tree: &Tree,
+    rope: &Rope,
+    diagnostic: Diagnostic,
+    uri: &Url,
+) -> Vec<CodeActionOrCommand> {
+    let Some((action, arguments)) = get_code_action_data(&diagnostic) else {
+        return Vec::new();
+    };
+    let is_preferred = arguments.len() == 1;
+    match action {
+        CodeActions::Replace => arguments
+            .iter()
+            .map(|replacement| replace_action(&diagnostic, uri, replacement, is_preferred))
+            .collect(),
+        CodeActions::RenameCapture => arguments
+            .iter()
+            .filter_map(|name| {
+                rename_capture_action(tree, rope, &diagnostic, uri, name, is_preferred)
+            })
+            .chain(prefix_underscore_action(
+                tree,
+                rope,
+                &diagnostic,
+                uri,
+                false,
+            ))
+            .collect(),
+        _ => diag_to_code_action(tree, rope, diagnostic, uri)
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Get the preferred code action which fixes the given diagnostic, if there is an unambiguous one.
 pub fn diag_to_code_action(
     tree: &Tree,
     rope: &Rope,
     diagnostic: Diagnostic,
     uri: &Url,
 ) -> Option<CodeActionOrCommand> {
-    match serde_json::from_value::<CodeActions>(diagnostic.data.clone()?) {
-        Ok(CodeActions::RemoveBackslash) => Some(CodeActionOrCommand::CodeAction(CodeAction {
+    let (action, arguments) = get_code_action_data(&diagnostic)?;
+    match action {
+        CodeActions::RemoveBackslash => Some(CodeActionOrCommand::CodeAction(CodeAction {
             title: String::from("Remove unnecessary backslash"),
             kind: Some(CodeActionKind::QUICKFIX),
             is_preferred: Some(true),
@@ -84,62 +165,18 @@ pub fn diag_to_code_action(
             diagnostics: Some(vec![diagnostic]),
             ..Default::default()
         })),
-        Ok(CodeActions::PrefixUnderscore) => {
-            let root = tree.root_node();
-            let current_node =
-                get_current_capture_node(root, diagnostic.range.start.to_ts_point(rope))?;
-            let mut cursor = QueryCursor::new();
-            let provider = TextProviderRope(rope);
-            let refs = get_references(
-                &root,
-                &current_node,
-                &CAPTURES_QUERY,
-                &mut cursor,
-                &provider,
-                rope,
-            );
-            let edits = refs
-                .into_iter()
-                .map(|node| {
-                    let mut range = node.lsp_range(rope);
-                    range.start.character += 1;
-                    range.end.character = range.start.character;
-                    TextEdit {
-                        new_text: String::from("_"),
-                        range,
-                    }
-                })
-                .collect();
-            Some(CodeActionOrCommand::CodeAction(CodeAction {
-                title: String::from("Prefix capture name with underscore"),
-                kind: Some(CodeActionKind::QUICKFIX),
-                is_preferred: Some(true),
-                edit: Some(WorkspaceEdit {
-                    changes: Some(HashMap::from([(uri.clone(), edits)])),
-                    ..Default::default()
-                }),
-                diagnostics: Some(vec![diagnostic]),
-                ..Default::default()
-            }))
+        CodeActions::PrefixUnderscore => {
+            prefix_underscore_action(tree, rope, &diagnostic, uri, true)
         }
-        Ok(CodeActions::Remove) => Some(CodeActionOrCommand::CodeAction(CodeAction {
-            title: String::from("Remove pattern"),
-            kind: Some(CodeActionKind::QUICKFIX),
-            is_preferred: Some(true),
-            edit: Some(WorkspaceEdit {
-                changes: Some(HashMap::from([(
-                    uri.clone(),
-                    vec![TextEdit {
-                        new_text: String::new(),
-                        range: diagnostic.range,
-                    }],
-                )])),
-                ..Default::default()
-            }),
-            diagnostics: Some(vec![diagnostic]),
-            ..Default::default()
-        })),
-        Ok(CodeActions::Trim) => {
+        CodeActions::Remove => Some(remove_action(diagnostic, uri, "Remove pattern")),
+        CodeActions::RemovePredicate => Some(remove_action(diagnostic, uri, "Remove predicate")),
+        CodeActions::RemoveDuplicateValue => {
+            Some(remove_action(diagnostic, uri, "Remove duplicate value"))
+        }
+        CodeActions::RemoveRedundantPattern => {
+            Some(remove_action(diagnostic, uri, "Remove redundant pattern"))
+        }
+        CodeActions::Trim => {
             let mut range = diagnostic.range;
             range.start.character += 1;
             range.end.character -= 1;
@@ -162,7 +199,7 @@ pub fn diag_to_code_action(
                 ..Default::default()
             }))
         }
-        Ok(CodeActions::Enquote) => {
+        CodeActions::Enquote => {
             let new_text = diagnostic.range.text(rope);
             let new_text = format!("\"{new_text}\"");
             Some(CodeActionOrCommand::CodeAction(CodeAction {
@@ -183,22 +220,375 @@ pub fn diag_to_code_action(
                 ..Default::default()
             }))
         }
-        Err(_) => None,
+        CodeActions::ReplaceWithEq => {
+            let [name, string] = arguments.as_slice() else {
+                return None;
+            };
+            let name_node = get_predicate_name_node(tree, diagnostic.range, rope)?;
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Replace with `#{name}?`"),
+                kind: Some(CodeActionKind::QUICKFIX),
+                is_preferred: Some(true),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(
+                        uri.clone(),
+                        vec![
+                            TextEdit {
+                                new_text: name.clone(),
+                                range: name_node.lsp_range(rope),
+                            },
+                            TextEdit {
+                                new_text: string.clone(),
+                                range: diagnostic.range,
+                            },
+                        ],
+                    )])),
+                    ..Default::default()
+                }),
+                diagnostics: Some(vec![diagnostic]),
+                ..Default::default()
+            }))
+        }
+        CodeActions::UseAnyPredicate | CodeActions::RenamePredicate => {
+            let [name] = arguments.as_slice() else {
+                return None;
+            };
+            let name_node = get_predicate_name_node(tree, diagnostic.range, rope)?;
+            let title = if matches!(action, CodeActions::UseAnyPredicate) {
+                format!("Use `#{name}?`")
+            } else {
+                format!("Rename to `#{name}?`")
+            };
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                is_preferred: Some(true),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(
+                        uri.clone(),
+                        vec![TextEdit {
+                            new_text: name.clone(),
+                            range: name_node.lsp_range(rope),
+                        }],
+                    )])),
+                    ..Default::default()
+                }),
+                diagnostics: Some(vec![diagnostic]),
+                ..Default::default()
+            }))
+        }
+        CodeActions::Replace => match arguments.as_slice() {
+            [replacement] => Some(replace_action(&diagnostic, uri, replacement, true)),
+            _ => None,
+        },
+        CodeActions::MergeIntoAnyOf => {
+            let [replacement] = arguments.as_slice() else {
+                return None;
+            };
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: String::from("Merge into one pattern using `#any-of?`"),
+                kind: Some(CodeActionKind::QUICKFIX),
+                is_preferred: Some(true),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(
+                        uri.clone(),
+                        vec![TextEdit {
+                            new_text: replacement.clone(),
+                            range: diagnostic.range,
+                        }],
+                    )])),
+                    ..Default::default()
+                }),
+                diagnostics: Some(vec![diagnostic]),
+                ..Default::default()
+            }))
+        }
+        // Without an unambiguous replacement, fall back to marking the capture as auxiliary
+        CodeActions::RenameCapture => match arguments.as_slice() {
+            [name] => rename_capture_action(tree, rope, &diagnostic, uri, name, true),
+            _ => prefix_underscore_action(tree, rope, &diagnostic, uri, true),
+        },
+    }
+}
+
+fn remove_action(diagnostic: Diagnostic, uri: &Url, title: &str) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from(title),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(true),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                uri.clone(),
+                vec![TextEdit {
+                    new_text: String::new(),
+                    range: diagnostic.range,
+                }],
+            )])),
+            ..Default::default()
+        }),
+        diagnostics: Some(vec![diagnostic]),
+        ..Default::default()
+    })
+}
+
+fn replace_action(
+    diagnostic: &Diagnostic,
+    uri: &Url,
+    replacement: &str,
+    is_preferred: bool,
+) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Replace with `{replacement}`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(is_preferred),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                uri.clone(),
+                vec![TextEdit {
+                    new_text: replacement.to_owned(),
+                    range: diagnostic.range,
+                }],
+            )])),
+            ..Default::default()
+        }),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        ..Default::default()
+    })
+}
+
+/// Get the ranges of the definitions and references of the capture at the start of the
+/// diagnostic's range.
+fn get_capture_references(tree: &Tree, rope: &Rope, diagnostic: &Diagnostic) -> Option<Vec<Range>> {
+    let root = tree.root_node();
+    let current_node = get_current_capture_node(root, diagnostic.range.start.to_ts_point(rope))?;
+    let mut cursor = QueryCursor::new();
+    let provider = TextProviderRope(rope);
+    Some(
+        get_references(
+            &root,
+            &current_node,
+            &CAPTURES_QUERY,
+            &mut cursor,
+            &provider,
+            rope,
+        )
+        .map(|node| node.lsp_range(rope))
+        .collect(),
+    )
+}
+
+fn prefix_underscore_action(
+    tree: &Tree,
+    rope: &Rope,
+    diagnostic: &Diagnostic,
+    uri: &Url,
+    is_preferred: bool,
+) -> Option<CodeActionOrCommand> {
+    let edits = get_capture_references(tree, rope, diagnostic)?
+        .into_iter()
+        .map(|mut range| {
+            range.start.character += 1;
+            range.end.character = range.start.character;
+            TextEdit {
+                new_text: String::from("_"),
+                range,
+            }
+        })
+        .collect();
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Prefix capture name with underscore"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(is_preferred),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        ..Default::default()
+    }))
+}
+
+fn rename_capture_action(
+    tree: &Tree,
+    rope: &Rope,
+    diagnostic: &Diagnostic,
+    uri: &Url,
+    name: &str,
+    is_preferred: bool,
+) -> Option<CodeActionOrCommand> {
+    let edits = get_capture_references(tree, rope, diagnostic)?
+        .into_iter()
+        .map(|range| TextEdit {
+            new_text: name.to_owned(),
+            range,
+        })
+        .collect();
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Rename capture to `{name}`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(is_preferred),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        ..Default::default()
+    }))
+}
+
+/// Apply the preferred fix of each of the given diagnostics which has one, as `lint --fix` does.
+/// Fixes which overlap a fix that has already been accepted are skipped, since their edits were
+/// computed from the original text; fixes enclosing others are accepted first, since they usually
+/// replace them (e.g. removing a pattern which contains another fix). Returns the fixed text, if
+/// anything was fixed, along with the number of diagnostics which were not fixed (not counting
+/// those of imported modules, which must be fixed in the modules).
+pub fn apply_fixes(
+    tree: &Tree,
+    rope: &Rope,
+    diagnostics: Vec<Diagnostic>,
+    uri: &Url,
+) -> (Option<Rope>, usize) {
+    let mut fixes = Vec::with_capacity(diagnostics.len());
+    let mut unfixed_issues = 0;
+    for diagnostic in diagnostics {
+        let is_module_diagnostic = diagnostic.code == DiagnosticCode::ImportIssues.into();
+        let Some(action) = diag_to_code_action(tree, rope, diagnostic, uri) else {
+            if !is_module_diagnostic {
+                unfixed_issues += 1;
+            }
+            continue;
+        };
+        let CodeActionOrCommand::CodeAction(CodeAction {
+            edit: Some(edit), ..
+        }) = action
+        else {
+            continue;
+        };
+        let Some(changes) = edit.changes.and_then(|mut changes| changes.remove(uri)) else {
+            continue;
+        };
+        let Some(start) = changes.iter().map(|edit| edit.range.start).min() else {
+            continue;
+        };
+        let end = changes
+            .iter()
+            .map(|edit| edit.range.end)
+            .max()
+            .unwrap_or(start);
+        fixes.push((start, end, changes));
+    }
+    fixes.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+    let mut edits: Vec<TextEdit> = Vec::with_capacity(fixes.len());
+    for (_, _, mut changes) in fixes {
+        if changes.iter().any(|change| {
+            edits
+                .iter()
+                .any(|edit| ranges_overlap(change.range, edit.range))
+        }) {
+            unfixed_issues += 1;
+            continue;
+        }
+        edits.append(&mut changes);
+    }
+    if edits.is_empty() {
+        return (None, unfixed_issues);
+    }
+    edits.sort_unstable_by_key(|edit| std::cmp::Reverse(edit.range.start));
+    let mut rope = rope.clone();
+    for edit in edits {
+        edit_rope(&mut rope, edit.range, &edit.new_text);
+    }
+    (Some(rope), unfixed_issues)
+}
+
+/// Get the edits which apply the preferred fix of each of the given diagnostics to a document, as
+/// `apply_fixes` does. The edits are computed from the fixed text, so that they are minimal.
+pub fn get_fix_all_edits(
+    tree: &Tree,
+    rope: &Rope,
+    diagnostics: Vec<Diagnostic>,
+    uri: &Url,
+) -> Option<Vec<TextEdit>> {
+    let fixed = apply_fixes(tree, rope, diagnostics, uri).0?.to_string();
+    let source = rope.to_string();
+    let edits: Vec<_> = diffs(&source, &fixed, rope.clone()).collect();
+    if edits.is_empty() { None } else { Some(edits) }
+}
+
+/// Whether two edit ranges overlap. Insertions at the same position also overlap, since their
+/// order would be ambiguous.
+fn ranges_overlap(a: Range, b: Range) -> bool {
+    (a.start < b.end && b.start < a.end) || a.start == b.start
+}
+
+/// Get the name of the predicate containing the given range.
+fn get_predicate_name_node<'a>(tree: &'a Tree, range: Range, rope: &Rope) -> Option<Node<'a>> {
+    let start = range.start.to_ts_point(rope);
+    let end = range.end.to_ts_point(rope);
+    let mut predicate = tree
+        .root_node()
+        .named_descendant_for_point_range(start, end)?;
+    while predicate.kind() != "predicate" {
+        predicate = predicate.parent()?;
     }
+    predicate
+        .children_by_field_name("name", &mut predicate.walk())
+        .find(|node| node.kind() == "identifier")
 }
 
-pub fn code_action<C: LspClient>(
+pub async fn code_action<C: LspClient>(
     backend: &Backend<C>,
     params: CodeActionParams,
 ) -> Option<CodeActionResponse> {
     let uri = &params.text_document.uri;
+    let only = params.context.only.as_deref();
     let diagnostics = params.context.diagnostics;
+    // Source actions apply to the whole document, so they are only offered when requested
+    let fix_all_action = if only.is_some() && is_requested(only, &CodeActionKind::SOURCE_FIX_ALL) {
+        get_fix_all_action(backend, uri).await
+    } else {
+        None
+    };
+    let (string_argument_style, default_options) = {
+        let options = backend.options.read().await;
+        (
+            options.diagnostic_options.string_argument_style.clone(),
+            *options == Options::default(),
+        )
+    };
+    let workspace_paths = backend
+        .workspace_paths
+        .read()
+        .as_deref()
+        .cloned()
+        .unwrap_or_default();
     let doc = backend.document_map.get(uri)?;
 
-    let actions: Vec<CodeActionOrCommand> = diagnostics
-        .into_iter()
-        .filter_map(|diagnostic| diag_to_code_action(&doc.tree, &doc.rope, diagnostic, uri))
-        .collect();
+    let mut actions: Vec<CodeActionOrCommand> = Vec::new();
+    if is_requested(only, &CodeActionKind::QUICKFIX) {
+        for diagnostic in diagnostics {
+            let add_to_config_action = add_to_config_action(
+                &doc.tree,
+                &doc.rope,
+                &diagnostic,
+                uri,
+                &workspace_paths,
+                default_options,
+            );
+            actions.extend(diag_to_code_actions(&doc.tree, &doc.rope, diagnostic, uri));
+            actions.extend(add_to_config_action);
+        }
+    }
+    if is_requested(only, &CodeActionKind::REFACTOR_REWRITE) {
+        actions.extend(get_refactor_actions(
+            &doc.tree,
+            &doc.rope,
+            uri,
+            params.range,
+            &string_argument_style,
+        ));
+    }
+    actions.extend(fix_all_action);
 
     if actions.is_empty() {
         None
@@ -207,54 +597,867 @@ pub fn code_action<C: LspClient>(
     }
 }
 
-#[cfg(test)]
-mod test {
-    use std::collections::HashMap;
-
-    use pretty_assertions::assert_eq;
-    use rstest::rstest;
-    use tower_lsp::lsp_types::{
-        CodeAction, CodeActionContext, CodeActionKind, Diagnostic, Position, Range,
-        TextDocumentIdentifier, TextEdit, WorkspaceEdit,
-    };
-    use tower_lsp::lsp_types::{
-        CodeActionOrCommand, CodeActionParams, PartialResultParams, WorkDoneProgressParams,
-        request::CodeActionRequest,
+/// Get an action which declares the capture, predicate or directive reported by the given
+/// diagnostic in the configuration file, creating the file if there is none and the effective
+/// options are the defaults.
+fn add_to_config_action(
+    tree: &Tree,
+    rope: &Rope,
+    diagnostic: &Diagnostic,
+    uri: &Url,
+    workspace_paths: &[PathBuf],
+    default_options: bool,
+) -> Option<CodeActionOrCommand> {
+    let (title, key_path, value) = if diagnostic.code == DiagnosticCode::InvalidCaptureName.into() {
+        let query_name = uri_to_basename(uri)?;
+        let name = diagnostic.range.text(rope);
+        let name = name.strip_prefix('@')?;
+        (
+            format!("Add `@{name}` to valid_captures.{query_name}"),
+            ["valid_captures", query_name.as_str(), name]
+                .map(String::from)
+                .to_vec(),
+            Value::String(String::new()),
+        )
+    } else if diagnostic.code == DiagnosticCode::UnrecognizedPredicate.into()
+        || diagnostic.code == DiagnosticCode::UnrecognizedDirective.into()
+    {
+        let start = diagnostic.range.start.to_ts_point(rope);
+        let end = diagnostic.range.end.to_ts_point(rope);
+        let node = tree
+            .root_node()
+            .named_descendant_for_point_range(start, end)?;
+        let predicate = get_ancestor_of_kind(node, "predicate")?;
+        let mut value = get_predicate_stub(predicate);
+        let name = diagnostic.range.text(rope);
+        if diagnostic.code == DiagnosticCode::UnrecognizedDirective.into() {
+            (
+                format!("Add `#{name}!` to valid_directives"),
+                vec![String::from("valid_directives"), name],
+                value,
+            )
+        } else {
+            // Prefixed variants are declared through their base predicate
+            let name = name.strip_prefix("not-").unwrap_or(&name);
+            let name = if let Some(name) = name.strip_prefix("any-") {
+                value["any"] = Value::Bool(true);
+                name
+            } else {
+                name
+            };
+            (
+                format!("Add `#{name}?` to valid_predicates"),
+                vec![String::from("valid_predicates"), name.to_owned()],
+                value,
+            )
+        }
+    } else {
+        return None;
     };
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(get_config_edit(
+            workspace_paths,
+            &key_path,
+            value,
+            default_options,
+        )?),
+        ..Default::default()
+    }))
+}
 
-    use crate::{
-        Options,
-        handlers::code_action::CodeActions,
-        test_helpers::helpers::{TEST_URI, TestService, initialize_server},
-    };
+/// Get a specification for a predicate or directive with the parameters it is given at the call
+/// site, for the user to fill in.
+fn get_predicate_stub(predicate: Node) -> Value {
+    let parameters: Vec<_> = predicate
+        .child_by_field_name("parameters")
+        .map(|params| {
+            params
+                .named_children(&mut params.walk())
+                .filter(|param| param.kind() != "comment")
+                .map(|param| {
+                    let type_ = if param.kind() == "capture" {
+                        PredicateParameterType::Capture
+                    } else {
+                        PredicateParameterType::String
+                    };
+                    serde_json::json!({ "type": type_ })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    serde_json::json!({
+        "description": "",
+        "parameters": parameters,
+    })
+}
 
-    #[rstest]
-    #[case(r#""\p" @_somecap"#, Options::default(), Position::new(0, 2), CodeActionContext {
-        diagnostics: vec![Diagnostic {
-            message: String::from("bad escape"),
-            range: Range::new(Position::new(0, 1), Position::new(0, 3)),
-            data: Some(serde_json::to_value(CodeActions::RemoveBackslash).unwrap()),
-            ..Default::default()
-        }],
+/// Get an edit which sets the value at the given key path of the configuration file which applies
+/// to the workspace, creating the file in the first workspace directory if there is none.
+///
+/// A configuration file replaces the initialization options entirely, so the file is only created
+/// when the effective options are the defaults; the new file holding just the given value then
+/// keeps every other option as it was.
+fn get_config_edit(
+    workspace_paths: &[PathBuf],
+    key_path: &[String],
+    value: Value,
+    default_options: bool,
+) -> Option<WorkspaceEdit> {
+    let mut operations = Vec::new();
+    let (uri, edit) = if let Some((path, _)) = get_first_valid_file_config(workspace_paths) {
+        let contents = fs::read_to_string(&path).ok()?;
+        let rope = Rope::from_str(&contents);
+        let (start, end, new_text) = get_json_edit(&contents, key_path, value)?;
+        let range = Range::new(start.to_lsp_pos(&rope), end.to_lsp_pos(&rope));
+        (
+            Url::from_file_path(&path).ok()?,
+            TextEdit { range, new_text },
+        )
+    } else if default_options {
+        let path = workspace_paths.first()?.join(".tsqueryrc.json");
+        let uri = Url::from_file_path(&path).ok()?;
+        operations.push(DocumentChangeOperation::Op(ResourceOp::Create(
+            CreateFile {
+                uri: uri.clone(),
+                options: None,
+                annotation_id: None,
+            },
+        )));
+        let config = nest_json_value(key_path, value);
+        let new_text = serde_json::to_string_pretty(&config).ok()? + "\n";
+        (uri, TextEdit::new(Range::default(), new_text))
+    } else {
+        return None;
+    };
+    operations.push(DocumentChangeOperation::Edit(TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+        edits: vec![OneOf::Left(edit)],
+    }));
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(operations)),
         ..Default::default()
-    }, &[CodeActionOrCommand::CodeAction(CodeAction {
-        title: String::from("Remove unnecessary backslash"),
-        kind: Some(CodeActionKind::QUICKFIX),
-        is_preferred: Some(true),
-        diagnostics: Some(vec![Diagnostic {
-            message: String::from("bad escape"),
-            range: Range::new(Position::new(0, 1), Position::new(0, 3)),
-            data: Some(serde_json::to_value(CodeActions::RemoveBackslash).unwrap()),
-            ..Default::default()
-        }]),
+    })
+}
+
+/// Wrap the value in an object for each key of the key path.
+fn nest_json_value(key_path: &[String], value: Value) -> Value {
+    key_path.iter().rev().fold(value, |value, key| {
+        Value::Object(Map::from_iter([(key.clone(), value)]))
+    })
+}
+
+/// Get the byte range and the replacement text which set the value at the given key path of a
+/// JSON document, leaving the rest of the document as it is. The new text is indented like the
+/// document.
+fn get_json_edit(text: &str, key_path: &[String], value: Value) -> Option<(usize, usize, String)> {
+    let bytes = text.as_bytes();
+    let indent_unit = text
+        .lines()
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+    let mut object_start = skip_json_whitespace(bytes, 0);
+    for (i, key) in key_path.iter().enumerate() {
+        let (members, object_end) = scan_json_object(bytes, object_start)?;
+        if let Some(member) = members.iter().find(|member| &member.key == key) {
+            if i == key_path.len() - 1 {
+                let indent = line_indent(text, member.key_start);
+                let new_text = to_json_string(&value, indent_unit, indent)?;
+                return Some((member.value_start, member.value_end, new_text));
+            }
+            object_start = member.value_start;
+            continue;
+        }
+        let value = nest_json_value(&key_path[i + 1..], value);
+        let parent_indent = line_indent(text, object_start);
+        let indent = members.last().map_or_else(
+            || parent_indent.to_owned() + indent_unit,
+            |member| line_indent(text, member.key_start).to_owned(),
+        );
+        let member = format!(
+            "{}: {}",
+            serde_json::to_string(key).ok()?,
+            to_json_string(&value, indent_unit, &indent)?
+        );
+        return Some(match members.last() {
+            Some(last) => (
+                last.value_end,
+                last.value_end,
+                format!(",\n{indent}{member}"),
+            ),
+            None => (
+                object_start + 1,
+                object_end,
+                format!("\n{indent}{member}\n{parent_indent}"),
+            ),
+        });
+    }
+    None
+}
+
+/// Serialize the value with the given indentation unit, for a position at the given indentation.
+fn to_json_string(value: &Value, indent_unit: &str, indent: &str) -> Option<String> {
+    let mut buffer = Vec::new();
+    let formatter = PrettyFormatter::with_indent(indent_unit.as_bytes());
+    let mut serializer = Serializer::with_formatter(&mut buffer, formatter);
+    value.serialize(&mut serializer).ok()?;
+    let text = String::from_utf8(buffer).ok()?;
+    Some(text.replace('\n', &format!("\n{indent}")))
+}
+
+/// The leading whitespace of the line containing the given byte offset.
+fn line_indent(text: &str, offset: usize) -> &str {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[line_start..];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// A member of a JSON object, with the byte offsets of its key and value.
+struct JsonMember {
+    key: String,
+    key_start: usize,
+    value_start: usize,
+    value_end: usize,
+}
+
+fn skip_json_whitespace(bytes: &[u8], mut offset: usize) -> usize {
+    while bytes.get(offset).is_some_and(u8::is_ascii_whitespace) {
+        offset += 1;
+    }
+    offset
+}
+
+/// Get the members of the JSON object starting at the given byte offset, and the offset of its
+/// closing brace.
+fn scan_json_object(bytes: &[u8], start: usize) -> Option<(Vec<JsonMember>, usize)> {
+    if bytes.get(start) != Some(&b'{') {
+        return None;
+    }
+    let mut members = Vec::new();
+    let mut offset = skip_json_whitespace(bytes, start + 1);
+    if bytes.get(offset) == Some(&b'}') {
+        return Some((members, offset));
+    }
+    loop {
+        let key_start = offset;
+        let key_end = scan_json_value(bytes, key_start)?;
+        let key = serde_json::from_slice(&bytes[key_start..key_end]).ok()?;
+        offset = skip_json_whitespace(bytes, key_end);
+        if bytes.get(offset) != Some(&b':') {
+            return None;
+        }
+        let value_start = skip_json_whitespace(bytes, offset + 1);
+        let value_end = scan_json_value(bytes, value_start)?;
+        members.push(JsonMember {
+            key,
+            key_start,
+            value_start,
+            value_end,
+        });
+        offset = skip_json_whitespace(bytes, value_end);
+        match bytes.get(offset)? {
+            b',' => offset = skip_json_whitespace(bytes, offset + 1),
+            b'}' => return Some((members, offset)),
+            _ => return None,
+        }
+    }
+}
+
+/// Get the byte offset just past the JSON value starting at the given byte offset.
+fn scan_json_value(bytes: &[u8], start: usize) -> Option<usize> {
+    match bytes.get(start)? {
+        b'"' => {
+            let mut offset = start + 1;
+            loop {
+                match bytes.get(offset)? {
+                    b'\\' => offset += 2,
+                    b'"' => return Some(offset + 1),
+                    _ => offset += 1,
+                }
+            }
+        }
+        b'{' => scan_json_object(bytes, start).map(|(_, end)| end + 1),
+        b'[' => {
+            let mut offset = skip_json_whitespace(bytes, start + 1);
+            if bytes.get(offset) == Some(&b']') {
+                return Some(offset + 1);
+            }
+            loop {
+                offset = skip_json_whitespace(bytes, scan_json_value(bytes, offset)?);
+                match bytes.get(offset)? {
+                    b',' => offset = skip_json_whitespace(bytes, offset + 1),
+                    b']' => return Some(offset + 1),
+                    _ => return None,
+                }
+            }
+        }
+        _ => {
+            let end = bytes[start..]
+                .iter()
+                .position(|byte| matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace())
+                .map_or(bytes.len(), |i| start + i);
+            (end > start).then_some(end)
+        }
+    }
+}
+
+/// Whether code actions of the given kind were requested.
+fn is_requested(only: Option<&[CodeActionKind]>, kind: &CodeActionKind) -> bool {
+    only.is_none_or(|only| {
+        only.iter().any(|requested| {
+            kind.as_str() == requested.as_str()
+                || kind
+                    .as_str()
+                    .strip_prefix(requested.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    })
+}
+
+/// Get an action which fixes every diagnostic of the document which has a preferred fix.
+async fn get_fix_all_action<C: LspClient>(
+    backend: &Backend<C>,
+    uri: &Url,
+) -> Option<CodeActionOrCommand> {
+    let document = backend.document_map.get(uri).map(|doc| doc.clone())?;
+    let language_data = document
+        .language_name
+        .as_ref()
+        .and_then(|name| backend.language_map.get(name).map(|data| data.clone()));
+    let ignore_missing_language = false;
+    let cache = true;
+    let diagnostics = get_diagnostics(
+        uri,
+        &backend.document_map,
+        document.clone(),
+        language_data,
+        backend.options.clone(),
+        &backend.parser_names_map,
+        ignore_missing_language,
+        cache,
+    )
+    .await;
+    let edits = get_fix_all_edits(&document.tree, &document.rope, diagnostics, uri)?;
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Fix all auto-fixable problems"),
+        kind: Some(CodeActionKind::SOURCE_FIX_ALL),
         edit: Some(WorkspaceEdit {
-            changes: Some(HashMap::from([(
-                TEST_URI.clone(),
-                    vec![TextEdit {
-                        range: Range::new(Position::new(0, 1), Position::new(0, 2)),
-                        new_text: String::new()
-                    }]
-            )])),
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+fn refactor_action(title: String, uri: &Url, edits: Vec<TextEdit>) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Get the innermost node of the given kind which contains the given node.
+fn get_ancestor_of_kind<'a>(node: Node<'a>, kind: &str) -> Option<Node<'a>> {
+    let mut node = node;
+    while node.kind() != kind {
+        node = node.parent()?;
+    }
+    Some(node)
+}
+
+/// Get the text of a pattern without its trailing quantifier and captures, along with the text of
+/// those.
+fn split_pattern_suffix(node: Node, rope: &Rope) -> (String, String) {
+    let text = node.text(rope);
+    let core_end = node
+        .named_children(&mut node.walk())
+        .find(|child| {
+            child.kind() == "capture" || node.child_by_field_name("quantifier") == Some(*child)
+        })
+        .map_or_else(|| node.end_byte(), |child| child.start_byte());
+    let core = text[..core_end - node.start_byte()].trim_end();
+    (core.to_owned(), text[core.len()..].to_owned())
+}
+
+/// Get the refactorings which are available for the given range of a document.
+fn get_refactor_actions(
+    tree: &Tree,
+    rope: &Rope,
+    uri: &Url,
+    range: Range,
+    string_argument_style: &StringArgumentStyle,
+) -> Vec<CodeActionOrCommand> {
+    let start = range.start.to_ts_point(rope);
+    let end = range.end.to_ts_point(rope);
+    let root = tree.root_node();
+    let Some(node) = root.named_descendant_for_point_range(start, start) else {
+        return Vec::new();
+    };
+    let mut actions = Vec::new();
+    actions.extend(sort_alternation_action(node, rope, uri));
+    actions.extend(sort_any_of_action(node, rope, uri));
+    if start != end {
+        actions.extend(wrap_actions(tree, rope, uri, range));
+    }
+    actions.extend(merge_patterns_action(node, rope, uri));
+    actions.extend(split_alternation_action(node, rope, uri));
+    actions.extend(convert_argument_action(
+        node,
+        rope,
+        uri,
+        string_argument_style,
+    ));
+    actions
+}
+
+/// Replace a sequence of sibling nodes with their texts sorted by the given key and deduplicated,
+/// keeping the whitespace which separated the first two.
+fn sorted_nodes_edit<F: Fn(&Node) -> String>(
+    nodes: &[Node],
+    rope: &Rope,
+    key: F,
+) -> Option<TextEdit> {
+    let (first, last) = (nodes.first()?, nodes.last()?);
+    let separator = nodes.get(1).map_or_else(String::new, |second| {
+        rope.byte_slice(first.end_byte()..second.start_byte())
+            .to_string()
+    });
+    let mut sorted: Vec<_> = nodes
+        .iter()
+        .map(|node| (key(node), node.text(rope)))
+        .collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    sorted.dedup_by(|a, b| a.0 == b.0);
+    let is_unchanged = sorted.len() == nodes.len()
+        && sorted
+            .iter()
+            .zip(nodes)
+            .all(|((_, text), node)| *text == node.text(rope));
+    if is_unchanged {
+        return None;
+    }
+    Some(TextEdit {
+        new_text: sorted
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<_>>()
+            .join(&separator),
+        range: Range::new(first.lsp_range(rope).start, last.lsp_range(rope).end),
+    })
+}
+
+fn sort_alternation_action(node: Node, rope: &Rope, uri: &Url) -> Option<CodeActionOrCommand> {
+    let list = get_ancestor_of_kind(node, "list")?;
+    let mut members = Vec::new();
+    for child in list.named_children(&mut list.walk()) {
+        match child.kind() {
+            // Comments cannot be kept in place when the members are reordered
+            "comment" => return None,
+            "capture" | "quantifier" => break,
+            _ => members.push(child),
+        }
+    }
+    let edit = sorted_nodes_edit(&members, rope, |node| node.text(rope))?;
+    Some(refactor_action(
+        String::from("Sort and deduplicate alternation members"),
+        uri,
+        vec![edit],
+    ))
+}
+
+fn sort_any_of_action(node: Node, rope: &Rope, uri: &Url) -> Option<CodeActionOrCommand> {
+    let predicate = get_ancestor_of_kind(node, "predicate")?;
+    let name = predicate
+        .children_by_field_name("name", &mut predicate.walk())
+        .find(|node| node.kind() == "identifier")?
+        .text(rope);
+    if !name.ends_with("any-of") {
+        return None;
+    }
+    let params = predicate.child_by_field_name("parameters")?;
+    let values: Vec<_> = params
+        .named_children(&mut params.walk())
+        .skip_while(|param| param.kind() == "capture")
+        .collect();
+    if values.iter().any(|value| value.kind() == "capture") {
+        return None;
+    }
+    let edit = sorted_nodes_edit(&values, rope, |node| {
+        let text = node.text(rope);
+        if node.kind() == "string" {
+            unescape_string_content(&text[1..text.len() - 1]).0
+        } else {
+            text
+        }
+    })?;
+    Some(refactor_action(
+        format!("Sort and deduplicate `#{name}?` values"),
+        uri,
+        vec![edit],
+    ))
+}
+
+/// Whether a node can be wrapped in a grouping or alternation.
+fn is_wrappable(node: &Node) -> bool {
+    matches!(
+        node.kind(),
+        "named_node" | "anonymous_node" | "grouping" | "list" | "missing_node" | "field_definition"
+    )
+}
+
+fn wrap_actions(tree: &Tree, rope: &Rope, uri: &Url, range: Range) -> Vec<CodeActionOrCommand> {
+    let start = range.start.to_ts_point(rope);
+    let end = range.end.to_ts_point(rope);
+    let Some(mut ancestor) = tree
+        .root_node()
+        .named_descendant_for_point_range(start, end)
+    else {
+        return Vec::new();
+    };
+    // Move up to the first node which is wholly selected, or whose children are
+    let selection = start..=end;
+    while !is_wrappable(&ancestor)
+        && ancestor.kind() != "program"
+        && let Some(parent) = ancestor.parent()
+        && selection.contains(&ancestor.start_position())
+        && selection.contains(&ancestor.end_position())
+    {
+        ancestor = parent;
+    }
+    let nodes: Vec<_> = if is_wrappable(&ancestor)
+        && selection.contains(&ancestor.start_position())
+        && selection.contains(&ancestor.end_position())
+    {
+        vec![ancestor]
+    } else {
+        ancestor
+            .named_children(&mut ancestor.walk())
+            .filter(|child| child.end_position() > start && child.start_position() < end)
+            .collect()
+    };
+    let (Some(first), Some(last)) = (nodes.first(), nodes.last()) else {
+        return Vec::new();
+    };
+    if !nodes.iter().all(is_wrappable)
+        || first.start_position() < start
+        || last.end_position() > end
+    {
+        return Vec::new();
+    }
+    let text = rope
+        .byte_slice(first.start_byte()..last.end_byte())
+        .to_string();
+    let range = Range::new(first.lsp_range(rope).start, last.lsp_range(rope).end);
+    [("grouping", '(', ')'), ("alternation", '[', ']')]
+        .into_iter()
+        .map(|(name, open, close)| {
+            refactor_action(
+                format!("Wrap in {name}"),
+                uri,
+                vec![TextEdit {
+                    new_text: format!("{open}{text}{close}"),
+                    range,
+                }],
+            )
+        })
+        .collect()
+}
+
+/// Get the leaves of a pattern, excluding comments.
+fn get_pattern_leaves<'a>(node: Node<'a>, leaves: &mut Vec<Node<'a>>) {
+    if node.kind() == "comment" {
+        return;
+    }
+    if node.child_count() == 0 {
+        leaves.push(node);
+        return;
+    }
+    for child in node.children(&mut node.walk()) {
+        get_pattern_leaves(child, leaves);
+    }
+}
+
+/// Get the named node whose kind is the only difference between two patterns, if there is one.
+fn get_node_kind_difference<'a>(
+    pattern: Node<'a>,
+    other: Node,
+    rope: &Rope,
+) -> Option<(usize, Node<'a>)> {
+    let (mut leaves, mut other_leaves) = (Vec::new(), Vec::new());
+    get_pattern_leaves(pattern, &mut leaves);
+    get_pattern_leaves(other, &mut other_leaves);
+    if leaves.len() != other_leaves.len() {
+        return None;
+    }
+    let mut differences =
+        leaves
+            .iter()
+            .zip(&other_leaves)
+            .enumerate()
+            .filter(|(_, (leaf, other_leaf))| {
+                leaf.kind() != other_leaf.kind() || leaf.text(rope) != other_leaf.text(rope)
+            });
+    let (index, (leaf, _)) = differences.next()?;
+    if differences.next().is_some() || leaf.kind() != "identifier" {
+        return None;
+    }
+    let named_node = leaf.parent().filter(|parent| {
+        parent.kind() == "named_node" && parent.child_by_field_name("name") == Some(*leaf)
+    })?;
+    Some((index, named_node))
+}
+
+fn merge_patterns_action(node: Node, rope: &Rope, uri: &Url) -> Option<CodeActionOrCommand> {
+    let mut pattern = node;
+    while pattern.parent()?.kind() != "program" {
+        pattern = pattern.parent()?;
+    }
+    if pattern.kind() == "comment" {
+        return None;
+    }
+    // Find the adjacent patterns which only differ from this one in the kind of the same node
+    let mut difference: Option<(usize, Node)> = None;
+    let mut group = vec![pattern];
+    for forward in [false, true] {
+        let mut current = pattern;
+        loop {
+            let next = if forward {
+                current.next_named_sibling()
+            } else {
+                current.prev_named_sibling()
+            };
+            let Some(next) = next.filter(|next| next.kind() != "comment") else {
+                break;
+            };
+            let Some((index, named_node)) = get_node_kind_difference(pattern, next, rope) else {
+                break;
+            };
+            if difference.is_some_and(|(other_index, _)| other_index != index) {
+                break;
+            }
+            difference = Some((index, named_node));
+            if forward {
+                group.push(next);
+            } else {
+                group.insert(0, next);
+            }
+            current = next;
+        }
+    }
+    let (index, named_node) = difference?;
+    let (_, suffix) = split_pattern_suffix(named_node, rope);
+    let variants = group
+        .iter()
+        .map(|member| {
+            let mut leaves = Vec::new();
+            get_pattern_leaves(*member, &mut leaves);
+            let variant = leaves[index].parent()?;
+            Some(split_pattern_suffix(variant, rope).0)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let new_text = format!(
+        "{}[{}]{suffix}{}",
+        rope.byte_slice(pattern.start_byte()..named_node.start_byte()),
+        variants.join(" "),
+        rope.byte_slice(named_node.end_byte()..pattern.end_byte()),
+    );
+    let (first, last) = (group.first()?, group.last()?);
+    Some(refactor_action(
+        String::from("Merge patterns into an alternation"),
+        uri,
+        vec![TextEdit {
+            new_text,
+            range: Range::new(first.lsp_range(rope).start, last.lsp_range(rope).end),
+        }],
+    ))
+}
+
+fn split_alternation_action(node: Node, rope: &Rope, uri: &Url) -> Option<CodeActionOrCommand> {
+    let list = get_ancestor_of_kind(node, "list")?;
+    // Alternations cannot be split out of quantified patterns without changing their meaning
+    let mut pattern = list;
+    loop {
+        if pattern.child_by_field_name("quantifier").is_some() {
+            return None;
+        }
+        let parent = pattern.parent()?;
+        if parent.kind() == "program" {
+            break;
+        }
+        pattern = parent;
+    }
+    let (_, suffix) = split_pattern_suffix(list, rope);
+    let members: Vec<_> = list
+        .named_children(&mut list.walk())
+        .take_while(|child| child.kind() != "capture")
+        .filter(|child| child.kind() != "comment")
+        .collect();
+    if members.len() < 2 {
+        return None;
+    }
+    let prefix = rope.byte_slice(pattern.start_byte()..list.start_byte());
+    let postfix = rope.byte_slice(list.end_byte()..pattern.end_byte());
+    let new_text = members
+        .iter()
+        .map(|member| format!("{prefix}{}{suffix}{postfix}", member.text(rope)))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Some(refactor_action(
+        String::from("Split alternation into separate patterns"),
+        uri,
+        vec![TextEdit {
+            new_text,
+            range: pattern.lsp_range(rope),
+        }],
+    ))
+}
+
+fn convert_argument_action(
+    node: Node,
+    rope: &Rope,
+    uri: &Url,
+    string_argument_style: &StringArgumentStyle,
+) -> Option<CodeActionOrCommand> {
+    let argument = match node.kind() {
+        "string_content" => node.parent()?,
+        "escape_sequence" => node.parent()?.parent()?,
+        _ => node,
+    };
+    if argument.parent()?.kind() != "parameters" {
+        return None;
+    }
+    let text = argument.text(rope);
+    let (title, new_text) = match argument.kind() {
+        "string" if *string_argument_style != StringArgumentStyle::PreferQuoted => {
+            let content = &text[1..text.len() - 1];
+            if argument
+                .named_child(0)
+                .is_some_and(|c| c.named_child_count() > 0)
+                || !IDENTIFIER_REGEX.is_match(content)
+            {
+                return None;
+            }
+            ("Convert to unquoted string", content.to_owned())
+        }
+        "identifier" if *string_argument_style != StringArgumentStyle::PreferUnquoted => {
+            ("Convert to quoted string", format!("\"{text}\""))
+        }
+        _ => return None,
+    };
+    Some(refactor_action(
+        String::from(title),
+        uri,
+        vec![TextEdit {
+            new_text,
+            range: argument.lsp_range(rope),
+        }],
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::{Value, json};
+    use tower_lsp::lsp_types::{
+        CodeAction, CodeActionContext, CodeActionKind, CreateFile, Diagnostic,
+        DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
+        Position, Range, ResourceOp, TextDocumentEdit, TextDocumentIdentifier, TextEdit, Url,
+        WorkspaceEdit,
+    };
+    use tower_lsp::lsp_types::{
+        CodeActionOrCommand, CodeActionParams, PartialResultParams, WorkDoneProgressParams,
+        request::CodeActionRequest,
+    };
+
+    use ts_query_ls::{DiagnosticOptions, StringArgumentStyle};
+
+    use crate::{
+        Options,
+        handlers::{
+            code_action::{CodeActions, get_json_edit},
+            diagnostic::DiagnosticCode,
+        },
+        test_helpers::helpers::{TEST_URI, TestEdit, TestService, initialize_server},
+    };
+
+    /// An action creating the configuration file of the test workspace with the given contents.
+    fn add_to_config_action(
+        title: &str,
+        diagnostic: Diagnostic,
+        contents: &str,
+    ) -> CodeActionOrCommand {
+        let config_uri = Url::from_file_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/test_workspace/.tsqueryrc.json"
+        ))
+        .unwrap();
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: String::from(title),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![diagnostic]),
+            edit: Some(WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Operations(vec![
+                    DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                        uri: config_uri.clone(),
+                        options: None,
+                        annotation_id: None,
+                    })),
+                    DocumentChangeOperation::Edit(TextDocumentEdit {
+                        text_document: OptionalVersionedTextDocumentIdentifier {
+                            uri: config_uri,
+                            version: None,
+                        },
+                        edits: vec![OneOf::Left(TextEdit {
+                            range: Range::default(),
+                            new_text: String::from(contents),
+                        })],
+                    }),
+                ])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[rstest]
+    #[case(r#""\p" @_somecap"#, Options::default(), Position::new(0, 2), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("bad escape"),
+            range: Range::new(Position::new(0, 1), Position::new(0, 3)),
+            data: Some(serde_json::to_value(CodeActions::RemoveBackslash).unwrap()),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Remove unnecessary backslash"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(true),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("bad escape"),
+            range: Range::new(Position::new(0, 1), Position::new(0, 3)),
+            data: Some(serde_json::to_value(CodeActions::RemoveBackslash).unwrap()),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                    vec![TextEdit {
+                        range: Range::new(Position::new(0, 1), Position::new(0, 2)),
+                        new_text: String::new()
+                    }]
+            )])),
             ..Default::default()
         }),
         ..Default::default()
@@ -267,6 +1470,7 @@ mod test {
             data: Some(serde_json::to_value(CodeActions::PrefixUnderscore).unwrap()),
             ..Default::default()
         }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
         ..Default::default()
     }, &[CodeActionOrCommand::CodeAction(CodeAction {
         title: String::from("Prefix capture name with underscore"),
@@ -301,6 +1505,7 @@ mod test {
             data: Some(serde_json::to_value(CodeActions::Trim).unwrap()),
             ..Default::default()
         }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
         ..Default::default()
     }, &[CodeActionOrCommand::CodeAction(CodeAction {
         title: String::from("Trim quotations from string"),
@@ -324,6 +1529,393 @@ mod test {
         }),
         ..Default::default()
     })])]
+    #[case(r#"((comment) @c
+  (#lua-match? @c "^foo%.$"))"#, Options::default(), Position::new(1, 20), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Fixed string"),
+            range: Range::new(Position::new(1, 18), Position::new(1, 27)),
+            data: Some(CodeActions::ReplaceWithEq.with_arguments(&[
+                String::from("eq"),
+                String::from("\"foo.\""),
+            ])),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Replace with `#eq?`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(true),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("Fixed string"),
+            range: Range::new(Position::new(1, 18), Position::new(1, 27)),
+            data: Some(CodeActions::ReplaceWithEq.with_arguments(&[
+                String::from("eq"),
+                String::from("\"foo.\""),
+            ])),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                    vec![TextEdit {
+                        range: Range::new(Position::new(1, 4), Position::new(1, 13)),
+                        new_text: String::from("eq")
+                    }, TextEdit {
+                        range: Range::new(Position::new(1, 18), Position::new(1, 27)),
+                        new_text: String::from("\"foo.\"")
+                    }]
+            ), ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })])]
+    #[case(r#"((comment)+ @c (#not-eq? @c "x"))"#, Options::default(), Position::new(0, 26), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Quantified capture"),
+            range: Range::new(Position::new(0, 25), Position::new(0, 27)),
+            data: Some(CodeActions::UseAnyPredicate.with_arguments(&[String::from("not-any-eq")])),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Use `#not-any-eq?`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(true),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("Quantified capture"),
+            range: Range::new(Position::new(0, 25), Position::new(0, 27)),
+            data: Some(CodeActions::UseAnyPredicate.with_arguments(&[String::from("not-any-eq")])),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                    vec![TextEdit {
+                        range: Range::new(Position::new(0, 17), Position::new(0, 23)),
+                        new_text: String::from("not-any-eq")
+                    }]
+            ), ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })])]
+    #[case(r#"((x) @c (#any-of? @c "a"))"#, Options::default(), Position::new(0, 12), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Single value"),
+            range: Range::new(Position::new(0, 8), Position::new(0, 25)),
+            data: Some(CodeActions::RenamePredicate.with_arguments(&[String::from("eq")])),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Rename to `#eq?`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(true),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("Single value"),
+            range: Range::new(Position::new(0, 8), Position::new(0, 25)),
+            data: Some(CodeActions::RenamePredicate.with_arguments(&[String::from("eq")])),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                    vec![TextEdit {
+                        range: Range::new(Position::new(0, 10), Position::new(0, 16)),
+                        new_text: String::from("eq")
+                    }]
+            ), ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })])]
+    #[case(r#"((x) @c (#any-of? @c "a" "a"))"#, Options::default(), Position::new(0, 26), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Duplicate value"),
+            range: Range::new(Position::new(0, 25), Position::new(0, 28)),
+            data: Some(CodeActions::RemoveDuplicateValue.into()),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Remove duplicate value"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(true),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("Duplicate value"),
+            range: Range::new(Position::new(0, 25), Position::new(0, 28)),
+            data: Some(CodeActions::RemoveDuplicateValue.into()),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                    vec![TextEdit {
+                        range: Range::new(Position::new(0, 25), Position::new(0, 28)),
+                        new_text: String::new()
+                    }]
+            ), ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })])]
+    #[case(r"(feild) @c", Options::default(), Position::new(0, 3), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Invalid name"),
+            range: Range::new(Position::new(0, 1), Position::new(0, 6)),
+            data: Some(CodeActions::Replace.with_arguments(&[String::from("field"), String::from("fields")])),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Replace with `field`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(false),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("Invalid name"),
+            range: Range::new(Position::new(0, 1), Position::new(0, 6)),
+            data: Some(CodeActions::Replace.with_arguments(&[String::from("field"), String::from("fields")])),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                vec![TextEdit {
+                    range: Range::new(Position::new(0, 1), Position::new(0, 6)),
+                    new_text: String::from("field")
+                }]
+            ), ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }), CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Replace with `fields`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(false),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("Invalid name"),
+            range: Range::new(Position::new(0, 1), Position::new(0, 6)),
+            data: Some(CodeActions::Replace.with_arguments(&[String::from("field"), String::from("fields")])),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                vec![TextEdit {
+                    range: Range::new(Position::new(0, 1), Position::new(0, 6)),
+                    new_text: String::from("fields")
+                }]
+            ), ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })])]
+    #[case(r#"((comment) @varr (#eq? @varr "x"))"#, Options::default(), Position::new(0, 13), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Invalid name"),
+            range: Range::new(Position::new(0, 11), Position::new(0, 16)),
+            data: Some(CodeActions::RenameCapture.with_arguments(&[String::from("@var")])),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Rename capture to `@var`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(true),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("Invalid name"),
+            range: Range::new(Position::new(0, 11), Position::new(0, 16)),
+            data: Some(CodeActions::RenameCapture.with_arguments(&[String::from("@var")])),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                vec![TextEdit {
+                    range: Range::new(Position::new(0, 11), Position::new(0, 16)),
+                    new_text: String::from("@var")
+                }, TextEdit {
+                    range: Range::new(Position::new(0, 23), Position::new(0, 28)),
+                    new_text: String::from("@var")
+                }]
+            ), ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }), CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Prefix capture name with underscore"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(false),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("Invalid name"),
+            range: Range::new(Position::new(0, 11), Position::new(0, 16)),
+            data: Some(CodeActions::RenameCapture.with_arguments(&[String::from("@var")])),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                vec![TextEdit {
+                    range: Range::new(Position::new(0, 12), Position::new(0, 12)),
+                    new_text: String::from("_")
+                }, TextEdit {
+                    range: Range::new(Position::new(0, 24), Position::new(0, 24)),
+                    new_text: String::from("_")
+                }]
+            ), ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })])]
+    #[case(r#"[((comment) @c (#eq? @c "a")) ((comment) @c (#eq? @c "b"))]"#, Options::default(), Position::new(0, 1), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Mergeable"),
+            range: Range::new(Position::new(0, 0), Position::new(0, 63)),
+            data: Some(CodeActions::MergeIntoAnyOf.with_arguments(&[String::from(
+                r#"((comment) @c (#any-of? @c "a" "b"))"#,
+            )])),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Merge into one pattern using `#any-of?`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(true),
+        diagnostics: Some(vec![Diagnostic {
+            message: String::from("Mergeable"),
+            range: Range::new(Position::new(0, 0), Position::new(0, 63)),
+            data: Some(CodeActions::MergeIntoAnyOf.with_arguments(&[String::from(
+                r#"((comment) @c (#any-of? @c "a" "b"))"#,
+            )])),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                TEST_URI.clone(),
+                    vec![TextEdit {
+                        range: Range::new(Position::new(0, 0), Position::new(0, 63)),
+                        new_text: String::from(r#"((comment) @c (#any-of? @c "a" "b"))"#)
+                    }]
+            ), ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })])]
+    #[case(r"(identifier) @foo", Options::default(), Position::new(0, 15), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Invalid capture name"),
+            range: Range::new(Position::new(0, 13), Position::new(0, 17)),
+            code: DiagnosticCode::InvalidCaptureName.into(),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[add_to_config_action(
+        "Add `@foo` to valid_captures.test",
+        Diagnostic {
+            message: String::from("Invalid capture name"),
+            range: Range::new(Position::new(0, 13), Position::new(0, 17)),
+            code: DiagnosticCode::InvalidCaptureName.into(),
+            ..Default::default()
+        },
+        r#"{
+  "valid_captures": {
+    "test": {
+      "foo": ""
+    }
+  }
+}
+"#,
+    )])]
+    #[case(r#"((identifier) @x (#foo? @x "bar"))"#, Options::default(), Position::new(0, 20), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Unrecognized predicate"),
+            range: Range::new(Position::new(0, 19), Position::new(0, 22)),
+            code: DiagnosticCode::UnrecognizedPredicate.into(),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[add_to_config_action(
+        "Add `#foo?` to valid_predicates",
+        Diagnostic {
+            message: String::from("Unrecognized predicate"),
+            range: Range::new(Position::new(0, 19), Position::new(0, 22)),
+            code: DiagnosticCode::UnrecognizedPredicate.into(),
+            ..Default::default()
+        },
+        r#"{
+  "valid_predicates": {
+    "foo": {
+      "description": "",
+      "parameters": [
+        {
+          "type": "capture"
+        },
+        {
+          "type": "string"
+        }
+      ]
+    }
+  }
+}
+"#,
+    )])]
+    #[case(r#"((identifier) @x (#not-any-foo? @x "bar"))"#, Options::default(), Position::new(0, 20), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Unrecognized predicate"),
+            range: Range::new(Position::new(0, 19), Position::new(0, 30)),
+            code: DiagnosticCode::UnrecognizedPredicate.into(),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[add_to_config_action(
+        "Add `#foo?` to valid_predicates",
+        Diagnostic {
+            message: String::from("Unrecognized predicate"),
+            range: Range::new(Position::new(0, 19), Position::new(0, 30)),
+            code: DiagnosticCode::UnrecognizedPredicate.into(),
+            ..Default::default()
+        },
+        r#"{
+  "valid_predicates": {
+    "foo": {
+      "any": true,
+      "description": "",
+      "parameters": [
+        {
+          "type": "capture"
+        },
+        {
+          "type": "string"
+        }
+      ]
+    }
+  }
+}
+"#,
+    )])]
+    #[case(r"(identifier) @foo", Options {
+        hierarchical_captures: true,
+        ..Default::default()
+    }, Position::new(0, 15), CodeActionContext {
+        diagnostics: vec![Diagnostic {
+            message: String::from("Invalid capture name"),
+            range: Range::new(Position::new(0, 13), Position::new(0, 17)),
+            code: DiagnosticCode::InvalidCaptureName.into(),
+            ..Default::default()
+        }],
+        only: Some(vec![CodeActionKind::QUICKFIX]),
+        ..Default::default()
+    }, &[])]
     #[tokio::test(flavor = "current_thread")]
     async fn server_code_action(
         #[case] source: &str,
@@ -349,7 +1941,208 @@ mod test {
             .await;
 
         // Assert
-        let expected_code_actions = Some(expected_code_actions.to_vec());
+        let expected_code_actions =
+            (!expected_code_actions.is_empty()).then(|| expected_code_actions.to_vec());
+        assert_eq!(expected_code_actions, code_actions);
+    }
+
+    #[rstest]
+    #[case(
+        "{\n    \"valid_captures\": {\n        \"test\": {\n            \"bar\": \"\"\n        }\n    }\n}\n",
+        &["valid_captures", "test", "foo"],
+        json!(""),
+        "{\n    \"valid_captures\": {\n        \"test\": {\n            \"bar\": \"\",\n            \"foo\": \"\"\n        }\n    }\n}\n"
+    )]
+    #[case(
+        "{\n  \"valid_predicates\": {}\n}\n",
+        &["valid_predicates", "foo"],
+        json!({ "description": "" }),
+        "{\n  \"valid_predicates\": {\n    \"foo\": {\n      \"description\": \"\"\n    }\n  }\n}\n"
+    )]
+    #[case(
+        "{\n\t\"parser_aliases\": {}\n}",
+        &["valid_directives", "foo"],
+        json!({ "description": "" }),
+        "{\n\t\"parser_aliases\": {},\n\t\"valid_directives\": {\n\t\t\"foo\": {\n\t\t\t\"description\": \"\"\n\t\t}\n\t}\n}"
+    )]
+    #[case(
+        "{\n  \"valid_predicates\": {\n    \"foo\": { \"description\": \"Foo\" }\n  }\n}\n",
+        &["valid_predicates", "foo"],
+        json!({ "any": true }),
+        "{\n  \"valid_predicates\": {\n    \"foo\": {\n      \"any\": true\n    }\n  }\n}\n"
+    )]
+    #[case("{}", &["a"], json!("b"), "{\n  \"a\": \"b\"\n}")]
+    fn json_edit(
+        #[case] text: &str,
+        #[case] key_path: &[&str],
+        #[case] value: Value,
+        #[case] expected: &str,
+    ) {
+        // Arrange
+        let key_path: Vec<_> = key_path.iter().map(ToString::to_string).collect();
+
+        // Act
+        let (start, end, new_text) = get_json_edit(text, &key_path, value).unwrap();
+
+        // Assert
+        let actual = format!("{}{new_text}{}", &text[..start], &text[end..]);
+        assert_eq!(expected, actual);
+    }
+
+    fn refactor(title: &str, edits: &[(Range, &str)]) -> CodeActionOrCommand {
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: String::from(title),
+            kind: Some(CodeActionKind::REFACTOR_REWRITE),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(
+                    TEST_URI.clone(),
+                    edits
+                        .iter()
+                        .map(|(range, new_text)| TextEdit {
+                            range: *range,
+                            new_text: String::from(*new_text),
+                        })
+                        .collect(),
+                )])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[rstest]
+    #[case(r"[(b) (a) (b)] @x", Options::default(), Range::new(Position::new(0, 2), Position::new(0, 2)), &[
+        refactor("Sort and deduplicate alternation members", &[(
+            Range::new(Position::new(0, 1), Position::new(0, 12)), "(a) (b)"
+        )]),
+        refactor("Split alternation into separate patterns", &[(
+            Range::new(Position::new(0, 0), Position::new(0, 16)), "(b) @x\n\n(a) @x\n\n(b) @x"
+        )]),
+    ])]
+    #[case(r#"((x) @c (#any-of? @c "b" a "a"))"#, Options::default(), Range::new(Position::new(0, 11), Position::new(0, 11)), &[
+        refactor("Sort and deduplicate `#any-of?` values", &[(
+            Range::new(Position::new(0, 21), Position::new(0, 30)), r#"a "b""#
+        )]),
+    ])]
+    #[case(r"(a) (b)", Options::default(), Range::new(Position::new(0, 0), Position::new(0, 7)), &[
+        refactor("Wrap in grouping", &[(
+            Range::new(Position::new(0, 0), Position::new(0, 7)), "((a) (b))"
+        )]),
+        refactor("Wrap in alternation", &[(
+            Range::new(Position::new(0, 0), Position::new(0, 7)), "[(a) (b)]"
+        )]),
+        refactor("Merge patterns into an alternation", &[(
+            Range::new(Position::new(0, 0), Position::new(0, 7)), "[(a) (b)]"
+        )]),
+    ])]
+    #[case(r"((a (c) @c) @x)
+((b (c) @c) @x)", Options::default(), Range::new(Position::new(1, 2), Position::new(1, 2)), &[
+        refactor("Merge patterns into an alternation", &[(
+            Range::new(Position::new(0, 0), Position::new(1, 15)), "([(a (c) @c) (b (c) @c)] @x)"
+        )]),
+    ])]
+    #[case(r#"((x) @c (#eq? @c "foo"))"#, Options::default(), Range::new(Position::new(0, 18), Position::new(0, 18)), &[
+        refactor("Convert to unquoted string", &[(
+            Range::new(Position::new(0, 17), Position::new(0, 22)), "foo"
+        )]),
+    ])]
+    #[case(r"((x) @c (#eq? @c foo))", Options {
+        diagnostic_options: DiagnosticOptions {
+            string_argument_style: StringArgumentStyle::PreferUnquoted,
+            ..Default::default()
+        },
+        ..Default::default()
+    }, Range::new(Position::new(0, 18), Position::new(0, 18)), &[])]
+    #[tokio::test(flavor = "current_thread")]
+    async fn server_refactor_code_action(
+        #[case] source: &str,
+        #[case] options: Options,
+        #[case] range: Range,
+        #[case] expected_code_actions: &[CodeActionOrCommand],
+    ) {
+        // Arrange
+        let mut service = initialize_server(&[(TEST_URI.clone(), source)], &options).await;
+
+        // Act
+        let code_actions = service
+            .request::<CodeActionRequest>(CodeActionParams {
+                context: CodeActionContext {
+                    only: Some(vec![CodeActionKind::REFACTOR]),
+                    ..Default::default()
+                },
+                range,
+                text_document: TextDocumentIdentifier {
+                    uri: TEST_URI.clone(),
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await;
+
+        // Assert
+        let expected_code_actions =
+            (!expected_code_actions.is_empty()).then(|| expected_code_actions.to_vec());
+        assert_eq!(expected_code_actions, code_actions);
+    }
+
+    #[rstest]
+    #[case(r#"((comment) @c (#eq? @c "foo"))"#, &[
+        TestEdit::new("", (0, 23), (0, 24)),
+        TestEdit::new("", (0, 27), (0, 28)),
+    ])]
+    // The fixes within the duplicate pattern are dropped, since it is removed
+    #[case(
+        r#"((comment) @c (#any-of? @c "foo"))
+((comment) @c (#any-of? @c "foo"))
+"#,
+        &[
+            TestEdit::new("eq", (0, 16), (1, 22)),
+            TestEdit::new("", (1, 27), (1, 28)),
+            TestEdit::new("", (1, 31), (1, 32)),
+            TestEdit::new("\n", (2, 0), (2, 0)),
+        ]
+    )]
+    #[tokio::test(flavor = "current_thread")]
+    async fn server_fix_all_code_action(#[case] source: &str, #[case] edits: &[TestEdit]) {
+        // Arrange
+        let options = Options {
+            diagnostic_options: DiagnosticOptions {
+                string_argument_style: StringArgumentStyle::PreferUnquoted,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut service = initialize_server(&[(TEST_URI.clone(), source)], &options).await;
+
+        // Act
+        let code_actions = service
+            .request::<CodeActionRequest>(CodeActionParams {
+                context: CodeActionContext {
+                    only: Some(vec![CodeActionKind::SOURCE_FIX_ALL]),
+                    ..Default::default()
+                },
+                range: Range::default(),
+                text_document: TextDocumentIdentifier {
+                    uri: TEST_URI.clone(),
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await;
+
+        // Assert
+        let expected_code_actions = Some(vec![CodeActionOrCommand::CodeAction(CodeAction {
+            title: String::from("Fix all auto-fixable problems"),
+            kind: Some(CodeActionKind::SOURCE_FIX_ALL),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(
+                    TEST_URI.clone(),
+                    edits.iter().map(Into::into).collect(),
+                )])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })]);
         assert_eq!(expected_code_actions, code_actions);
     }
 }
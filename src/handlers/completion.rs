@@ -1,5 +1,6 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
+use ropey::Rope;
 use streaming_iterator::StreamingIterator;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::{
@@ -7,14 +8,18 @@ use tower_lsp::lsp_types::{
     Documentation, InsertTextFormat, MarkupContent, MarkupKind, Position, Range, TextEdit,
 };
 use tracing::warn;
-use tree_sitter::QueryCursor;
-use ts_query_ls::{PredicateParameterArity, PredicateParameterType};
+use tree_sitter::{Node, Point, QueryCursor};
+use ts_query_ls::{Options, ParameterConstraint, PredicateParameterArity, PredicateParameterType};
 
 use crate::util::{
-    CAPTURES_QUERY, NodeUtil, PosUtil, TextProviderRope, get_current_capture_node,
-    get_language_name_raw, get_scm_files, node_is_or_has_ancestor, uri_to_basename,
+    CAPTURES_QUERY, NodeUtil, ParserNamesCache, PosUtil, TextProviderRope, get_available_languages,
+    get_current_capture_node, get_language_name_raw, get_scm_files, node_is_or_has_ancestor,
+    unescape_string_content, uri_to_basename,
+};
+use crate::{
+    Backend, LspClient, SymbolInfo,
+    node_types::{NodeInfo, NodeTypeRef, get_node_types},
 };
-use crate::{Backend, LspClient, SymbolInfo};
 
 pub async fn completion<C: LspClient>(
     backend: &Backend<C>,
@@ -220,8 +225,31 @@ pub async fn completion<C: LspClient>(
                     .parent()
                     .is_some_and(|p| p.kind() == "negated_field"));
 
+        let node_types = doc
+            .language_name
+            .as_deref()
+            .and_then(|name| get_node_types(&backend.node_types_map, uri, name, &options));
+        let schema = node_types.as_deref().and_then(|node_types| {
+            get_child_schema(
+                node_types,
+                supertypes,
+                current_node,
+                params.text_document_position.position.to_ts_point(rope),
+                rope,
+            )
+        });
+        // No field can appear within the value of another field
+        let field_allowed = |field: &String| {
+            schema.as_ref().is_none_or(|schema| {
+                schema
+                    .fields
+                    .as_ref()
+                    .is_some_and(|fields| fields.contains(field))
+            })
+        };
+
         if in_negated_field {
-            for field in fields.clone() {
+            for field in fields.iter().filter(|field| field_allowed(field)).cloned() {
                 completion_items.push(CompletionItem {
                     label: field,
                     kind: Some(CompletionItemKind::FIELD),
@@ -232,6 +260,13 @@ pub async fn completion<C: LspClient>(
         }
         if !top_level {
             for symbol in symbols {
+                if symbol.named
+                    && let Some(schema) = &schema
+                    && !schema.nodes.contains(symbol)
+                    && symbol.label != "ERROR"
+                {
+                    continue;
+                }
                 if (in_anon && !symbol.named) || (!in_anon && symbol.named) {
                     completion_items.push(CompletionItem {
                         label: symbol.label.clone(),
@@ -248,6 +283,24 @@ pub async fn completion<C: LspClient>(
                     });
                 }
             }
+            if !in_anon && !in_missing {
+                completion_items.extend(
+                    symbols
+                        .iter()
+                        .filter(|symbol| {
+                            schema
+                                .as_ref()
+                                .is_some_and(|schema| schema.nodes.contains(symbol))
+                        })
+                        .filter_map(|symbol| {
+                            node_types
+                                .as_deref()?
+                                .iter()
+                                .find(|node| node.symbol() == *symbol)
+                        })
+                        .filter_map(required_fields_snippet),
+                );
+            }
         }
         if !in_missing && !in_anon {
             if !top_level {
@@ -257,7 +310,7 @@ pub async fn completion<C: LspClient>(
                     ..Default::default()
                 });
             }
-            for field in fields {
+            for field in fields.iter().filter(|field| field_allowed(field)) {
                 completion_items.push(CompletionItem {
                     label: format!("{field}: "),
                     kind: Some(CompletionItemKind::FIELD),
@@ -267,6 +320,17 @@ pub async fn completion<C: LspClient>(
         }
     }
 
+    // Directive key and value completions
+    if in_predicate && !in_capture {
+        completion_items.extend(get_directive_key_completions(
+            current_node,
+            params.text_document_position.position.to_ts_point(rope),
+            rope,
+            &options,
+            &backend.parser_names_map,
+        ));
+    }
+
     // Capture completions
     if node_is_or_has_ancestor(tree.root_node(), current_node, "string") {
         return Ok(Some(CompletionResponse::Array(completion_items)));
@@ -342,6 +406,212 @@ pub async fn completion<C: LspClient>(
     Ok(Some(CompletionResponse::Array(completion_items)))
 }
 
+/// Get the keys of the key/value directive enclosing the cursor, or the values of its key if the
+/// key has already been given.
+fn get_directive_key_completions(
+    current_node: Node,
+    cursor_point: Point,
+    rope: &Rope,
+    options: &Options,
+    parser_names: &ParserNamesCache,
+) -> Vec<CompletionItem> {
+    let mut predicate = current_node;
+    while predicate.kind() != "predicate" {
+        let Some(parent) = predicate.parent() else {
+            return Vec::new();
+        };
+        predicate = parent;
+    }
+    let mut tree_cursor = predicate.walk();
+    let is_directive = predicate
+        .named_children(&mut tree_cursor)
+        .any(|child| child.kind() == "predicate_type" && child.text(rope) == "!");
+    let Some(directive) = predicate
+        .children_by_field_name("name", &mut tree_cursor)
+        .find(|node| node.kind() == "identifier")
+        .and_then(|name| options.valid_directives.get(&name.text(rope)))
+        .filter(|directive| is_directive && !directive.keys.is_empty())
+    else {
+        return Vec::new();
+    };
+    // The string parameters before the one being typed
+    let strings: Vec<String> = predicate
+        .named_child(2)
+        .map(|params| {
+            params
+                .named_children(&mut tree_cursor)
+                .filter(|param| param.kind() != "capture" && param.end_position() < cursor_point)
+                .map(|param| {
+                    let text = param.text(rope);
+                    if param.kind() == "string" {
+                        unescape_string_content(&text[1..text.len() - 1]).0
+                    } else {
+                        text
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    match strings.as_slice() {
+        [] => directive
+            .keys
+            .iter()
+            .map(|(key, spec)| CompletionItem {
+                label: key.clone(),
+                documentation: spec.description.as_ref().map(|description| {
+                    Documentation::MarkupContent(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: description.clone(),
+                    })
+                }),
+                kind: Some(CompletionItemKind::PROPERTY),
+                ..Default::default()
+            })
+            .collect(),
+        [key] => {
+            let values = match directive.keys.get(key).map(|spec| &spec.value) {
+                Some(ParameterConstraint::Enum(values)) => values.clone(),
+                Some(ParameterConstraint::Language) => {
+                    get_available_languages(parser_names, options)
+                        .unwrap_or_else(|| options.parser_aliases.keys().cloned().collect())
+                        .into_iter()
+                        .collect()
+                }
+                _ => Vec::new(),
+            };
+            values
+                .into_iter()
+                .map(|value| CompletionItem {
+                    label: value,
+                    kind: Some(CompletionItemKind::ENUM_MEMBER),
+                    ..Default::default()
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The node kinds and fields which the schema of the enclosing node permits at the cursor.
+struct ChildSchema {
+    nodes: HashSet<SymbolInfo>,
+    /// The permitted fields, or `None` if the cursor is within the value of a field.
+    fields: Option<HashSet<String>>,
+}
+
+/// Get the schema of the node enclosing the cursor, i.e. the innermost named node whose name is not
+/// the one being completed (a name ending right at the cursor is still being typed). Supertypes
+/// are expanded to their subtypes, and supertypes whose subtypes are permitted are permitted
+/// themselves.
+fn get_child_schema(
+    node_types: &[NodeInfo],
+    supertypes: &HashMap<SymbolInfo, BTreeSet<SymbolInfo>>,
+    current_node: Node,
+    point: Point,
+    rope: &Rope,
+) -> Option<ChildSchema> {
+    let contains_point = |node: Option<Node>| {
+        node.is_some_and(|node| node.start_position() < point && point <= node.end_position())
+    };
+    let mut field = None;
+    let mut candidate = Some(current_node);
+    let parent = loop {
+        let node = candidate?;
+        match node.kind() {
+            "named_node" if !contains_point(node.child_by_field_name("name")) => break node,
+            "field_definition"
+                if field.is_none() && !contains_point(node.child_by_field_name("name")) =>
+            {
+                field = node.child_by_field_name("name").map(|name| name.text(rope));
+            }
+            _ => {}
+        }
+        candidate = node.parent();
+    };
+    let parent_name = parent.child_by_field_name("name")?.text(rope);
+    let parent_info = node_types
+        .iter()
+        .find(|node| node.named && node.kind == parent_name)?;
+
+    let (types, fields) = if let Some(field) = field {
+        (parent_info.fields.get(&field)?.types.iter().collect(), None)
+    } else {
+        (
+            parent_info
+                .children
+                .iter()
+                .chain(parent_info.fields.values())
+                .flat_map(|child| &child.types)
+                .collect::<Vec<_>>(),
+            Some(parent_info.fields.keys().cloned().collect()),
+        )
+    };
+
+    let mut nodes = HashSet::new();
+    let mut pending = types
+        .into_iter()
+        .map(NodeTypeRef::symbol)
+        .collect::<Vec<_>>();
+    while let Some(symbol) = pending.pop() {
+        if let Some(subtypes) = supertypes.get(&symbol) {
+            pending.extend(
+                subtypes
+                    .iter()
+                    .filter(|subtype| !nodes.contains(*subtype))
+                    .cloned(),
+            );
+        }
+        nodes.insert(symbol);
+    }
+    for (supertype, subtypes) in supertypes {
+        if subtypes.iter().any(|subtype| nodes.contains(subtype)) {
+            nodes.insert(supertype.clone());
+        }
+    }
+
+    Some(ChildSchema { nodes, fields })
+}
+
+/// Create a snippet completion for a node with its required fields filled in.
+fn required_fields_snippet(node: &NodeInfo) -> Option<CompletionItem> {
+    let required_fields = node
+        .fields
+        .iter()
+        .filter(|(_, field)| field.required)
+        .collect::<Vec<_>>();
+    if required_fields.is_empty() {
+        return None;
+    }
+    let (labels, snippets): (Vec<_>, Vec<_>) = required_fields
+        .into_iter()
+        .enumerate()
+        .map(|(i, (name, field))| {
+            let i = i + 1;
+            let (label, snippet) = match field.types.as_slice() {
+                [node_type] if node_type.named => (
+                    format!("({})", node_type.kind),
+                    format!("(${{{i}:{}}})", node_type.kind),
+                ),
+                [node_type] => (
+                    format!("\"{}\"", node_type.kind),
+                    format!("\"${{{i}:{}}}\"", node_type.kind),
+                ),
+                _ => (String::from("_"), format!("${{{i}:_}}")),
+            };
+            (format!("{name}: {label}"), format!("{name}: {snippet}"))
+        })
+        .unzip();
+    // The label differs from the plain node completion, so that both can be told apart
+    Some(CompletionItem {
+        label: format!("{} {}", node.kind, labels.join(" ")),
+        kind: Some(CompletionItemKind::SNIPPET),
+        detail: Some(String::from("Node with required fields")),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        insert_text: Some(format!("{} {}", node.kind, snippets.join(" "))),
+        ..Default::default()
+    })
+}
+
 #[cfg(test)]
 mod test {
     use std::{
@@ -358,7 +628,8 @@ mod test {
         TextEdit, WorkDoneProgressParams, request::Completion,
     };
     use ts_query_ls::{
-        Options, Predicate, PredicateParameter, PredicateParameterArity, PredicateParameterType,
+        DirectiveKey, Options, ParameterConstraint, Predicate, PredicateParameter,
+        PredicateParameterArity, PredicateParameterType,
     };
 
     use crate::test_helpers::helpers::{QUERY_TEST_URI, TestService, initialize_server};
@@ -688,7 +959,8 @@ mod test {
                     }, PredicateParameter {
                         type_: PredicateParameterType::Any,
                     ..Default::default()
-                    }]
+                    }],
+                    ..Default::default()
                 })
             ]),
             valid_directives: BTreeMap::from([
@@ -704,7 +976,8 @@ mod test {
                         type_: PredicateParameterType::String,
                         arity: PredicateParameterArity::Optional,
                     ..Default::default()
-                    }]
+                    }],
+                    ..Default::default()
                 })
             ]),
             ..Default::default()
@@ -750,6 +1023,57 @@ mod test {
             },
         ]
     )]
+    #[case(
+        r"((constant) @constant (#set! @constant ))",
+        Position { line: 0, character: 39 },
+        &Options { valid_directives: BTreeMap::from([(String::from("set"), Predicate {
+            description: String::from("Set metadata"),
+            keys: BTreeMap::from([
+                (String::from("conceal"), DirectiveKey::default()),
+                (String::from("priority"), DirectiveKey {
+                    description: Some(String::from("The highlight priority")),
+                    value: ParameterConstraint::IntegerRange(0..=200),
+                }),
+            ]),
+            ..Default::default()
+        })]), ..Default::default() },
+        &[
+            item("conceal", CompletionItemKind::PROPERTY),
+            CompletionItem {
+                label: String::from("priority"),
+                kind: Some(CompletionItemKind::PROPERTY),
+                documentation: Some(Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: String::from("The highlight priority"),
+                })),
+                ..Default::default()
+            },
+            item("@constant", CompletionItemKind::VARIABLE),
+        ]
+    )]
+    #[case(
+        r#"((constant) @constant (#set! "injection.language" ""))"#,
+        Position { line: 0, character: 51 },
+        &Options { valid_directives: BTreeMap::from([(String::from("set"), Predicate {
+            description: String::from("Set metadata"),
+            keys: BTreeMap::from([
+                (String::from("injection.language"), DirectiveKey {
+                    description: None,
+                    value: ParameterConstraint::Language,
+                }),
+            ]),
+            ..Default::default()
+        })]),
+        parser_aliases: BTreeMap::from([
+            (String::from("ecma"), String::from("javascript")),
+            (String::from("jsx"), String::from("javascript")),
+        ]),
+        ..Default::default() },
+        &[
+            item("ecma", CompletionItemKind::ENUM_MEMBER),
+            item("jsx", CompletionItemKind::ENUM_MEMBER),
+        ]
+    )]
     #[case(
         r"((constant ! ) @constant)",
         Position { line: 0, character: 12 },
@@ -866,4 +1190,104 @@ the `inherits:` keyword, and there must be no spaces in-between module names.
         };
         assert_eq!(expected_completions, actual_completions);
     }
+
+    fn item(label: &str, kind: CompletionItemKind) -> CompletionItem {
+        CompletionItem {
+            label: String::from(label),
+            kind: Some(kind),
+            ..Default::default()
+        }
+    }
+
+    fn snippet(label: &str, insert_text: &str) -> CompletionItem {
+        CompletionItem {
+            label: String::from(label),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: Some(String::from("Node with required fields")),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            insert_text: Some(String::from(insert_text)),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(r"(predicate )", Position::new(0, 11), vec![
+        item("ERROR", CompletionItemKind::CLASS),
+        item("identifier", CompletionItemKind::CLASS),
+        item("predicate_type", CompletionItemKind::CLASS),
+        item("parameters", CompletionItemKind::CLASS),
+        item("MISSING", CompletionItemKind::KEYWORD),
+        item("name: ", CompletionItemKind::FIELD),
+        item("parameters: ", CompletionItemKind::FIELD),
+        item("type: ", CompletionItemKind::FIELD),
+    ])]
+    #[case(r"(field_definition name: (i))", Position::new(0, 26), vec![
+        item("ERROR", CompletionItemKind::CLASS),
+        item("identifier", CompletionItemKind::CLASS),
+        item("MISSING", CompletionItemKind::KEYWORD),
+    ])]
+    #[case(r"(named_node !)", Position::new(0, 13), vec![
+        item("name", CompletionItemKind::FIELD),
+        item("quantifier", CompletionItemKind::FIELD),
+        item("supertype", CompletionItemKind::FIELD),
+    ])]
+    #[case(r"(field_definition (p))", Position::new(0, 20), vec![
+        item("ERROR", CompletionItemKind::CLASS),
+        item("identifier", CompletionItemKind::CLASS),
+        item("definition", CompletionItemKind::INTERFACE),
+        item("list", CompletionItemKind::CLASS),
+        item("grouping", CompletionItemKind::CLASS),
+        item("missing_node", CompletionItemKind::CLASS),
+        item("anonymous_node", CompletionItemKind::CLASS),
+        item("named_node", CompletionItemKind::CLASS),
+        item("field_definition", CompletionItemKind::CLASS),
+        item("predicate", CompletionItemKind::CLASS),
+        snippet("anonymous_node name: _", "anonymous_node name: ${1:_}"),
+        snippet("named_node name: _", "named_node name: ${1:_}"),
+        snippet("field_definition name: _", "field_definition name: ${1:_}"),
+        snippet(
+            "predicate name: _ type: (predicate_type)",
+            "predicate name: ${1:_} type: (${2:predicate_type})",
+        ),
+        item("MISSING", CompletionItemKind::KEYWORD),
+        item("name: ", CompletionItemKind::FIELD),
+    ])]
+    #[tokio::test(flavor = "current_thread")]
+    #[allow(clippy::literal_string_with_formatting_args)]
+    async fn schema_completions(
+        #[case] source: &str,
+        #[case] position: Position,
+        #[case] expected_completions: Vec<CompletionItem>,
+    ) {
+        // Arrange
+        let options = Options {
+            grammar_directories: vec![String::from(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/grammars"
+            ))],
+            ..Default::default()
+        };
+        let mut service = initialize_server(&[(QUERY_TEST_URI.clone(), source)], &options).await;
+
+        // Act
+        let actual_completions = service
+            .request::<Completion>(CompletionParams {
+                context: None,
+                text_document_position: TextDocumentPositionParams {
+                    position,
+                    text_document: TextDocumentIdentifier {
+                        uri: QUERY_TEST_URI.clone(),
+                    },
+                },
+                partial_result_params: PartialResultParams::default(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await;
+
+        // Assert
+        assert_eq!(
+            Some(CompletionResponse::Array(expected_completions)),
+            actual_completions
+        );
+    }
 }
@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     sync::{Arc, LazyLock},
 };
 
@@ -17,18 +17,19 @@ use tower_lsp::{
 };
 use tree_sitter::{
     Language, Node, Query, QueryCursor, QueryError, QueryErrorKind, StreamingIterator as _,
-    TreeCursor,
 };
 use ts_query_ls::{
-    Options, ParameterConstraint, PredicateParameter, PredicateParameterArity,
-    PredicateParameterType, StringArgumentStyle,
+    Options, ParameterConstraint, PatternPrecedence, Predicate, PredicateParameter,
+    PredicateParameterArity, PredicateParameterType, StringArgumentStyle,
 };
 
 use crate::{
     Backend, DocumentData, ImportedUri, LanguageData, LspClient, QUERY_LANGUAGE, SymbolInfo,
+    pattern_syntax::{get_fixed_string, get_pattern_error},
     util::{
-        CAPTURES_QUERY, NodeUtil as _, TextProviderRope, remove_unnecessary_escapes,
-        uri_to_basename,
+        ByteUtil as _, CAPTURES_QUERY, NodeUtil as _, ParserNamesCache, TextProviderRope,
+        escape_string_content, get_available_languages, get_format_ignore_regions, get_suggestions,
+        remove_unnecessary_escapes, unescape_string_content, uri_to_basename,
     },
 };
 
@@ -63,11 +64,25 @@ pub enum DiagnosticCode {
     InvalidEnumMember,
     UnexpectedParameter,
     MissingParameter,
+    InvalidRegexPattern,
+    InvalidLanguage,
+    UnrecognizedKey,
+    QuantifiedCaptureArgument,
+    PartiallyDefinedCapture,
+    DuplicatePattern,
+    RedundantPattern,
+    ShadowedPattern,
+    UnterminatedFormatIgnore,
 
     // Hints
     UnnecessaryQuotations,
     UnquotedString,
     RedundantAlternant,
+    FixedStringPattern,
+    SingleValueAnyOf,
+    DuplicateAnyOfValue,
+    MergeableEqPredicates,
+    ConstantPredicate,
 
     // Special
     ImportIssues,
@@ -105,6 +120,20 @@ impl From<DiagnosticCode> for Option<NumberOrString> {
             DiagnosticCode::UnexpectedParameter => "unexpected-parameter",
             DiagnosticCode::MissingParameter => "missing-parameter",
             DiagnosticCode::RedundantAlternant => "redundant-alternant",
+            DiagnosticCode::InvalidRegexPattern => "invalid-regex-pattern",
+            DiagnosticCode::InvalidLanguage => "invalid-language",
+            DiagnosticCode::UnrecognizedKey => "unrecognized-key",
+            DiagnosticCode::QuantifiedCaptureArgument => "quantified-capture-argument",
+            DiagnosticCode::PartiallyDefinedCapture => "partially-defined-capture",
+            DiagnosticCode::FixedStringPattern => "fixed-string-pattern",
+            DiagnosticCode::SingleValueAnyOf => "single-value-any-of",
+            DiagnosticCode::DuplicateAnyOfValue => "duplicate-any-of-value",
+            DiagnosticCode::MergeableEqPredicates => "mergeable-eq-predicates",
+            DiagnosticCode::ConstantPredicate => "constant-predicate",
+            DiagnosticCode::DuplicatePattern => "duplicate-pattern",
+            DiagnosticCode::RedundantPattern => "redundant-pattern",
+            DiagnosticCode::ShadowedPattern => "shadowed-pattern",
+            DiagnosticCode::UnterminatedFormatIgnore => "unterminated-format-ignore",
         };
         Some(NumberOrString::String(String::from(string_slice)))
     }
@@ -208,6 +237,7 @@ async fn create_diagnostic_report<C: LspClient>(
         document,
         language_data,
         backend.options.clone(),
+        &backend.parser_names_map,
         ignore_missing_language,
         cache,
     )
@@ -284,12 +314,14 @@ const ERROR_SEVERITY: Option<DiagnosticSeverity> = Some(DiagnosticSeverity::ERRO
 const WARNING_SEVERITY: Option<DiagnosticSeverity> = Some(DiagnosticSeverity::WARNING);
 const HINT_SEVERITY: Option<DiagnosticSeverity> = Some(DiagnosticSeverity::HINT);
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_diagnostics(
     uri: &Url,
     document_map: &DashMap<Url, DocumentData>,
     document: DocumentData,
     language_data: Option<Arc<LanguageData>>,
     options_arc: Arc<tokio::sync::RwLock<Options>>,
+    parser_names: &ParserNamesCache,
     ignore_missing_language: bool,
     cache: bool,
 ) -> Vec<Diagnostic> {
@@ -321,6 +353,7 @@ pub async fn get_diagnostics(
         document,
         language_data.clone(),
         options_arc.clone(),
+        parser_names,
         cache,
         &mut HashSet::new(),
     )
@@ -356,18 +389,21 @@ pub async fn get_diagnostics(
     full_report
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn get_diagnostics_recursively(
     uri: &Url,
     document_map: &DashMap<Url, DocumentData>,
     document: DocumentData,
     language_data: Option<Arc<LanguageData>>,
     options_arc: Arc<tokio::sync::RwLock<Options>>,
+    parser_names: &ParserNamesCache,
     cache: bool,
     seen: &mut HashSet<Url>,
 ) -> Vec<Diagnostic> {
     let mut diagnostics = Box::pin(get_imported_query_diagnostics(
         document_map,
         options_arc.clone(),
+        parser_names,
         &document.imported_uris,
         language_data.clone(),
         seen,
@@ -428,9 +464,8 @@ async fn get_diagnostics_recursively(
     diagnostics.append(&mut handle.unwrap_or_default());
 
     let options = options_arc.read().await;
-    let valid_captures = options
-        .valid_captures
-        .get(&uri_to_basename(uri).unwrap_or_default());
+    let query_name = uri_to_basename(uri).unwrap_or_default();
+    let validates_captures = options.validates_captures(&query_name);
     let rope = &document.rope;
     let tree = &document.tree;
 
@@ -443,7 +478,6 @@ async fn get_diagnostics_recursively(
     let supertypes = language_data.as_deref().map(|ld| &ld.supertype_map);
     let mut cursor = QueryCursor::new();
     let mut helper_cursor = QueryCursor::new();
-    let mut tree_cursor = tree.root_node().walk();
     let provider = &TextProviderRope(rope);
     let mut matches = cursor.matches(&DIAGNOSTICS_QUERY, tree.root_node(), provider);
     while let Some(match_) = matches.next() {
@@ -467,11 +501,26 @@ async fn get_diagnostics_recursively(
                         named,
                     };
                     if !symbols.contains(&sym) {
+                        let suggestions = get_node_suggestions(&sym, language_data.as_deref());
+                        let replacements: Vec<_> = if named {
+                            suggestions.clone()
+                        } else {
+                            suggestions
+                                .iter()
+                                .map(|s| escape_string_content(s))
+                                .collect()
+                        };
                         diagnostics.push(Diagnostic {
-                            message: format!("Invalid node type: \"{}\"", sym.label),
+                            message: format!(
+                                "Invalid node type: \"{}\"{}",
+                                sym.label,
+                                did_you_mean(&suggestions)
+                            ),
                             severity: ERROR_SEVERITY,
                             range,
                             code: DiagnosticCode::InvalidNode.into(),
+                            data: (!replacements.is_empty())
+                                .then(|| CodeActions::Replace.with_arguments(&replacements)),
                             ..Default::default()
                         });
                     }
@@ -507,11 +556,18 @@ async fn get_diagnostics_recursively(
                                 ..Default::default()
                             });
                         } else if subtypes.is_empty() && !symbols.contains(&subtype_sym) {
+                            let suggestions =
+                                get_node_suggestions(&subtype_sym, language_data.as_deref());
                             diagnostics.push(Diagnostic {
-                                message: format!("Invalid node type: \"{subtype_text}\""),
+                                message: format!(
+                                    "Invalid node type: \"{subtype_text}\"{}",
+                                    did_you_mean(&suggestions)
+                                ),
                                 severity: ERROR_SEVERITY,
                                 range,
                                 code: DiagnosticCode::InvalidNode.into(),
+                                data: (!suggestions.is_empty())
+                                    .then(|| CodeActions::Replace.with_arguments(&suggestions)),
                                 ..Default::default()
                             });
                         }
@@ -531,50 +587,40 @@ async fn get_diagnostics_recursively(
                     };
                     let field = capture_text;
                     if !fields.contains(&field) {
+                        let suggestions = language_data
+                            .as_deref()
+                            .map(|ld| {
+                                get_suggestions(&field, ld.fields_vec.iter().map(String::as_str))
+                            })
+                            .unwrap_or_default();
                         diagnostics.push(Diagnostic {
-                            message: format!("Invalid field name: \"{field}\""),
+                            message: format!(
+                                "Invalid field name: \"{field}\"{}",
+                                did_you_mean(&suggestions)
+                            ),
                             severity: ERROR_SEVERITY,
                             range,
                             code: DiagnosticCode::InvalidField.into(),
+                            data: (!suggestions.is_empty())
+                                .then(|| CodeActions::Replace.with_arguments(&suggestions)),
                             ..Default::default()
                         });
                     }
                 }
                 "alternation" => {
-                    let mut seen = HashSet::new();
-                    for child in capture.node.named_children(&mut tree_cursor) {
-                        let kind = child.kind();
-                        let named = match kind {
-                            "named_node" => true,
-                            "anonymous_node" => false,
-                            "capture" => break,
-                            _ => continue,
-                        };
-                        if named && child.named_child(1).is_some_and(|c| c.kind() != "capture") {
-                            continue;
-                        }
-                        let content_node = child.named_child(0).unwrap_or(child);
-                        let id = (content_node.text(rope), named);
-                        if seen.contains(&id) {
-                            diagnostics.push(Diagnostic {
-                                message: format!(
-                                    "{} already captured by this alternation (fix available)",
-                                    if named {
-                                        ["(", id.0.as_str(), ")"].concat()
-                                    } else {
-                                        id.0
-                                    },
-                                ),
-                                severity: HINT_SEVERITY,
-                                range: child.lsp_range(rope),
-                                data: Some(CodeActions::Remove.into()),
-                                code: DiagnosticCode::RedundantAlternant.into(),
-                                ..Default::default()
-                            });
-                        } else {
-                            seen.insert(id);
-                        }
-                    }
+                    push_redundant_alternant_diagnostics(
+                        &mut diagnostics,
+                        uri,
+                        capture.node,
+                        rope,
+                        supertypes,
+                    );
+                    push_mergeable_eq_diagnostic(
+                        &mut diagnostics,
+                        capture.node,
+                        rope,
+                        valid_predicates,
+                    );
                 }
                 "error" => diagnostics.push(Diagnostic {
                     message: "Invalid syntax".to_owned(),
@@ -598,17 +644,16 @@ async fn get_diagnostics_recursively(
                             .unwrap(),
                         provider,
                     );
-                    let mut valid = false;
+                    let mut definitions = Vec::new();
                     while let Some(m) = matches.next() {
-                        if m.captures
-                            .iter()
-                            .any(|cap| cap.node.text(rope) == capture_text)
-                        {
-                            valid = true;
-                            break;
-                        }
+                        definitions.extend(
+                            m.captures
+                                .iter()
+                                .filter(|cap| cap.node.text(rope) == capture_text)
+                                .map(|cap| cap.node),
+                        );
                     }
-                    if !valid {
+                    if definitions.is_empty() {
                         diagnostics.push(Diagnostic {
                             message: format!("Undeclared capture: \"{capture_text}\""),
                             severity: ERROR_SEVERITY,
@@ -616,21 +661,90 @@ async fn get_diagnostics_recursively(
                             code: DiagnosticCode::UndeclaredCapture.into(),
                             ..Default::default()
                         });
+                        continue;
+                    }
+                    let Some(predicate) = capture.node.parent().and_then(|params| params.parent())
+                    else {
+                        continue;
+                    };
+                    if let Some(alternation) = get_partial_alternation(&definitions, predicate) {
+                        diagnostics.push(Diagnostic {
+                            message: format!(
+                                "Capture \"{capture_text}\" is not defined in every branch of an alternation, so this predicate may not apply when another branch matches"
+                            ),
+                            severity: WARNING_SEVERITY,
+                            range,
+                            code: DiagnosticCode::PartiallyDefinedCapture.into(),
+                            related_information: Some(vec![DiagnosticRelatedInformation {
+                                location: Location {
+                                    uri: uri.clone(),
+                                    range: alternation.lsp_range(rope),
+                                },
+                                message: String::from("Alternation defined here"),
+                            }]),
+                            ..Default::default()
+                        });
+                    }
+                    let Some(name_node) = predicate.named_child(0) else {
+                        continue;
+                    };
+                    let name = name_node.text(rope);
+                    if !is_predicate(&predicate, rope)
+                        || !definitions.iter().any(|def| is_quantified(*def, rope))
+                    {
+                        continue;
                     }
+                    let Some(any_name) = get_any_predicate_name(&name)
+                        .filter(|any_name| valid_predicates.contains_key(any_name))
+                    else {
+                        continue;
+                    };
+                    diagnostics.push(Diagnostic {
+                        message: format!(
+                            "Quantified capture \"{capture_text}\" passed to `#{name}?`, which must hold for every captured node; consider `#{any_name}?` (fix available)"
+                        ),
+                        severity: WARNING_SEVERITY,
+                        range,
+                        code: DiagnosticCode::QuantifiedCaptureArgument.into(),
+                        data: Some(CodeActions::UseAnyPredicate.with_arguments(&[any_name])),
+                        ..Default::default()
+                    });
                 }
                 "capture.definition" => {
                     if let Some(suffix) = capture_text.strip_prefix("@") {
                         if !suffix.starts_with('_')
-                            && valid_captures
-                                .is_some_and(|c| !c.contains_key(&String::from(suffix)))
+                            && validates_captures
+                            && options.resolve_capture(&query_name, suffix).is_none()
                         {
+                            let suggestions: Vec<_> = get_suggestions(
+                                suffix,
+                                options
+                                    .valid_captures
+                                    .get(&query_name)
+                                    .into_iter()
+                                    .flat_map(|captures| captures.keys().map(String::as_str)),
+                            )
+                            .into_iter()
+                            .map(|suggestion| format!("@{suggestion}"))
+                            .collect();
                             diagnostics.push(Diagnostic {
-                                message: format!(
-                                    "Invalid capture name \"{capture_text}\" (fix available)"
-                                ),
+                                message: if suggestions.is_empty() {
+                                    format!(
+                                        "Invalid capture name \"{capture_text}\" (fix available)"
+                                    )
+                                } else {
+                                    format!(
+                                        "Invalid capture name \"{capture_text}\"{}",
+                                        did_you_mean(&suggestions)
+                                    )
+                                },
                                 severity: WARNING_SEVERITY,
                                 range,
-                                data: Some(CodeActions::PrefixUnderscore.into()),
+                                data: Some(if suggestions.is_empty() {
+                                    CodeActions::PrefixUnderscore.into()
+                                } else {
+                                    CodeActions::RenameCapture.with_arguments(&suggestions)
+                                }),
                                 code: DiagnosticCode::InvalidCaptureName.into(),
                                 ..Default::default()
                             });
@@ -669,6 +783,15 @@ async fn get_diagnostics_recursively(
                     }
                 }
                 "predicate" | "directive" => {
+                    if capture_name == "predicate" {
+                        push_predicate_simplification_diagnostics(
+                            &mut diagnostics,
+                            uri,
+                            rope,
+                            valid_predicates,
+                            capture.node,
+                        );
+                    }
                     let (validator, code) = if capture_name == "predicate" {
                         (valid_predicates, DiagnosticCode::UnrecognizedPredicate)
                     } else {
@@ -680,18 +803,26 @@ async fn get_diagnostics_recursively(
                     if let Some(predicate) = validator.get(&capture_text) {
                         validate_predicate(
                             &mut diagnostics,
-                            &mut tree_cursor,
                             rope,
                             language_data.as_ref(),
-                            &predicate.parameters,
+                            &options,
+                            parser_names,
+                            predicate,
                             capture.node,
                         );
                     } else {
+                        let suggestions =
+                            get_suggestions(&capture_text, validator.keys().map(String::as_str));
                         diagnostics.push(Diagnostic {
-                            message: format!("Unrecognized {capture_name} \"{capture_text}\""),
+                            message: format!(
+                                "Unrecognized {capture_name} \"{capture_text}\"{}",
+                                did_you_mean(&suggestions)
+                            ),
                             severity: WARNING_SEVERITY,
                             range,
                             code: code.into(),
+                            data: (!suggestions.is_empty())
+                                .then(|| CodeActions::Replace.with_arguments(&suggestions)),
                             ..Default::default()
                         });
                     }
@@ -769,12 +900,469 @@ async fn get_diagnostics_recursively(
             }
         }
     }
+    for (start, _) in get_format_ignore_regions(rope, tree.root_node())
+        .into_iter()
+        .filter(|(_, end)| end.is_none())
+    {
+        diagnostics.push(Diagnostic {
+            message: String::from(
+                "Unterminated `format-ignore-start` comment; expected a `format-ignore-end` comment in the same node",
+            ),
+            range: start.lsp_range(rope),
+            severity: WARNING_SEVERITY,
+            code: DiagnosticCode::UnterminatedFormatIgnore.into(),
+            ..Default::default()
+        });
+    }
+    diagnostics.append(&mut get_pattern_overlap_diagnostics(
+        uri,
+        document_map,
+        &document,
+        options.diagnostic_options.pattern_precedence,
+    ));
+    diagnostics
+}
+
+/// Get the node kinds of the same namedness which are closest to an invalid node kind.
+fn get_node_suggestions(symbol: &SymbolInfo, language_data: Option<&LanguageData>) -> Vec<String> {
+    language_data
+        .map(|ld| {
+            get_suggestions(
+                &symbol.label,
+                ld.symbols_vec
+                    .iter()
+                    .filter(|candidate| candidate.named == symbol.named)
+                    .map(|candidate| candidate.label.as_str()),
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Describe the suggested replacements for an invalid name, to be appended to a diagnostic
+/// message.
+fn did_you_mean(suggestions: &[String]) -> String {
+    let quoted: Vec<_> = suggestions.iter().map(|s| format!("{s:?}")).collect();
+    match quoted.as_slice() {
+        [] => String::new(),
+        [single] => format!(", did you mean {single}? (fix available)"),
+        [rest @ .., last] => format!(
+            ", did you mean {} or {last}? (fixes available)",
+            rest.join(", ")
+        ),
+    }
+}
+
+/// What a member of an alternation matches, if it is a single node.
+#[derive(PartialEq, Eq)]
+enum AlternantKind {
+    /// `_`, which matches any node.
+    Wildcard,
+    /// `(_)`, which matches any named node.
+    NamedWildcard,
+    Symbol(SymbolInfo),
+}
+
+struct Alternant<'a> {
+    node: Node<'a>,
+    kind: AlternantKind,
+    /// Whether the alternant places no constraints on the node beyond its kind.
+    plain: bool,
+    captures: Vec<String>,
+    label: String,
+}
+
+impl Alternant<'_> {
+    /// Whether every node matched by `other` is also matched by this alternant.
+    fn subsumes(
+        &self,
+        other: &Self,
+        supertypes: Option<&HashMap<SymbolInfo, BTreeSet<SymbolInfo>>>,
+    ) -> bool {
+        if !self.plain {
+            return false;
+        }
+        match (&self.kind, &other.kind) {
+            (AlternantKind::Wildcard, _)
+            | (AlternantKind::NamedWildcard, AlternantKind::NamedWildcard) => true,
+            (AlternantKind::NamedWildcard, AlternantKind::Symbol(symbol)) => symbol.named,
+            (AlternantKind::Symbol(supertype), AlternantKind::Symbol(symbol)) => {
+                supertype == symbol
+                    || supertypes.is_some_and(|supertypes| {
+                        is_subtype_of(symbol, supertype, supertypes, &mut HashSet::new())
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether `symbol` is a subtype of `supertype`, directly or through nested supertypes.
+fn is_subtype_of<'a>(
+    symbol: &SymbolInfo,
+    supertype: &'a SymbolInfo,
+    supertypes: &'a HashMap<SymbolInfo, BTreeSet<SymbolInfo>>,
+    seen: &mut HashSet<&'a SymbolInfo>,
+) -> bool {
+    if !seen.insert(supertype) {
+        return false;
+    }
+    supertypes.get(supertype).is_some_and(|subtypes| {
+        subtypes.contains(symbol)
+            || subtypes
+                .iter()
+                .any(|subtype| is_subtype_of(symbol, subtype, supertypes, seen))
+    })
+}
+
+fn get_alternant<'a>(node: Node<'a>, rope: &Rope) -> Option<Alternant<'a>> {
+    let named = match node.kind() {
+        "named_node" => true,
+        "anonymous_node" => false,
+        _ => return None,
+    };
+    if node.child_by_field_name("quantifier").is_some() {
+        return None;
+    }
+    let name = node.child_by_field_name("name")?;
+    let kind = match name.kind() {
+        "_" if named => AlternantKind::NamedWildcard,
+        "_" => AlternantKind::Wildcard,
+        "string" => {
+            let text = name.text(rope);
+            AlternantKind::Symbol(SymbolInfo {
+                label: unescape_string_content(&text[1..text.len() - 1]).0,
+                named: false,
+            })
+        }
+        _ => AlternantKind::Symbol(SymbolInfo {
+            label: name.text(rope),
+            named: true,
+        }),
+    };
+    let mut captures = Vec::new();
+    let mut plain = node.child_by_field_name("supertype").is_none();
+    for child in node.named_children(&mut node.walk()) {
+        match child.kind() {
+            "capture" => captures.push(child.text(rope)),
+            "comment" | "quantifier" => {}
+            _ if child == name => {}
+            _ => plain = false,
+        }
+    }
+    let label = match &kind {
+        AlternantKind::Wildcard => String::from("_"),
+        AlternantKind::NamedWildcard => String::from("(_)"),
+        AlternantKind::Symbol(_) if named => format!("({})", name.text(rope)),
+        AlternantKind::Symbol(_) => name.text(rope),
+    };
+    Some(Alternant {
+        node,
+        kind,
+        plain,
+        captures,
+        label,
+    })
+}
+
+/// Report members of an alternation which only match nodes that are already matched by another
+/// member, because they are identical or subsumed by a supertype or wildcard.
+fn push_redundant_alternant_diagnostics(
+    diagnostics: &mut Vec<Diagnostic>,
+    uri: &Url,
+    list: Node,
+    rope: &Rope,
+    supertypes: Option<&HashMap<SymbolInfo, BTreeSet<SymbolInfo>>>,
+) {
+    let alternants: Vec<_> = list
+        .named_children(&mut list.walk())
+        .take_while(|child| child.kind() != "capture")
+        .filter_map(|child| get_alternant(child, rope))
+        .collect();
+    for (i, alternant) in alternants.iter().enumerate() {
+        let subsumer = alternants.iter().enumerate().find_map(|(j, other)| {
+            if i == j || !other.subsumes(alternant, supertypes) {
+                return None;
+            }
+            // Of two identical alternants, the later one is redundant
+            let identical = alternant.subsumes(other, supertypes);
+            if identical {
+                (j < i).then_some((other, true))
+            } else {
+                alternant
+                    .captures
+                    .iter()
+                    .all(|capture| other.captures.contains(capture))
+                    .then_some((other, false))
+            }
+        });
+        let Some((subsumer, identical)) = subsumer else {
+            continue;
+        };
+        let message = if identical {
+            format!(
+                "{} already captured by this alternation (fix available)",
+                alternant.label
+            )
+        } else {
+            format!(
+                "{} already captured by {} in this alternation (fix available)",
+                alternant.label, subsumer.label
+            )
+        };
+        diagnostics.push(Diagnostic {
+            message,
+            severity: HINT_SEVERITY,
+            range: alternant.node.lsp_range(rope),
+            data: Some(CodeActions::Remove.into()),
+            code: DiagnosticCode::RedundantAlternant.into(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location {
+                    uri: uri.clone(),
+                    range: subsumer.node.lsp_range(rope),
+                },
+                message: String::from("Already captured here"),
+            }]),
+            ..Default::default()
+        });
+    }
+}
+
+/// A summary of a top-level pattern, used to compare it with the other patterns of a query and
+/// those it inherits.
+struct PatternSummary {
+    location: Location,
+    /// The tokens of the pattern, ignoring comments and formatting.
+    key: String,
+    /// The tokens of the pattern, without captures and predicates.
+    shape: String,
+    /// The names of the captures defined by the pattern, in order.
+    captures: Vec<String>,
+    /// Whether the pattern contains predicates, i.e. may not match every node of its shape.
+    conditional: bool,
+    /// Whether the pattern belongs to the document being diagnosed, rather than an inherited one.
+    local: bool,
+}
+
+fn is_predicate(node: &Node, rope: &Rope) -> bool {
+    node.kind() == "predicate"
+        && node
+            .named_children(&mut node.walk())
+            .any(|child| child.kind() == "predicate_type" && child.text(rope) == "?")
+}
+
+/// Push the tokens of a node, skipping comments and the parentheses of groupings which contain a
+/// single pattern. If `captures` is given, capture names are moved into it and predicates are
+/// skipped.
+fn push_normalized_tokens(
+    node: Node,
+    rope: &Rope,
+    tokens: &mut Vec<String>,
+    mut captures: Option<&mut Vec<String>>,
+) {
+    match node.kind() {
+        "comment" => return,
+        "capture" => {
+            if let Some(captures) = captures {
+                captures.push(node.text(rope));
+                tokens.push(String::from("@"));
+            } else {
+                tokens.push(node.text(rope));
+            }
+            return;
+        }
+        "string" => {
+            tokens.push(node.text(rope));
+            return;
+        }
+        _ if captures.is_some() && is_predicate(&node, rope) => return,
+        _ if node.child_count() == 0 => {
+            tokens.push(node.text(rope));
+            return;
+        }
+        _ => {}
+    }
+    let children = node
+        .children(&mut node.walk())
+        .filter(|child| {
+            child.kind() != "comment" && (captures.is_none() || !is_predicate(child, rope))
+        })
+        .collect::<Vec<_>>();
+    if let ("grouping", [open, inner, close]) = (node.kind(), children.as_slice())
+        && open.kind() == "("
+        && close.kind() == ")"
+    {
+        push_normalized_tokens(*inner, rope, tokens, captures);
+        return;
+    }
+    for child in children {
+        push_normalized_tokens(child, rope, tokens, captures.as_deref_mut());
+    }
+}
+
+fn summarize_patterns(uri: &Url, document: &DocumentData, local: bool) -> Vec<PatternSummary> {
+    let rope = &document.rope;
+    let root = document.tree.root_node();
+    root.named_children(&mut root.walk())
+        .filter(|node| node.kind() != "comment" && !node.has_error())
+        .map(|node| {
+            let mut key = Vec::new();
+            push_normalized_tokens(node, rope, &mut key, None);
+            let mut shape = Vec::new();
+            let mut captures = Vec::new();
+            push_normalized_tokens(node, rope, &mut shape, Some(&mut captures));
+            let mut cursor = node.walk();
+            let mut conditional = false;
+            'outer: loop {
+                if is_predicate(&cursor.node(), rope) {
+                    conditional = true;
+                    break;
+                }
+                if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                    continue;
+                }
+                while cursor.goto_parent() {
+                    if cursor.goto_next_sibling() {
+                        continue 'outer;
+                    }
+                }
+                break;
+            }
+            PatternSummary {
+                location: Location {
+                    uri: uri.clone(),
+                    range: node.lsp_range(rope),
+                },
+                key: key.join(" "),
+                shape: shape.join(" "),
+                captures,
+                conditional,
+                local,
+            }
+        })
+        .collect()
+}
+
+/// Summarize the patterns of the inherited queries, in the order they are prepended to the query.
+fn summarize_imported_patterns(
+    document_map: &DashMap<Url, DocumentData>,
+    imported_uris: &[ImportedUri],
+    seen: &mut HashSet<Url>,
+    patterns: &mut Vec<PatternSummary>,
+) {
+    for uri in imported_uris
+        .iter()
+        .filter_map(|import| import.uri.as_ref())
+    {
+        if !seen.insert(uri.clone()) {
+            continue;
+        }
+        let Some(document) = document_map.get(uri).map(|doc| doc.clone()) else {
+            continue;
+        };
+        summarize_imported_patterns(document_map, &document.imported_uris, seen, patterns);
+        patterns.append(&mut summarize_patterns(uri, &document, false));
+    }
+}
+
+/// Report patterns which duplicate, are made redundant by, or are shadowed by another pattern of
+/// the query or of the queries it inherits.
+fn get_pattern_overlap_diagnostics(
+    uri: &Url,
+    document_map: &DashMap<Url, DocumentData>,
+    document: &DocumentData,
+    precedence: PatternPrecedence,
+) -> Vec<Diagnostic> {
+    let mut patterns = Vec::new();
+    summarize_imported_patterns(
+        document_map,
+        &document.imported_uris,
+        &mut HashSet::from([uri.clone()]),
+        &mut patterns,
+    );
+    patterns.append(&mut summarize_patterns(uri, document, true));
+    let patterns = &patterns;
+
+    let mut shapes: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, pattern) in patterns.iter().enumerate() {
+        shapes.entry(&pattern.shape).or_default().push(i);
+    }
+
+    let mut diagnostics = Vec::new();
+    for (i, pattern) in patterns.iter().enumerate().filter(|(_, p)| p.local) {
+        let group = &shapes[pattern.shape.as_str()];
+        let unconditional_with = |same_captures: bool| {
+            move |j: &&usize| {
+                let other = &patterns[**j];
+                !other.conditional && (other.captures == pattern.captures) == same_captures
+            }
+        };
+        let shadowing = match precedence {
+            PatternPrecedence::None => None,
+            PatternPrecedence::FirstWins => group
+                .iter()
+                .take_while(|j| **j < i)
+                .find(unconditional_with(false)),
+            PatternPrecedence::LastWins => group
+                .iter()
+                .rev()
+                .take_while(|j| **j > i)
+                .find(unconditional_with(false)),
+        };
+        let (message, related_message, code, j) = if let Some(j) = group
+            .iter()
+            .take_while(|j| **j < i)
+            .find(|j| patterns[**j].key == pattern.key)
+        {
+            (
+                "Duplicate pattern (fix available)",
+                "First defined here",
+                DiagnosticCode::DuplicatePattern,
+                *j,
+            )
+        } else if let Some(j) = pattern
+            .conditional
+            .then(|| group.iter().find(unconditional_with(true)))
+            .flatten()
+        {
+            (
+                "Redundant pattern, since an unconditional pattern captures the same nodes (fix available)",
+                "Captured unconditionally here",
+                DiagnosticCode::RedundantPattern,
+                *j,
+            )
+        } else if let Some(j) = shadowing {
+            (
+                "This pattern can never take effect, since it is shadowed by an unconditional pattern",
+                "Shadowed by this pattern",
+                DiagnosticCode::ShadowedPattern,
+                *j,
+            )
+        } else {
+            continue;
+        };
+        // Shadowed patterns may be intended to take effect, so they are not simply removed
+        let removable = !matches!(code, DiagnosticCode::ShadowedPattern);
+        diagnostics.push(Diagnostic {
+            message: String::from(message),
+            severity: WARNING_SEVERITY,
+            range: pattern.location.range,
+            tags: removable.then(|| vec![DiagnosticTag::UNNECESSARY]),
+            data: removable.then(|| CodeActions::RemoveRedundantPattern.into()),
+            code: code.into(),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: patterns[j].location.clone(),
+                message: String::from(related_message),
+            }]),
+            ..Default::default()
+        });
+    }
     diagnostics
 }
 
 async fn get_imported_query_diagnostics(
     document_map: &DashMap<Url, DocumentData>,
     options_arc: Arc<tokio::sync::RwLock<Options>>,
+    parser_names: &ParserNamesCache,
     imported_uris: &Vec<ImportedUri>,
     language_data: Option<Arc<LanguageData>>,
     seen: &mut HashSet<Url>,
@@ -804,6 +1392,7 @@ async fn get_imported_query_diagnostics(
                     document,
                     language_data.clone(),
                     options_arc.clone(),
+                    parser_names,
                     true,
                     seen,
                 )
@@ -858,54 +1447,366 @@ async fn get_imported_query_diagnostics(
     items
 }
 
-fn validate_predicate<'a>(
-    diagnostics: &mut Vec<Diagnostic>,
-    tree_cursor: &mut TreeCursor<'a>,
-    rope: &Rope,
-    language_data: Option<&Arc<LanguageData>>,
-    predicate_params: &[PredicateParameter],
-    predicate_node: Node<'a>,
-) {
-    let params_node = predicate_node.parent().unwrap().named_child(2);
-    let mut param_spec_iter = predicate_params.iter().peekable();
-    let mut prev_param_spec = param_spec_iter.peek().copied();
+/// Get the name of the `any-` variant of a predicate, e.g. `not-any-eq` for `not-eq`.
+fn get_any_predicate_name(name: &str) -> Option<String> {
+    if name.starts_with("any-") || name.starts_with("not-any-") {
+        return None;
+    }
+    Some(match name.strip_prefix("not-") {
+        Some(name) => format!("not-any-{name}"),
+        None => format!("any-{name}"),
+    })
+}
 
-    let param_type_mismatch = |param: Node, param_spec: &PredicateParameter| {
-        let range = param.lsp_range(rope);
-        let is_capture = param.kind() == "capture";
-        let severity = WARNING_SEVERITY;
-        if is_capture && param_spec.type_ == PredicateParameterType::String
-            || !is_capture && param_spec.type_ == PredicateParameterType::Capture
+/// Whether a capture may capture several nodes, i.e. whether the node it is attached to, or any
+/// of its ancestors, is quantified with `*` or `+`.
+fn is_quantified(capture: Node, rope: &Rope) -> bool {
+    let mut node = capture.parent();
+    while let Some(current) = node {
+        if current
+            .children(&mut current.walk())
+            .any(|child| child.kind() == "quantifier" && child.text(rope) != "?")
         {
-            return Some(Diagnostic {
-                message: format!(
-                    "Parameter type mismatch: expected \"{}\", got \"{}\"",
-                    param_spec.type_,
-                    if is_capture { "capture" } else { "string" }
-                ),
-                severity,
-                range,
-                code: DiagnosticCode::ParameterTypeMismatch.into(),
-                ..Default::default()
-            });
+            return true;
         }
+        node = current.parent();
+    }
+    false
+}
 
-        if is_capture {
-            return None;
+/// Get the alternation, if any, with a branch which contains none of the given definitions of a
+/// capture while another branch does. Alternations containing the predicate are ignored, since
+/// the predicate then only applies to its own branch.
+fn get_partial_alternation<'a>(definitions: &[Node<'a>], predicate: Node) -> Option<Node<'a>> {
+    let contains = |outer: Node, inner: Node| {
+        outer.start_byte() <= inner.start_byte() && inner.end_byte() <= outer.end_byte()
+    };
+    definitions.iter().find_map(|definition| {
+        let mut node = definition.parent();
+        while let Some(current) = node {
+            if current.kind() == "list" && !contains(current, predicate) {
+                let mut branches = current
+                    .named_children(&mut current.walk())
+                    .filter(|child| !matches!(child.kind(), "capture" | "quantifier" | "comment"))
+                    .collect::<Vec<_>>()
+                    .into_iter();
+                if branches.any(|branch| !definitions.iter().any(|def| contains(branch, *def))) {
+                    return Some(current);
+                }
+            }
+            node = current.parent();
         }
+        None
+    })
+}
 
-        let param_text = param.text(rope);
-        let param_text = if param.kind() == "string" {
-            remove_unnecessary_escapes(&param_text[1..param_text.len() - 1])
+/// Get the value of a string or identifier predicate argument.
+fn get_argument_value(node: &Node, rope: &Rope) -> String {
+    let text = node.text(rope);
+    if node.kind() == "string" {
+        unescape_string_content(&text[1..text.len() - 1]).0
+    } else {
+        text
+    }
+}
+
+/// Whether a predicate with the given name can be used, i.e. it is recognized or no predicates are
+/// configured at all.
+fn is_valid_predicate(name: &str, valid_predicates: &BTreeMap<String, Predicate>) -> bool {
+    valid_predicates.is_empty() || valid_predicates.contains_key(name)
+}
+
+/// Evaluate a predicate of a well-known name whose arguments are all literal strings. `#match?` is
+/// only evaluated when its pattern is declared to be a regular expression of the `regex` crate,
+/// since it may be configured to use another dialect.
+fn evaluate_constant_predicate(
+    name: &str,
+    arguments: &[String],
+    valid_predicates: &BTreeMap<String, Predicate>,
+) -> Option<bool> {
+    let (negated, name) = name
+        .strip_prefix("not-")
+        .map_or((false, name), |name| (true, name));
+    let is_regex = || {
+        valid_predicates
+            .get(name)
+            .and_then(|predicate| predicate.parameters.get(1))
+            .is_some_and(|param| param.constraint == ParameterConstraint::Regex)
+    };
+    let result = match (name, arguments) {
+        ("eq", [first, rest @ ..]) if !rest.is_empty() => rest.iter().all(|arg| arg == first),
+        ("any-of", [first, rest @ ..]) => rest.contains(first),
+        ("match", [text, pattern]) if is_regex() => Regex::new(pattern).ok()?.is_match(text),
+        _ => return None,
+    };
+    Some(result != negated)
+}
+
+/// Report predicates which can be written more simply, or which always have the same result.
+fn push_predicate_simplification_diagnostics(
+    diagnostics: &mut Vec<Diagnostic>,
+    uri: &Url,
+    rope: &Rope,
+    valid_predicates: &BTreeMap<String, Predicate>,
+    name_node: Node,
+) {
+    let predicate_node = name_node.parent().unwrap();
+    let name = name_node.text(rope);
+    let arguments: Vec<_> = predicate_node
+        .named_child(2)
+        .map(|params| params.named_children(&mut params.walk()).collect())
+        .unwrap_or_default();
+    if arguments.is_empty() {
+        return;
+    }
+
+    if arguments.iter().all(|arg| arg.kind() != "capture") {
+        let values: Vec<_> = arguments
+            .iter()
+            .map(|arg| get_argument_value(arg, rope))
+            .collect();
+        // Predicates which cannot be evaluated here may depend on context other than captures,
+        // e.g. `#is-not? local`
+        let Some(always_true) = evaluate_constant_predicate(&name, &values, valid_predicates)
+        else {
+            return;
+        };
+        let message = if always_true {
+            format!(
+                "`#{name}?` only compares literal strings, so it is always true (fix available)"
+            )
         } else {
-            param_text
+            format!(
+                "`#{name}?` only compares literal strings, so it is always false and the pattern never matches"
+            )
         };
-        match &param_spec.constraint {
-            ParameterConstraint::None => None,
-            ParameterConstraint::NamedNode => {
-                let sym = &SymbolInfo {
-                    label: param_text,
-                    named: true,
+        diagnostics.push(Diagnostic {
+            message,
+            severity: HINT_SEVERITY,
+            range: predicate_node.lsp_range(rope),
+            code: DiagnosticCode::ConstantPredicate.into(),
+            tags: always_true.then(|| vec![DiagnosticTag::UNNECESSARY]),
+            data: always_true.then(|| CodeActions::RemovePredicate.into()),
+            ..Default::default()
+        });
+        return;
+    }
+
+    let Some(prefix) = name.strip_suffix("any-of") else {
+        return;
+    };
+    let values = &arguments[1..];
+    let eq_name = format!("{prefix}eq");
+    if let [value] = values
+        && value.kind() != "capture"
+        && is_valid_predicate(&eq_name, valid_predicates)
+    {
+        diagnostics.push(Diagnostic {
+            message: format!(
+                "`#{name}?` with a single value can be written as `#{eq_name}?` (fix available)"
+            ),
+            severity: HINT_SEVERITY,
+            range: predicate_node.lsp_range(rope),
+            code: DiagnosticCode::SingleValueAnyOf.into(),
+            data: Some(CodeActions::RenamePredicate.with_arguments(&[eq_name])),
+            ..Default::default()
+        });
+    }
+    let mut seen: HashMap<String, Node> = HashMap::new();
+    for value in values.iter().filter(|value| value.kind() != "capture") {
+        let text = get_argument_value(value, rope);
+        if let Some(first) = seen.get(&text) {
+            diagnostics.push(Diagnostic {
+                message: format!("Duplicate value {text:?} (fix available)"),
+                severity: HINT_SEVERITY,
+                range: value.lsp_range(rope),
+                code: DiagnosticCode::DuplicateAnyOfValue.into(),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: Some(CodeActions::RemoveDuplicateValue.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: uri.clone(),
+                        range: first.lsp_range(rope),
+                    },
+                    message: String::from("First given here"),
+                }]),
+                ..Default::default()
+            });
+        } else {
+            seen.insert(text, *value);
+        }
+    }
+}
+
+/// Get the `#eq?` predicate of a grouping, along with its capture and string arguments, if it is
+/// the only predicate of the grouping.
+fn get_grouping_eq_predicate<'a>(
+    grouping: Node<'a>,
+    rope: &Rope,
+) -> Option<(Node<'a>, String, Node<'a>)> {
+    if grouping.kind() != "grouping" || grouping.child_by_field_name("quantifier").is_some() {
+        return None;
+    }
+    let mut grouping_cursor = grouping.walk();
+    let mut predicates = grouping
+        .named_children(&mut grouping_cursor)
+        .filter(|child| child.kind() == "predicate");
+    let predicate = predicates.next()?;
+    if predicates.next().is_some() || !is_predicate(&predicate, rope) {
+        return None;
+    }
+    let name = predicate
+        .children_by_field_name("name", &mut predicate.walk())
+        .find(|node| node.kind() == "identifier")?;
+    if name.text(rope) != "eq" {
+        return None;
+    }
+    let params = predicate.named_child(2)?;
+    let mut params_cursor = params.walk();
+    let [capture, value] = params
+        .named_children(&mut params_cursor)
+        .collect::<Vec<_>>()[..]
+    else {
+        return None;
+    };
+    (capture.kind() == "capture" && value.kind() != "capture")
+        .then(|| (predicate, capture.text(rope), value))
+}
+
+/// Report alternations of patterns which only differ in the string compared by an `#eq?`
+/// predicate, and which can therefore be merged into one pattern using `#any-of?`.
+fn push_mergeable_eq_diagnostic(
+    diagnostics: &mut Vec<Diagnostic>,
+    list: Node,
+    rope: &Rope,
+    valid_predicates: &BTreeMap<String, Predicate>,
+) {
+    if !is_valid_predicate("any-of", valid_predicates)
+        || list.child_by_field_name("quantifier").is_some()
+    {
+        return;
+    }
+    let mut members = Vec::new();
+    for child in list.named_children(&mut list.walk()) {
+        match child.kind() {
+            "comment" => {}
+            "capture" => return,
+            _ => members.push(child),
+        }
+    }
+    if members.len() < 2 {
+        return;
+    }
+    let mut key = None;
+    let mut values: Vec<String> = Vec::new();
+    for member in &members {
+        let Some((predicate, capture, value)) = get_grouping_eq_predicate(*member, rope) else {
+            return;
+        };
+        // The text of the member without the compared string
+        let member_key = (
+            rope.byte_slice(member.start_byte()..value.start_byte())
+                .to_string(),
+            rope.byte_slice(value.end_byte()..member.end_byte())
+                .to_string(),
+            capture,
+        );
+        if key.get_or_insert_with(|| (member_key.clone(), predicate)).0 != member_key {
+            return;
+        }
+        let value = value.text(rope);
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+    let Some(((_, _, capture), predicate)) = key else {
+        return;
+    };
+    let first = members[0];
+    let replacement = format!(
+        "{}(#any-of? {capture} {}){}",
+        rope.byte_slice(first.start_byte()..predicate.start_byte()),
+        values.join(" "),
+        rope.byte_slice(predicate.end_byte()..first.end_byte()),
+    );
+    diagnostics.push(Diagnostic {
+        message: String::from(
+            "These patterns only differ in the string compared by `#eq?`, and can be merged using `#any-of?` (fix available)",
+        ),
+        severity: HINT_SEVERITY,
+        range: list.lsp_range(rope),
+        code: DiagnosticCode::MergeableEqPredicates.into(),
+        data: Some(CodeActions::MergeIntoAnyOf.with_arguments(&[replacement])),
+        ..Default::default()
+    });
+}
+
+/// Get the name of the equality predicate corresponding to a pattern matching predicate, e.g.
+/// `not-eq` for `not-lua-match`.
+fn get_eq_predicate_name(name: &str) -> Option<String> {
+    let prefix = name.strip_suffix("match")?;
+    let prefix = prefix
+        .strip_suffix("lua-")
+        .or_else(|| prefix.strip_suffix("vim-"))
+        .unwrap_or(prefix);
+    Some(format!("{prefix}eq"))
+}
+
+/// Validate the parameters of a predicate or directive, as well as the key and value of a
+/// key/value directive.
+fn validate_predicate(
+    diagnostics: &mut Vec<Diagnostic>,
+    rope: &Rope,
+    language_data: Option<&Arc<LanguageData>>,
+    options: &Options,
+    parser_names: &ParserNamesCache,
+    predicate: &Predicate,
+    predicate_node: Node,
+) {
+    let params_node = predicate_node.parent().unwrap().named_child(2);
+    let mut tree_cursor = predicate_node.walk();
+    // Fixed-string patterns can only be replaced by equality checks in predicates
+    let is_predicate = is_predicate(&predicate_node.parent().unwrap(), rope);
+    let valid_predicates = is_predicate.then_some(&options.valid_predicates);
+    let mut param_spec_iter = predicate.parameters.iter().peekable();
+    let mut prev_param_spec = param_spec_iter.peek().copied();
+
+    let param_type_mismatch = |param: Node, param_spec: &PredicateParameter| {
+        let range = param.lsp_range(rope);
+        let is_capture = param.kind() == "capture";
+        let severity = WARNING_SEVERITY;
+        if is_capture && param_spec.type_ == PredicateParameterType::String
+            || !is_capture && param_spec.type_ == PredicateParameterType::Capture
+        {
+            return Some(Diagnostic {
+                message: format!(
+                    "Parameter type mismatch: expected \"{}\", got \"{}\"",
+                    param_spec.type_,
+                    if is_capture { "capture" } else { "string" }
+                ),
+                severity,
+                range,
+                code: DiagnosticCode::ParameterTypeMismatch.into(),
+                ..Default::default()
+            });
+        }
+
+        if is_capture {
+            return None;
+        }
+
+        let param_text = param.text(rope);
+        let param_text = if param.kind() == "string" {
+            remove_unnecessary_escapes(&param_text[1..param_text.len() - 1])
+        } else {
+            param_text
+        };
+        match &param_spec.constraint {
+            ParameterConstraint::None => None,
+            ParameterConstraint::NamedNode => {
+                let sym = &SymbolInfo {
+                    label: param_text,
+                    named: true,
                 };
                 if let Some(ld) = language_data
                     && !ld.symbols_set.contains(sym)
@@ -934,6 +1835,43 @@ fn validate_predicate<'a>(
                     })
                 }
             }
+            ParameterConstraint::IntegerRange(integer_range) => match param_text.parse::<i64>() {
+                Ok(value) if integer_range.contains(&value) => None,
+                Ok(value) => Some(Diagnostic {
+                    message: format!(
+                        "Expected an integer from {} to {}, got {value}",
+                        integer_range.start(),
+                        integer_range.end()
+                    ),
+                    severity,
+                    range,
+                    code: DiagnosticCode::InvalidInteger.into(),
+                    ..Default::default()
+                }),
+                Err(_) => Some(Diagnostic {
+                    message: format!("Expected a valid integer, got {param_text:?}"),
+                    severity,
+                    range,
+                    code: DiagnosticCode::InvalidInteger.into(),
+                    ..Default::default()
+                }),
+            },
+            ParameterConstraint::Language => {
+                // Injected languages are often not installed, so only check them when the
+                // installed parsers are known
+                let languages = get_available_languages(parser_names, options);
+                if languages.is_none_or(|languages| languages.contains(&param_text)) {
+                    None
+                } else {
+                    Some(Diagnostic {
+                        message: format!("Unknown language {param_text:?}"),
+                        severity,
+                        range,
+                        code: DiagnosticCode::InvalidLanguage.into(),
+                        ..Default::default()
+                    })
+                }
+            }
             ParameterConstraint::Enum(values) => {
                 if values.contains(&param_text) {
                     None
@@ -947,11 +1885,53 @@ fn validate_predicate<'a>(
                     })
                 }
             }
+            constraint @ (ParameterConstraint::Regex
+            | ParameterConstraint::LuaPattern
+            | ParameterConstraint::VimRegex) => {
+                let (content, content_start) = if param.kind() == "string" {
+                    let text = param.text(rope);
+                    (text[1..text.len() - 1].to_owned(), param.start_byte() + 1)
+                } else {
+                    (param.text(rope), param.start_byte())
+                };
+                let (pattern, offsets) = unescape_string_content(&content);
+                if let Some(error) = get_pattern_error(constraint, &pattern) {
+                    let start = content_start + offsets[error.range.start];
+                    let end = content_start + offsets[error.range.end];
+                    return Some(Diagnostic {
+                        message: format!("Invalid {constraint}: {}", error.message),
+                        severity,
+                        range: Range::new(start.to_lsp_pos(rope), end.to_lsp_pos(rope)),
+                        code: DiagnosticCode::InvalidRegexPattern.into(),
+                        ..Default::default()
+                    });
+                }
+                let valid_predicates = valid_predicates?;
+                let fixed = get_fixed_string(constraint, &pattern)?;
+                let eq_name = get_eq_predicate_name(&predicate_node.text(rope))?;
+                let fixable = valid_predicates.contains_key(&eq_name);
+                Some(Diagnostic {
+                    message: format!(
+                        "This {constraint} only matches the string {fixed:?}, consider `#{eq_name}?`{}",
+                        if fixable { " (fix available)" } else { "" }
+                    ),
+                    severity: HINT_SEVERITY,
+                    range,
+                    code: DiagnosticCode::FixedStringPattern.into(),
+                    data: fixable.then(|| {
+                        CodeActions::ReplaceWithEq.with_arguments(&[
+                            eq_name,
+                            format!("\"{}\"", escape_string_content(&fixed)),
+                        ])
+                    }),
+                    ..Default::default()
+                })
+            }
         }
     };
 
     if let Some(params_node) = params_node {
-        for param in params_node.children(tree_cursor) {
+        for param in params_node.children(&mut tree_cursor) {
             if let Some(param_spec) = param_spec_iter.next() {
                 if let Some(diag) = param_type_mismatch(param, param_spec) {
                     diagnostics.push(diag);
@@ -974,6 +1954,46 @@ fn validate_predicate<'a>(
             }
         }
     }
+    if !predicate.keys.is_empty()
+        && let Some(params_node) = params_node
+    {
+        let mut strings = params_node
+            .named_children(&mut tree_cursor)
+            .filter(|param| param.kind() != "capture");
+        if let Some(key_node) = strings.next() {
+            let key = key_node.text(rope);
+            let key = if key_node.kind() == "string" {
+                unescape_string_content(&key[1..key.len() - 1]).0
+            } else {
+                key
+            };
+            if let Some(key_spec) = predicate.keys.get(&key) {
+                if let Some(value_node) = strings.next() {
+                    let value_spec = PredicateParameter {
+                        description: None,
+                        type_: PredicateParameterType::String,
+                        arity: PredicateParameterArity::Required,
+                        constraint: key_spec.value.clone(),
+                    };
+                    if let Some(diag) = param_type_mismatch(value_node, &value_spec) {
+                        diagnostics.push(diag);
+                    }
+                }
+            } else {
+                diagnostics.push(Diagnostic {
+                    message: format!(
+                        "Unrecognized key {key:?} for `#{}{}`",
+                        predicate_node.text(rope),
+                        if is_predicate { "?" } else { "!" }
+                    ),
+                    severity: WARNING_SEVERITY,
+                    range: key_node.lsp_range(rope),
+                    code: DiagnosticCode::UnrecognizedKey.into(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
     if let Some(PredicateParameter {
         type_,
         arity: PredicateParameterArity::Required,
@@ -994,10 +2014,15 @@ fn validate_predicate<'a>(
 mod test {
     use std::{
         collections::{BTreeMap, HashMap},
+        env::temp_dir,
+        fs,
+        path::Path,
         sync::LazyLock,
+        time::{Duration, SystemTime},
     };
 
     use pretty_assertions::assert_eq;
+    use regex::Regex;
     use rstest::rstest;
     use tower_lsp::lsp_types::{
         Diagnostic, DiagnosticRelatedInformation, DiagnosticTag, DocumentDiagnosticParams,
@@ -1007,8 +2032,9 @@ mod test {
         request::DocumentDiagnosticRequest,
     };
     use ts_query_ls::{
-        DiagnosticOptions, Options, ParameterConstraint, Predicate, PredicateParameter,
-        PredicateParameterArity, PredicateParameterType, StringArgumentStyle,
+        DiagnosticOptions, DirectiveKey, Options, ParameterConstraint, PatternPrecedence,
+        Predicate, PredicateParameter, PredicateParameterArity, PredicateParameterType,
+        StringArgumentStyle,
     };
 
     use crate::{
@@ -1037,6 +2063,17 @@ mod test {
         .unwrap()
     });
 
+    /// A parser install directory holding empty files of the given names.
+    fn parser_directory(name: &str, file_names: &[&str]) -> String {
+        let directory = temp_dir().join("ts-query-ls").join("parsers").join(name);
+        _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).expect("Failed to create parser directory");
+        for file_name in file_names {
+            fs::write(directory.join(file_name), "").expect("Failed to write parser file");
+        }
+        directory.to_string_lossy().into_owned()
+    }
+
     #[rstest]
     #[case(
         &[(
@@ -1220,7 +2257,10 @@ mod test {
             },
             severity: ERROR_SEVERITY,
             code: DiagnosticCode::InvalidNode.into(),
-            message: String::from("Invalid node type: \"identifierr\""),
+            message: String::from(
+                "Invalid node type: \"identifierr\", did you mean \"identifier\"? (fix available)"
+            ),
+            data: Some(CodeActions::Replace.with_arguments(&[String::from("identifier")])),
             ..Default::default()
         }],
         None,
@@ -1258,6 +2298,7 @@ mod test {
                     type_: PredicateParameterType::Any,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1266,6 +2307,92 @@ mod test {
         &[],
         None,
     )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r#"((identifier) @cap
+(#set! priority 300)
+(#set! "prio" 105)
+(#set! @cap injection.language rsut)
+(#set! injection.language "ecma")
+(#set! injection.language rust)
+(#set! injection.language javascript)
+(#set! priority))"#,
+        )],
+        Options {
+            valid_directives: BTreeMap::from([(String::from("set"), Predicate {
+                description: String::from("Set metadata"),
+                parameters: vec![PredicateParameter {
+                    type_: PredicateParameterType::Any,
+                    arity: PredicateParameterArity::Variadic,
+                    ..Default::default()
+                }],
+                keys: BTreeMap::from([
+                    (String::from("priority"), DirectiveKey {
+                        description: None,
+                        value: ParameterConstraint::IntegerRange(0..=200),
+                    }),
+                    (String::from("injection.language"), DirectiveKey {
+                        description: None,
+                        value: ParameterConstraint::Language,
+                    }),
+                ]),
+                ..Default::default()
+            })]),
+            parser_aliases: BTreeMap::from([(String::from("ecma"), String::from("javascript"))]),
+            parser_install_directories: vec![parser_directory("set_directive", &["tree-sitter-rust.wasm"])],
+            ..Default::default()
+        },
+        &[
+            Diagnostic {
+                message: String::from("Expected an integer from 0 to 200, got 300"),
+                severity: WARNING_SEVERITY,
+                range: Range::new(Position::new(1, 16), Position::new(1, 19)),
+                code: DiagnosticCode::InvalidInteger.into(),
+                ..Default::default()
+            },
+            Diagnostic {
+                message: String::from("Unrecognized key \"prio\" for `#set!`"),
+                severity: WARNING_SEVERITY,
+                range: Range::new(Position::new(2, 7), Position::new(2, 13)),
+                code: DiagnosticCode::UnrecognizedKey.into(),
+                ..Default::default()
+            },
+            Diagnostic {
+                message: String::from("Unknown language \"rsut\""),
+                severity: WARNING_SEVERITY,
+                range: Range::new(Position::new(3, 31), Position::new(3, 35)),
+                code: DiagnosticCode::InvalidLanguage.into(),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            "((identifier) @cap\n(#set! injection.language rsut))",
+        )],
+        Options {
+            valid_directives: BTreeMap::from([(String::from("set"), Predicate {
+                description: String::from("Set metadata"),
+                parameters: vec![PredicateParameter {
+                    type_: PredicateParameterType::Any,
+                    arity: PredicateParameterArity::Variadic,
+                    ..Default::default()
+                }],
+                keys: BTreeMap::from([(String::from("injection.language"), DirectiveKey {
+                    description: None,
+                    value: ParameterConstraint::Language,
+                })]),
+                ..Default::default()
+            })]),
+            parser_aliases: BTreeMap::from([(String::from("ecma"), String::from("javascript"))]),
+            ..Default::default()
+        },
+        &[],
+        None,
+    )]
     #[case(
         &[(
             QUERY_TEST_URI.clone(),
@@ -1282,6 +2409,7 @@ mod test {
                     type_: PredicateParameterType::Any,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1319,6 +2447,7 @@ mod test {
                     type_: PredicateParameterType::Any,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1358,6 +2487,7 @@ mod test {
                     arity: PredicateParameterArity::Variadic,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1388,6 +2518,7 @@ mod test {
                     arity: PredicateParameterArity::Variadic,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1431,6 +2562,7 @@ mod test {
                     arity: PredicateParameterArity::Variadic,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1483,6 +2615,7 @@ mod test {
                     arity: PredicateParameterArity::Variadic,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1522,6 +2655,7 @@ mod test {
                     arity: PredicateParameterArity::Variadic,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1563,6 +2697,7 @@ mod test {
                     arity: PredicateParameterArity::Variadic,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1589,6 +2724,7 @@ mod test {
                     arity: PredicateParameterArity::Variadic,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1615,6 +2751,7 @@ mod test {
                     arity: PredicateParameterArity::Optional,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1641,6 +2778,7 @@ mod test {
                     arity: PredicateParameterArity::Optional,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1667,6 +2805,7 @@ mod test {
                     arity: PredicateParameterArity::Optional,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1708,6 +2847,7 @@ mod test {
                     arity: PredicateParameterArity::Variadic,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             valid_captures: HashMap::from([(String::from("test"),
                 BTreeMap::from([(String::from("variable.builtin"), String::default())]))]),
@@ -1723,10 +2863,10 @@ mod test {
                 code: DiagnosticCode::UnrecognizedDirective.into(),
                 code_description: None,
                 source: None,
-                message: String::from("Unrecognized directive \"sett\""),
+                message: String::from("Unrecognized directive \"sett\", did you mean \"set\"? (fix available)"),
                 related_information: None,
                 tags: None,
-                data: None,
+                data: Some(CodeActions::Replace.with_arguments(&[String::from("set")])),
             },
         ],
         None,
@@ -2122,6 +3262,7 @@ mod test {
                     constraint: ParameterConstraint::NamedNode,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             ..Default::default()
         },
@@ -2169,6 +3310,7 @@ mod test {
                     constraint: ParameterConstraint::NamedNode,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             ..Default::default()
         },
@@ -2216,6 +3358,7 @@ mod test {
                     constraint: ParameterConstraint::Integer,
                     ..Default::default()
                 }],
+                ..Default::default()
             })]),
             ..Default::default()
         },
@@ -2266,6 +3409,7 @@ mod test {
                         ..Default::default()
                     },
                 ],
+                ..Default::default()
             })]),
             ..Default::default()
         },
@@ -2311,8 +3455,8 @@ mod test {
                     },
                 },
                 severity: WARNING_SEVERITY,
-                message: String::from("Invalid capture name \"@variable.builtn\" (fix available)"),
-                data: Some(CodeActions::PrefixUnderscore.into()),
+                message: String::from("Invalid capture name \"@variable.builtn\", did you mean \"@variable.builtin\"? (fix available)"),
+                data: Some(CodeActions::RenameCapture.with_arguments(&[String::from("@variable.builtin")])),
                 code: DiagnosticCode::InvalidCaptureName.into(),
                 ..Default::default()
             },
@@ -2341,7 +3485,7 @@ mod test {
                                         range: Range::new(Position::new(0, 14), Position::new(0, 30)),
                                         uri: CPP_HIGHLIGHTS_WS_URI.clone()
                                     },
-                                    message: String::from("Invalid capture name \"@variable.builtn\" (fix available)")
+                                    message: String::from("Invalid capture name \"@variable.builtn\", did you mean \"@variable.builtin\"? (fix available)")
                                 }
                             ]),
                             ..Default::default()
@@ -2358,6 +3502,80 @@ mod test {
             )
         ])),
     )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r"((identifier) @function.method.call.rust @injection.language @functio.call)",
+        )],
+        Options {
+            valid_captures: HashMap::from([(
+                String::from("test"),
+                BTreeMap::from([(String::from("function.method.call"), String::default())]),
+            )]),
+            valid_capture_patterns: HashMap::from([(
+                String::from("test"),
+                vec![Regex::new(r"^injection\..*").unwrap().into()],
+            )]),
+            hierarchical_captures: true,
+            ..Default::default()
+        },
+        &[
+            Diagnostic {
+                range: Range::new(Position::new(0, 61), Position::new(0, 74)),
+                severity: WARNING_SEVERITY,
+                message: String::from("Invalid capture name \"@functio.call\" (fix available)"),
+                data: Some(CodeActions::PrefixUnderscore.into()),
+                code: DiagnosticCode::InvalidCaptureName.into(),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r"((identifier) @function.method.call.rust @injection.language @functio.call @function.metod.call)",
+        )],
+        Options {
+            valid_captures: HashMap::from([(
+                String::from("test"),
+                BTreeMap::from([(String::from("function.method.call"), String::default())]),
+            )]),
+            valid_capture_patterns: HashMap::from([(
+                String::from("test"),
+                vec![Regex::new(r"^injection\..*").unwrap().into()],
+            )]),
+            hierarchical_captures: false,
+            ..Default::default()
+        },
+        &[
+            Diagnostic {
+                range: Range::new(Position::new(0, 14), Position::new(0, 40)),
+                severity: WARNING_SEVERITY,
+                message: String::from("Invalid capture name \"@function.method.call.rust\" (fix available)"),
+                data: Some(CodeActions::PrefixUnderscore.into()),
+                code: DiagnosticCode::InvalidCaptureName.into(),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(0, 61), Position::new(0, 74)),
+                severity: WARNING_SEVERITY,
+                message: String::from("Invalid capture name \"@functio.call\" (fix available)"),
+                data: Some(CodeActions::PrefixUnderscore.into()),
+                code: DiagnosticCode::InvalidCaptureName.into(),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(0, 75), Position::new(0, 95)),
+                severity: WARNING_SEVERITY,
+                message: String::from("Invalid capture name \"@function.metod.call\", did you mean \"@function.method.call\"? (fix available)"),
+                data: Some(CodeActions::RenameCapture.with_arguments(&[String::from("@function.method.call")])),
+                code: DiagnosticCode::InvalidCaptureName.into(),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
     #[case(
         &[(
             QUERY_TEST_URI.clone(),
@@ -2367,6 +3585,7 @@ mod test {
             valid_directives: BTreeMap::from([(String::from("offset"), Predicate {
                 description: String::from("Offsets a node's range"),
                 parameters: vec![],
+                ..Default::default()
             })]),
             ..Default::default()
         },
@@ -2388,6 +3607,7 @@ mod test {
                         ..Default::default()
                     },
                 ],
+                ..Default::default()
             })]),
             ..Default::default()
         },
@@ -2411,6 +3631,7 @@ mod test {
             valid_directives: BTreeMap::from([(String::from("offset"), Predicate {
                 description: String::from("Offsets a node's range"),
                 parameters: vec![],
+                ..Default::default()
             })]),
             ..Default::default()
         },
@@ -2446,16 +3667,54 @@ mod test {
             valid_directives: BTreeMap::from([(String::from("offset"), Predicate {
                 description: String::from("Offsets a node's range"),
                 parameters: vec![],
+                ..Default::default()
             })]),
             ..Default::default()
         },
         &[
+            Diagnostic {
+                range: Range::new(Position::new(0, 2), Position::new(0, 14)),
+                severity: HINT_SEVERITY,
+                message: String::from("(identifier) already captured by _ in this alternation (fix available)"),
+                code: DiagnosticCode::RedundantAlternant.into(),
+                data: Some(CodeActions::Remove.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(0, 63), Position::new(0, 64)),
+                    },
+                    message: String::from("Already captured here"),
+                }]),
+                ..Default::default()
+            },
             Diagnostic {
                 range: Range::new(Position::new(0, 15), Position::new(0, 27)),
                 severity: HINT_SEVERITY,
                 message: String::from("(identifier) already captured by this alternation (fix available)"),
                 code: DiagnosticCode::RedundantAlternant.into(),
                 data: Some(CodeActions::Remove.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(0, 2), Position::new(0, 14)),
+                    },
+                    message: String::from("Already captured here"),
+                }]),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(0, 28), Position::new(0, 37)),
+                severity: HINT_SEVERITY,
+                message: String::from("\"MISSING\" already captured by _ in this alternation (fix available)"),
+                code: DiagnosticCode::RedundantAlternant.into(),
+                data: Some(CodeActions::Remove.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(0, 63), Position::new(0, 64)),
+                    },
+                    message: String::from("Already captured here"),
+                }]),
                 ..Default::default()
             },
             Diagnostic {
@@ -2464,6 +3723,13 @@ mod test {
                 message: String::from("\"MISSING\" already captured by this alternation (fix available)"),
                 code: DiagnosticCode::RedundantAlternant.into(),
                 data: Some(CodeActions::Remove.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(0, 28), Position::new(0, 37)),
+                    },
+                    message: String::from("Already captured here"),
+                }]),
                 ..Default::default()
             },
             Diagnostic {
@@ -2472,6 +3738,13 @@ mod test {
                 message: String::from("\"MISSING\" already captured by this alternation (fix available)"),
                 code: DiagnosticCode::RedundantAlternant.into(),
                 data: Some(CodeActions::Remove.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(0, 28), Position::new(0, 37)),
+                    },
+                    message: String::from("Already captured here"),
+                }]),
                 ..Default::default()
             },
             Diagnostic {
@@ -2480,6 +3753,495 @@ mod test {
                 message: String::from("_ already captured by this alternation (fix available)"),
                 code: DiagnosticCode::RedundantAlternant.into(),
                 data: Some(CodeActions::Remove.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(0, 63), Position::new(0, 64)),
+                    },
+                    message: String::from("Already captured here"),
+                }]),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r#"[(definition) (named_node) @a (named_node (identifier)) (identifier)] @cap
+["(" "\(" (_) (list)] @cap"#,
+        )],
+        Options::default(),
+        &[
+            Diagnostic {
+                range: Range::new(Position::new(0, 30), Position::new(0, 55)),
+                severity: HINT_SEVERITY,
+                message: String::from("(named_node) already captured by (definition) in this alternation (fix available)"),
+                code: DiagnosticCode::RedundantAlternant.into(),
+                data: Some(CodeActions::Remove.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(0, 1), Position::new(0, 13)),
+                    },
+                    message: String::from("Already captured here"),
+                }]),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(1, 5), Position::new(1, 9)),
+                severity: HINT_SEVERITY,
+                message: String::from("\"\\(\" already captured by this alternation (fix available)"),
+                code: DiagnosticCode::RedundantAlternant.into(),
+                data: Some(CodeActions::Remove.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(1, 1), Position::new(1, 4)),
+                    },
+                    message: String::from("Already captured here"),
+                }]),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(1, 14), Position::new(1, 20)),
+                severity: HINT_SEVERITY,
+                message: String::from("(list) already captured by (_) in this alternation (fix available)"),
+                code: DiagnosticCode::RedundantAlternant.into(),
+                data: Some(CodeActions::Remove.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(1, 10), Position::new(1, 13)),
+                    },
+                    message: String::from("Already captured here"),
+                }]),
+                ..Default::default()
+            },
+            Diagnostic {
+                message: String::from("Unnecessary escape sequence (fix available)"),
+                severity: WARNING_SEVERITY,
+                range: Range::new(Position::new(1, 6), Position::new(1, 8)),
+                data: Some(CodeActions::RemoveBackslash.into()),
+                code: DiagnosticCode::UnnecessaryEscapeSequence.into(),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r#"(identifier) @variable
+((identifier)
+  @variable) ; comment
+((identifier) @variable (#eq? @variable "self"))
+(identifier) @constant"#,
+        )],
+        Options::default(),
+        &[
+            Diagnostic {
+                range: Range::new(Position::new(1, 0), Position::new(2, 12)),
+                severity: WARNING_SEVERITY,
+                message: String::from("Duplicate pattern (fix available)"),
+                code: DiagnosticCode::DuplicatePattern.into(),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: Some(CodeActions::RemoveRedundantPattern.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(0, 0), Position::new(0, 22)),
+                    },
+                    message: String::from("First defined here"),
+                }]),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(3, 0), Position::new(3, 48)),
+                severity: WARNING_SEVERITY,
+                message: String::from(
+                    "Redundant pattern, since an unconditional pattern captures the same nodes (fix available)"
+                ),
+                code: DiagnosticCode::RedundantPattern.into(),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: Some(CodeActions::RemoveRedundantPattern.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(0, 0), Position::new(0, 22)),
+                    },
+                    message: String::from("Captured unconditionally here"),
+                }]),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r#"((identifier) @constant (#match? @constant "^[A-Z]"))
+(identifier) @variable"#,
+        )],
+        Options {
+            diagnostic_options: DiagnosticOptions {
+                pattern_precedence: PatternPrecedence::FirstWins,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        &[],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r#"((identifier) @constant (#match? @constant "^[A-Z]"))
+(identifier) @variable"#,
+        )],
+        Options {
+            diagnostic_options: DiagnosticOptions {
+                pattern_precedence: PatternPrecedence::LastWins,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        &[
+            Diagnostic {
+                range: Range::new(Position::new(0, 0), Position::new(0, 53)),
+                severity: WARNING_SEVERITY,
+                message: String::from(
+                    "This pattern can never take effect, since it is shadowed by an unconditional pattern"
+                ),
+                code: DiagnosticCode::ShadowedPattern.into(),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(1, 0), Position::new(1, 22)),
+                    },
+                    message: String::from("Shadowed by this pattern"),
+                }]),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            FOO_HIGHLIGHTS_WS_URI.clone(),
+            "; inherits: cpp\n(identifier) @variable",
+        ), (
+            CPP_HIGHLIGHTS_WS_URI.clone(),
+            "(identifier) @variable",
+        )],
+        Options::default(),
+        &[
+            Diagnostic {
+                range: Range::new(Position::new(1, 0), Position::new(1, 22)),
+                severity: WARNING_SEVERITY,
+                message: String::from("Duplicate pattern (fix available)"),
+                code: DiagnosticCode::DuplicatePattern.into(),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: Some(CodeActions::RemoveRedundantPattern.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: CPP_HIGHLIGHTS_WS_URI.clone(),
+                        range: Range::new(Position::new(0, 0), Position::new(0, 22)),
+                    },
+                    message: String::from("First defined here"),
+                }]),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::default(),
+                severity: WARNING_SEVERITY,
+                message: String::from("Language object for \"foo\" not found"),
+                code: DiagnosticCode::NoLanguageObject.into(),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r#"((identifier) @a (#match? @a "^(foo"))
+((identifier) @a (#lua-match? @a "%d(%1)"))
+((identifier) @a (#vim-match? @a "\\v(a|b"))
+((identifier) @a (#match? @a "^se\\.lf$"))
+((identifier) @a (#not-lua-match? @a "^foo$"))
+((identifier) @a (#lua-match? @a "^[A-Z]"))"#,
+        )],
+        Options {
+            valid_predicates: BTreeMap::from(
+                [
+                    ("match", ParameterConstraint::Regex),
+                    ("lua-match", ParameterConstraint::LuaPattern),
+                    ("vim-match", ParameterConstraint::VimRegex),
+                    ("eq", ParameterConstraint::None),
+                ]
+                .map(|(name, constraint)| (String::from(name), Predicate {
+                    description: String::new(),
+                    parameters: vec![
+                        PredicateParameter {
+                            type_: PredicateParameterType::Capture,
+                            ..Default::default()
+                        },
+                        PredicateParameter {
+                            type_: PredicateParameterType::String,
+                            constraint,
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                })),
+            ),
+            ..Default::default()
+        },
+        &[
+            Diagnostic {
+                range: Range::new(Position::new(0, 31), Position::new(0, 32)),
+                severity: WARNING_SEVERITY,
+                message: String::from("Invalid regular expression: unclosed group"),
+                code: DiagnosticCode::InvalidRegexPattern.into(),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(1, 37), Position::new(1, 39)),
+                severity: WARNING_SEVERITY,
+                message: String::from("Invalid Lua pattern: invalid capture index %1"),
+                code: DiagnosticCode::InvalidRegexPattern.into(),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(2, 37), Position::new(2, 38)),
+                severity: WARNING_SEVERITY,
+                message: String::from("Invalid Vim regular expression: unmatched ("),
+                code: DiagnosticCode::InvalidRegexPattern.into(),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(3, 29), Position::new(3, 40)),
+                severity: HINT_SEVERITY,
+                message: String::from(
+                    "This regular expression only matches the string \"se.lf\", consider `#eq?` (fix available)"
+                ),
+                code: DiagnosticCode::FixedStringPattern.into(),
+                data: Some(CodeActions::ReplaceWithEq.with_arguments(&[
+                    String::from("eq"),
+                    String::from("\"se.lf\""),
+                ])),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(4, 37), Position::new(4, 44)),
+                severity: HINT_SEVERITY,
+                message: String::from(
+                    "This Lua pattern only matches the string \"foo\", consider `#not-eq?` (fix available)"
+                ),
+                code: DiagnosticCode::FixedStringPattern.into(),
+                data: Some(CodeActions::ReplaceWithEq.with_arguments(&[
+                    String::from("not-eq"),
+                    String::from("\"foo\""),
+                ])),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r#"((comment)+ @c (#eq? @c "x"))
+([(identifier) @i (comment)] (#eq? @i "y"))
+[((identifier) @j (#eq? @j "z")) (comment)] @k
+((comment)? @d (#not-eq? @d "w"))"#,
+        )],
+        Options {
+            valid_predicates: BTreeMap::from(["eq", "any-eq"].map(|name| (String::from(name), Predicate {
+                description: String::new(),
+                parameters: vec![
+                    PredicateParameter {
+                        type_: PredicateParameterType::Capture,
+                        ..Default::default()
+                    },
+                    PredicateParameter {
+                        type_: PredicateParameterType::String,
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }))),
+            ..Default::default()
+        },
+        &[
+            Diagnostic {
+                range: Range::new(Position::new(0, 21), Position::new(0, 23)),
+                severity: WARNING_SEVERITY,
+                message: String::from(
+                    "Quantified capture \"@c\" passed to `#eq?`, which must hold for every captured node; consider `#any-eq?` (fix available)"
+                ),
+                code: DiagnosticCode::QuantifiedCaptureArgument.into(),
+                data: Some(CodeActions::UseAnyPredicate.with_arguments(&[String::from("any-eq")])),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(1, 35), Position::new(1, 37)),
+                severity: WARNING_SEVERITY,
+                message: String::from(
+                    "Capture \"@i\" is not defined in every branch of an alternation, so this predicate may not apply when another branch matches"
+                ),
+                code: DiagnosticCode::PartiallyDefinedCapture.into(),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(1, 1), Position::new(1, 28)),
+                    },
+                    message: String::from("Alternation defined here"),
+                }]),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r#"((identifier) @x (#any-of? @x "a"))
+((identifier) @x (#not-any-of? @x "a" "b" "a" b))
+((identifier) @x (#eq? "a" "a"))
+((identifier) @x (#not-match? "foo" "^f"))
+((identifier) @x (#foo? "a"))
+[
+  ((identifier) @x (#eq? @x "a"))
+  ; comment
+  ((identifier) @x (#eq? @x "b"))
+]"#,
+        )],
+        Options::default(),
+        &[
+            Diagnostic {
+                message: String::from("`#any-of?` with a single value can be written as `#eq?` (fix available)"),
+                severity: HINT_SEVERITY,
+                range: Range::new(Position::new(0, 17), Position::new(0, 34)),
+                code: DiagnosticCode::SingleValueAnyOf.into(),
+                data: Some(CodeActions::RenamePredicate.with_arguments(&[String::from("eq")])),
+                ..Default::default()
+            },
+            Diagnostic {
+                message: String::from("Duplicate value \"a\" (fix available)"),
+                severity: HINT_SEVERITY,
+                range: Range::new(Position::new(1, 42), Position::new(1, 45)),
+                code: DiagnosticCode::DuplicateAnyOfValue.into(),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: Some(CodeActions::RemoveDuplicateValue.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(1, 34), Position::new(1, 37)),
+                    },
+                    message: String::from("First given here"),
+                }]),
+                ..Default::default()
+            },
+            Diagnostic {
+                message: String::from("Duplicate value \"b\" (fix available)"),
+                severity: HINT_SEVERITY,
+                range: Range::new(Position::new(1, 46), Position::new(1, 47)),
+                code: DiagnosticCode::DuplicateAnyOfValue.into(),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: Some(CodeActions::RemoveDuplicateValue.into()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: QUERY_TEST_URI.clone(),
+                        range: Range::new(Position::new(1, 38), Position::new(1, 41)),
+                    },
+                    message: String::from("First given here"),
+                }]),
+                ..Default::default()
+            },
+            Diagnostic {
+                message: String::from("`#eq?` only compares literal strings, so it is always true (fix available)"),
+                severity: HINT_SEVERITY,
+                range: Range::new(Position::new(2, 17), Position::new(2, 31)),
+                code: DiagnosticCode::ConstantPredicate.into(),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: Some(CodeActions::RemovePredicate.into()),
+                ..Default::default()
+            },
+            Diagnostic {
+                message: String::from("These patterns only differ in the string compared by `#eq?`, and can be merged using `#any-of?` (fix available)"),
+                severity: HINT_SEVERITY,
+                range: Range::new(Position::new(5, 0), Position::new(9, 1)),
+                code: DiagnosticCode::MergeableEqPredicates.into(),
+                data: Some(CodeActions::MergeIntoAnyOf.with_arguments(&[String::from(
+                    r#"((identifier) @x (#any-of? @x "a" "b"))"#,
+                )])),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            r#"((identifier) @x (#not-match? "foo" "^f"))"#,
+        )],
+        Options {
+            valid_predicates: BTreeMap::from([(String::from("match"), Predicate {
+                parameters: vec![
+                    PredicateParameter {
+                        type_: PredicateParameterType::Any,
+                        ..Default::default()
+                    },
+                    PredicateParameter {
+                        type_: PredicateParameterType::String,
+                        constraint: ParameterConstraint::Regex,
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            })]),
+            ..Default::default()
+        },
+        &[
+            Diagnostic {
+                message: String::from("`#not-match?` only compares literal strings, so it is always false and the pattern never matches"),
+                severity: HINT_SEVERITY,
+                range: Range::new(Position::new(0, 17), Position::new(0, 41)),
+                code: DiagnosticCode::ConstantPredicate.into(),
+                ..Default::default()
+            },
+        ],
+        None,
+    )]
+    #[case(
+        &[(
+            QUERY_TEST_URI.clone(),
+            "; format-ignore-start\n(identifier) @variable\n; format-ignore-end
+((identifier) @function
+  ; format-ignore-start
+  (#eq? @function \"foo\"))
+; format-ignore-start\n(string) @string",
+        )],
+        Options::default(),
+        &[
+            Diagnostic {
+                range: Range::new(Position::new(4, 2), Position::new(4, 23)),
+                severity: WARNING_SEVERITY,
+                message: String::from(
+                    "Unterminated `format-ignore-start` comment; expected a `format-ignore-end` comment in the same node"
+                ),
+                code: DiagnosticCode::UnterminatedFormatIgnore.into(),
+                ..Default::default()
+            },
+            Diagnostic {
+                range: Range::new(Position::new(6, 0), Position::new(6, 21)),
+                severity: WARNING_SEVERITY,
+                message: String::from(
+                    "Unterminated `format-ignore-start` comment; expected a `format-ignore-end` comment in the same node"
+                ),
+                code: DiagnosticCode::UnterminatedFormatIgnore.into(),
                 ..Default::default()
             },
         ],
@@ -2522,4 +4284,78 @@ mod test {
             actual_diagnostics
         );
     }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn server_diagnostics_installed_parser() {
+        // Arrange
+        let directory = parser_directory("installed_parser", &[]);
+        let options = Options {
+            valid_directives: BTreeMap::from([(
+                String::from("set"),
+                Predicate {
+                    parameters: vec![PredicateParameter {
+                        arity: PredicateParameterArity::Variadic,
+                        ..Default::default()
+                    }],
+                    keys: BTreeMap::from([(
+                        String::from("injection.language"),
+                        DirectiveKey {
+                            description: None,
+                            value: ParameterConstraint::Language,
+                        },
+                    )]),
+                    ..Default::default()
+                },
+            )]),
+            parser_install_directories: vec![directory.clone()],
+            ..Default::default()
+        };
+        let source = "((identifier) @cap\n(#set! injection.language rust))";
+        let mut service = initialize_server(&[(QUERY_TEST_URI.clone(), source)], &options).await;
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier {
+                uri: QUERY_TEST_URI.clone(),
+            },
+            identifier: None,
+            previous_result_id: None,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let diagnostics_before = service
+            .request::<DocumentDiagnosticRequest>(params.clone())
+            .await;
+
+        // Act
+        fs::write(Path::new(&directory).join("tree-sitter-rust.wasm"), "").unwrap();
+        // The parser may be installed within the resolution of the directory modification time
+        fs::File::open(&directory)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+        let diagnostics_after = service.request::<DocumentDiagnosticRequest>(params).await;
+
+        // Assert
+        let report = |items: Vec<Diagnostic>| {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(
+                RelatedFullDocumentDiagnosticReport {
+                    related_documents: None,
+                    full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                        result_id: None,
+                        items,
+                    },
+                },
+            ))
+        };
+        assert_eq!(
+            report(vec![Diagnostic {
+                message: String::from("Unknown language \"rust\""),
+                severity: WARNING_SEVERITY,
+                range: Range::new(Position::new(1, 26), Position::new(1, 30)),
+                code: DiagnosticCode::InvalidLanguage.into(),
+                ..Default::default()
+            }]),
+            diagnostics_before
+        );
+        assert_eq!(report(Vec::new()), diagnostics_after);
+    }
 }
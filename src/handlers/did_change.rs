@@ -6,7 +6,7 @@ use crate::{
     util::{ByteUtil, TextDocChangeUtil, edit_rope, get_imported_uris, parse, push_diagnostics},
 };
 
-use super::did_open::populate_import_documents;
+use super::{did_open::populate_import_documents, workspace_symbol::update_index};
 
 pub async fn did_change<C: LspClient>(backend: &Backend<C>, params: DidChangeTextDocumentParams) {
     let uri = params.text_document.uri;
@@ -45,6 +45,7 @@ pub async fn did_change<C: LspClient>(backend: &Backend<C>, params: DidChangeTex
     let tree = parse(&rope, (&document.tree).into());
 
     document.tree = tree.clone();
+    update_index(backend, &uri, &rope, &tree);
 
     // We must not hold a reference to something in the `document_map` while populating the import
     // documents.
@@ -9,7 +9,7 @@ pub async fn did_change_configuration<C: LspClient>(
     set_configuration_options(
         backend,
         Some(params.settings),
-        backend
+        &backend
             .workspace_paths
             .read()
             .map(|uris| uris.to_vec())
@@ -87,4 +87,40 @@ mod test {
             }
         );
     }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn server_did_change_configuration_default_patterns() {
+        // Arrange
+        let mut service = initialize_server(&[], &Options::default()).await;
+
+        // Act
+        service
+            .notify::<DidChangeConfiguration>(DidChangeConfigurationParams {
+                settings: serde_json::from_str(
+                    r#"
+                    {
+                      "language_retrieval_patterns": [
+                        "tree-sitter-([^/]+)/queries/[^/]+\\.scm$",
+                        "\\.ts\\-([^/]+)\\-parser\\.wasm"
+                      ]
+                    }
+                    "#,
+                )
+                .unwrap(),
+            })
+            .await;
+
+        // Assert
+        let options = service.inner().options.read().await;
+        assert_eq!(
+            options.language_retrieval_patterns,
+            vec![
+                Regex::new("tree-sitter-([^/]+)/queries/[^/]+\\.scm$")
+                    .unwrap()
+                    .into(),
+                Regex::new(r"\.ts\-([^/]+)\-parser\.wasm").unwrap().into(),
+                Regex::new("queries/([^/]+)/[^/]+\\.scm$").unwrap().into(),
+            ]
+        );
+    }
 }
@@ -0,0 +1,29 @@
+use tower_lsp::lsp_types::{DidChangeWatchedFilesParams, FileChangeType};
+use tracing::info;
+
+use crate::{Backend, LspClient};
+
+use super::workspace_symbol::{remove_from_index, update_index_from_disk};
+
+pub fn did_change_watched_files<C: LspClient>(
+    backend: &Backend<C>,
+    params: &DidChangeWatchedFilesParams,
+) {
+    for change in &params.changes {
+        let uri = &change.uri;
+        info!("ts_query_ls did_change_watched_files: {uri}");
+        if change.typ == FileChangeType::DELETED {
+            remove_from_index(backend, uri);
+            continue;
+        }
+        // Documents opened by the client are kept up to date by the document sync notifications
+        if backend
+            .document_map
+            .get(uri)
+            .is_some_and(|doc| doc.version.is_some())
+        {
+            continue;
+        }
+        update_index_from_disk(backend, uri);
+    }
+}
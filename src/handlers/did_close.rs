@@ -3,10 +3,15 @@ use tracing::{info, warn};
 
 use crate::{Backend, LspClient};
 
+use super::workspace_symbol::update_index_from_disk;
+
 pub fn did_close<C: LspClient>(backend: &Backend<C>, params: &DidCloseTextDocumentParams) {
     let uri = &params.text_document.uri;
     info!("ts_query_ls did_close: {uri}");
     if backend.document_map.remove(uri).is_none() {
         warn!("Document with URI: {uri} was not being tracked");
     }
+    backend.semantic_tokens_map.remove(uri);
+    // Unsaved changes are discarded when a document is closed
+    update_index_from_disk(backend, uri);
 }
@@ -10,6 +10,8 @@ use tower_lsp::lsp_types::{DidOpenTextDocumentParams, Url};
 use tracing::info;
 use tree_sitter::Language;
 
+use super::workspace_symbol::update_index;
+
 use crate::{
     Backend, DocumentData, ImportedUri, LanguageData, LspClient, Options, SymbolInfo,
     util::{get_imported_uris, get_language, get_language_name, parse, push_diagnostics},
@@ -26,6 +28,8 @@ pub async fn did_open<C: LspClient>(backend: &Backend<C>, params: DidOpenTextDoc
     let workspace_uris = backend.workspace_paths.read().unwrap().clone();
     let imported_uris = get_imported_uris(&workspace_uris, &options, &uri, &rope, &tree);
 
+    update_index(backend, &uri, &rope, &tree);
+
     // Track the document
     let version = Some(params.text_document.version);
     backend.document_map.insert(
@@ -62,7 +66,7 @@ pub async fn did_open<C: LspClient>(backend: &Backend<C>, params: DidOpenTextDoc
     push_diagnostics(backend, uri).await;
 }
 
-fn populate_language_info<C: LspClient>(
+pub fn populate_language_info<C: LspClient>(
     backend: &Backend<C>,
     language_name: Option<String>,
     options: &Options,
@@ -0,0 +1,117 @@
+use tower_lsp::lsp_types::{DocumentLink, DocumentLinkParams, Position, Range};
+use tracing::warn;
+
+use crate::{Backend, LspClient, util::uri_to_basename};
+
+pub fn document_link<C: LspClient>(
+    backend: &Backend<C>,
+    params: &DocumentLinkParams,
+) -> Option<Vec<DocumentLink>> {
+    let uri = &params.text_document.uri;
+    let Some(doc) = backend.document_map.get(uri) else {
+        warn!("No document found for URI: {uri} when handling document_link");
+        return None;
+    };
+    let query_name = uri_to_basename(uri).unwrap_or_default();
+    let workspace_dirs = backend
+        .workspace_paths
+        .read()
+        .map(|paths| {
+            paths
+                .iter()
+                .map(|path| format!("`{}`", path.display()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default();
+
+    Some(
+        doc.imported_uris
+            .iter()
+            .filter(|import| !import.name.is_empty())
+            .map(|import| {
+                let range = Range::new(
+                    Position::new(0, import.start_col),
+                    Position::new(0, import.end_col),
+                );
+                let tooltip = if import.uri.is_some() {
+                    format!("Open {query_name}.scm for module {:?}", import.name)
+                } else {
+                    format!(
+                        "Query module {:?} not found. Searched for {query_name}.scm files whose language resolves to {:?} in: {workspace_dirs}",
+                        import.name, import.name
+                    )
+                };
+                DocumentLink {
+                    range,
+                    target: import.uri.clone(),
+                    tooltip: Some(tooltip),
+                    data: None,
+                }
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use tower_lsp::lsp_types::{
+        DocumentLink, DocumentLinkParams, PartialResultParams, Position, Range,
+        TextDocumentIdentifier, Url, WorkDoneProgressParams, request::DocumentLinkRequest,
+    };
+
+    use crate::{
+        Options,
+        test_helpers::helpers::{FOO_HIGHLIGHTS_WS_URI, TestService, initialize_server},
+    };
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn document_link() {
+        // Arrange
+        let source = "; inherits: cpp,,nonexistent\n\n(identifier) @variable";
+        let mut service = initialize_server(
+            &[(FOO_HIGHLIGHTS_WS_URI.clone(), source)],
+            &Options::default(),
+        )
+        .await;
+
+        // Act
+        let links = service
+            .request::<DocumentLinkRequest>(DocumentLinkParams {
+                text_document: TextDocumentIdentifier {
+                    uri: FOO_HIGHLIGHTS_WS_URI.clone(),
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await;
+
+        // Assert
+        let cpp_uri = Url::from_file_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/test_workspace/queries/cpp/test.scm"
+        ))
+        .unwrap();
+        let workspace_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/test_workspace");
+        assert_eq!(
+            links,
+            Some(vec![
+                DocumentLink {
+                    range: Range::new(Position::new(0, 12), Position::new(0, 15)),
+                    target: Some(cpp_uri),
+                    tooltip: Some(String::from("Open test.scm for module \"cpp\"")),
+                    data: None,
+                },
+                DocumentLink {
+                    range: Range::new(Position::new(0, 17), Position::new(0, 28)),
+                    target: None,
+                    tooltip: Some(format!(
+                        "Query module \"nonexistent\" not found. Searched for test.scm files whose language resolves to \"nonexistent\" in: `{workspace_dir}`"
+                    )),
+                    data: None,
+                },
+            ])
+        );
+    }
+}
@@ -1,53 +1,162 @@
+use ropey::Rope;
 use tower_lsp::lsp_types::{
     DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, SymbolKind,
 };
 use tracing::warn;
-use tree_sitter::{QueryCursor, StreamingIterator};
+use tree_sitter::{Node, Tree};
 
-use crate::{
-    Backend, LspClient,
-    util::{CAPTURES_QUERY, NodeUtil, TextProviderRope},
-};
+use crate::{Backend, LspClient, util::NodeUtil};
 
 pub fn document_symbol<C: LspClient>(
     backend: &Backend<C>,
     params: &DocumentSymbolParams,
 ) -> Option<DocumentSymbolResponse> {
     let uri = &params.text_document.uri;
-    let mut document_symbols = vec![];
 
     let Some(doc) = backend.document_map.get(uri) else {
         warn!("No document found for URI: {uri} when searching for document symbols.");
         return None;
     };
-    let rope = &doc.rope;
-    let tree = &doc.tree;
-
-    let provider = &TextProviderRope(rope);
-    let mut cursor = QueryCursor::new();
-    let mut matches = cursor.matches(&CAPTURES_QUERY, tree.root_node(), provider);
-
-    while let Some(match_) = matches.next() {
-        for capture in match_.captures {
-            let capture_node = capture.node;
-            let node_text = capture_node.text(rope);
-            let parent = capture_node.parent().unwrap();
-            document_symbols.push(DocumentSymbol {
-                name: node_text,
+
+    Some(DocumentSymbolResponse::Nested(get_document_symbols(
+        &doc.rope, &doc.tree,
+    )))
+}
+
+/// Get one symbol per top-level pattern in the document, with nested symbols for its definitions,
+/// captures, and predicates.
+pub fn get_document_symbols(rope: &Rope, tree: &Tree) -> Vec<DocumentSymbol> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.named_children(&mut cursor)
+        .filter_map(|pattern| definition_symbol(pattern, pattern, None, rope))
+        .collect()
+}
+
+/// The number of alternatives named in the symbol of a list, after which the rest are elided.
+const MAX_LIST_NAME_MEMBERS: usize = 3;
+
+/// Create a symbol for a definition node, along with the captures, predicates, and definitions
+/// nested within it. `outer` is the node spanning the whole symbol, which differs from `node` when
+/// the definition is the value of a field.
+fn definition_symbol(
+    node: Node,
+    outer: Node,
+    field: Option<&str>,
+    rope: &Rope,
+) -> Option<DocumentSymbol> {
+    let (name, kind, selection_node) = match node.kind() {
+        "named_node" => {
+            let name = node.child_by_field_name("name")?;
+            let label = node.child_by_field_name("supertype").map_or_else(
+                || name.text(rope),
+                |supertype| format!("{}/{}", supertype.text(rope), name.text(rope)),
+            );
+            (format!("({label})"), SymbolKind::CLASS, name)
+        }
+        "missing_node" => {
+            let name = node.child_by_field_name("name");
+            let label = name.map_or_else(
+                || String::from("MISSING"),
+                |name| format!("MISSING {}", name.text(rope)),
+            );
+            (
+                format!("({label})"),
+                SymbolKind::CLASS,
+                name.unwrap_or(node),
+            )
+        }
+        "anonymous_node" => {
+            let name = node.child_by_field_name("name")?;
+            (name.text(rope), SymbolKind::STRING, name)
+        }
+        "grouping" => {
+            let names = member_names(node, rope).join(" ");
+            (format!("({names})"), SymbolKind::OBJECT, node.child(0)?)
+        }
+        "list" => {
+            let mut names = member_names(node, rope);
+            if names.len() > MAX_LIST_NAME_MEMBERS {
+                names.truncate(MAX_LIST_NAME_MEMBERS);
+                names.push(String::from("…"));
+            }
+            (
+                format!("[{}]", names.join(" ")),
+                SymbolKind::ENUM,
+                node.child(0)?,
+            )
+        }
+        "predicate" => {
+            let mut cursor = node.walk();
+            let name = node
+                .children_by_field_name("name", &mut cursor)
+                .find(|child| child.kind() == "identifier")?;
+            let predicate_type = node.child_by_field_name("type")?;
+            (
+                format!("#{}{}", name.text(rope), predicate_type.text(rope)),
+                SymbolKind::FUNCTION,
+                name,
+            )
+        }
+        "field_definition" => {
+            let name = node.child_by_field_name("name")?;
+            let definition = node.named_child(1)?;
+            return definition_symbol(definition, node, Some(&name.text(rope)), rope);
+        }
+        _ => return None,
+    };
+
+    let quantifier = node
+        .child_by_field_name("quantifier")
+        .map(|quantifier| quantifier.text(rope));
+    let detail = match (field, quantifier) {
+        (Some(field), Some(quantifier)) => Some(format!("{field}: {quantifier}")),
+        (Some(field), None) => Some(format!("{field}:")),
+        (None, quantifier) => quantifier,
+    };
+
+    let mut cursor = node.walk();
+    let children = node
+        .named_children(&mut cursor)
+        .filter_map(|child| match child.kind() {
+            "capture" => Some(DocumentSymbol {
+                name: child.text(rope),
                 kind: SymbolKind::VARIABLE,
-                range: parent.lsp_range(rope),
-                selection_range: capture_node.lsp_range(rope),
+                range: child.lsp_range(rope),
+                selection_range: child.lsp_range(rope),
                 detail: None,
-                // TODO: Structure this hierarchically
                 children: None,
                 tags: None,
                 #[allow(deprecated)]
                 deprecated: None,
-            });
-        }
-    }
+            }),
+            "predicate" => definition_symbol(child, child, None, rope),
+            _ if node.kind() == "predicate" => None,
+            _ => definition_symbol(child, child, None, rope),
+        })
+        .collect::<Vec<_>>();
 
-    Some(DocumentSymbolResponse::Nested(document_symbols))
+    Some(DocumentSymbol {
+        name,
+        kind,
+        range: outer.lsp_range(rope),
+        selection_range: selection_node.lsp_range(rope),
+        detail,
+        children: (!children.is_empty()).then_some(children),
+        tags: None,
+        #[allow(deprecated)]
+        deprecated: None,
+    })
+}
+
+/// Get the symbol names of the definitions within a grouping or list.
+fn member_names(node: Node, rope: &Rope) -> Vec<String> {
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .filter(|child| !matches!(child.kind(), "capture" | "predicate" | "quantifier"))
+        .filter_map(|child| definition_symbol(child, child, None, rope))
+        .map(|symbol| symbol.name)
+        .collect()
 }
 
 #[cfg(test)]
@@ -65,78 +174,174 @@ mod test {
         test_helpers::helpers::{SIMPLE_FILE, TEST_URI, TestService, initialize_server},
     };
 
-    type DocSymbol = (String, Range, Range);
+    fn symbol(
+        name: &str,
+        kind: SymbolKind,
+        range: (u32, u32, u32, u32),
+        selection_range: (u32, u32, u32, u32),
+        detail: Option<&str>,
+        children: Option<Vec<DocumentSymbol>>,
+    ) -> DocumentSymbol {
+        DocumentSymbol {
+            name: String::from(name),
+            kind,
+            range: Range::new(
+                Position::new(range.0, range.1),
+                Position::new(range.2, range.3),
+            ),
+            selection_range: Range::new(
+                Position::new(selection_range.0, selection_range.1),
+                Position::new(selection_range.2, selection_range.3),
+            ),
+            detail: detail.map(ToOwned::to_owned),
+            children,
+            tags: None,
+            #[allow(deprecated)]
+            deprecated: None,
+        }
+    }
 
     #[rstest]
-    #[case(SIMPLE_FILE, vec![(
-        String::from("@constant"),
-        Range {
-            start: Position {
-                line: 0,
-                character: 14
-            },
-            end: Position {
-                line: 0,
-                character: 23
-            }
-        },
-        Range {
-            start: Position {
-                line: 0,
-                character: 1
-            },
-            end: Position {
-                line: 0,
-                character: 23
-            }
-        },
-    ), (
-        String::from("@constant"),
-        Range {
-            start: Position {
-                line: 1,
-                character: 10
-            },
-            end: Position {
-                line: 1,
-                character: 19
-            }
-        },
-        Range {
-            start: Position {
-                line: 1,
-                character: 10
-            },
-            end: Position {
-                line: 1,
-                character: 29
-            }
-        },
-    ), (
-        String::from("@constant"),
-        Range {
-            start: Position {
-                line: 1,
-                character: 20
-            },
-            end: Position {
-                line: 1,
-                character: 29
-            }
-        },
-        Range {
-            start: Position {
-                line: 1,
-                character: 10
-            },
-            end: Position {
-                line: 1,
-                character: 29
-            }
-        },
+    #[case(SIMPLE_FILE, vec![symbol(
+        "((identifier))",
+        SymbolKind::OBJECT,
+        (0, 0, 1, 31),
+        (0, 0, 0, 1),
+        None,
+        Some(vec![
+            symbol(
+                "(identifier)",
+                SymbolKind::CLASS,
+                (0, 1, 0, 23),
+                (0, 2, 0, 12),
+                None,
+                Some(vec![symbol(
+                    "@constant",
+                    SymbolKind::VARIABLE,
+                    (0, 14, 0, 23),
+                    (0, 14, 0, 23),
+                    None,
+                    None,
+                )]),
+            ),
+            symbol("#match?", SymbolKind::FUNCTION, (1, 1, 1, 30), (1, 3, 1, 8), None, None),
+        ]),
+    )])]
+    #[case(r#"(function_definition
+  name: (identifier)? @function
+  body: (block
+    [
+      "return"
+      (MISSING ";")
+    ]* @keyword)) @definition
+
+(expression/identifier) @variable
+"#, vec![
+        symbol(
+            "(function_definition)",
+            SymbolKind::CLASS,
+            (0, 0, 6, 29),
+            (0, 1, 0, 20),
+            None,
+            Some(vec![
+                symbol(
+                    "(identifier)",
+                    SymbolKind::CLASS,
+                    (1, 2, 1, 31),
+                    (1, 9, 1, 19),
+                    Some("name: ?"),
+                    Some(vec![symbol(
+                        "@function",
+                        SymbolKind::VARIABLE,
+                        (1, 22, 1, 31),
+                        (1, 22, 1, 31),
+                        None,
+                        None,
+                    )]),
+                ),
+                symbol(
+                    "(block)",
+                    SymbolKind::CLASS,
+                    (2, 2, 6, 16),
+                    (2, 9, 2, 14),
+                    Some("body:"),
+                    Some(vec![symbol(
+                        "[\"return\" (MISSING \";\")]",
+                        SymbolKind::ENUM,
+                        (3, 4, 6, 15),
+                        (3, 4, 3, 5),
+                        Some("*"),
+                        Some(vec![
+                            symbol(
+                                "\"return\"",
+                                SymbolKind::STRING,
+                                (4, 6, 4, 14),
+                                (4, 6, 4, 14),
+                                None,
+                                None,
+                            ),
+                            symbol(
+                                "(MISSING \";\")",
+                                SymbolKind::CLASS,
+                                (5, 6, 5, 19),
+                                (5, 15, 5, 18),
+                                None,
+                                None,
+                            ),
+                            symbol(
+                                "@keyword",
+                                SymbolKind::VARIABLE,
+                                (6, 7, 6, 15),
+                                (6, 7, 6, 15),
+                                None,
+                                None,
+                            ),
+                        ]),
+                    )]),
+                ),
+                symbol(
+                    "@definition",
+                    SymbolKind::VARIABLE,
+                    (6, 18, 6, 29),
+                    (6, 18, 6, 29),
+                    None,
+                    None,
+                ),
+            ]),
+        ),
+        symbol(
+            "(expression/identifier)",
+            SymbolKind::CLASS,
+            (8, 0, 8, 33),
+            (8, 12, 8, 22),
+            None,
+            Some(vec![symbol(
+                "@variable",
+                SymbolKind::VARIABLE,
+                (8, 24, 8, 33),
+                (8, 24, 8, 33),
+                None,
+                None,
+            )]),
+        ),
+    ])]
+    #[case("[\"if\" \"else\" (ERROR) \"for\" \"while\"] @keyword", vec![symbol(
+        "[\"if\" \"else\" (ERROR) …]",
+        SymbolKind::ENUM,
+        (0, 0, 0, 44),
+        (0, 0, 0, 1),
+        None,
+        Some(vec![
+            symbol("\"if\"", SymbolKind::STRING, (0, 1, 0, 5), (0, 1, 0, 5), None, None),
+            symbol("\"else\"", SymbolKind::STRING, (0, 6, 0, 12), (0, 6, 0, 12), None, None),
+            symbol("(ERROR)", SymbolKind::CLASS, (0, 13, 0, 20), (0, 14, 0, 19), None, None),
+            symbol("\"for\"", SymbolKind::STRING, (0, 21, 0, 26), (0, 21, 0, 26), None, None),
+            symbol("\"while\"", SymbolKind::STRING, (0, 27, 0, 34), (0, 27, 0, 34), None, None),
+            symbol("@keyword", SymbolKind::VARIABLE, (0, 36, 0, 44), (0, 36, 0, 44), None, None),
+        ]),
     )])]
     #[tokio::test(flavor = "current_thread")]
-    async fn document_symbol(#[case] source: &str, #[case] symbols: Vec<DocSymbol>) {
+    async fn document_symbol(#[case] source: &str, #[case] symbols: Vec<DocumentSymbol>) {
         // Arrange
         let mut service =
             initialize_server(&[(TEST_URI.clone(), source)], &Options::default()).await;
@@ -153,22 +358,6 @@ mod test {
             .await;
 
         // Assert
-        let expected = Some(DocumentSymbolResponse::Nested(
-            symbols
-                .iter()
-                .map(|s| DocumentSymbol {
-                    name: s.0.clone(),
-                    selection_range: s.1,
-                    range: s.2,
-                    kind: SymbolKind::VARIABLE,
-                    detail: None,
-                    children: None,
-                    tags: None,
-                    #[allow(deprecated)]
-                    deprecated: None,
-                })
-                .collect(),
-        ));
-        assert_eq!(expected, tokens);
+        assert_eq!(Some(DocumentSymbolResponse::Nested(symbols)), tokens);
     }
 }
@@ -0,0 +1,342 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use dashmap::DashMap;
+use ropey::Rope;
+use serde_json::Value;
+use tower_lsp::{
+    jsonrpc::{Error, Result},
+    lsp_types::{
+        ApplyWorkspaceEditParams, ExecuteCommandParams, NumberOrString, TextEdit, Url,
+        WorkspaceEdit, request::ApplyWorkspaceEdit,
+    },
+};
+use tracing::warn;
+
+use crate::{
+    Backend, DocumentData, LspClient,
+    util::{
+        FileProgress, get_imported_uris, get_language_name, get_scm_files, get_work_done_token,
+        parse,
+    },
+};
+
+use super::{
+    code_action::get_fix_all_edits,
+    diagnostic::get_diagnostics,
+    did_open::{populate_import_documents, populate_language_info},
+    formatting::{diffs, format_document},
+};
+
+/// Apply the preferred fix of every fixable diagnostic in the workspace's query files.
+pub const FIX_ALL_WORKSPACE_COMMAND: &str = "ts_query_ls.fixAllWorkspace";
+/// Format every query file in the workspace.
+pub const FORMAT_WORKSPACE_COMMAND: &str = "ts_query_ls.formatWorkspace";
+
+#[derive(Clone, Copy)]
+enum WorkspaceCommand {
+    FixAll,
+    Format,
+}
+
+pub async fn execute_command<C: LspClient>(
+    backend: &Backend<C>,
+    params: ExecuteCommandParams,
+) -> Result<Option<Value>> {
+    let command = match params.command.as_str() {
+        FIX_ALL_WORKSPACE_COMMAND => WorkspaceCommand::FixAll,
+        FORMAT_WORKSPACE_COMMAND => WorkspaceCommand::Format,
+        command => {
+            return Err(Error::invalid_params(format!(
+                "Unknown command '{command}'"
+            )));
+        }
+    };
+    let token =
+        get_work_done_token(backend, params.work_done_progress_params.work_done_token).await;
+    let changes = get_workspace_changes(backend, command, token).await;
+    if changes.is_empty() {
+        return Ok(None);
+    }
+
+    let label = match command {
+        WorkspaceCommand::FixAll => "Fix all auto-fixable problems in the workspace",
+        WorkspaceCommand::Format => "Format the workspace",
+    };
+    match backend
+        .client
+        .send_request::<ApplyWorkspaceEdit>(ApplyWorkspaceEditParams {
+            label: Some(String::from(label)),
+            edit: WorkspaceEdit {
+                changes: Some(changes),
+                ..Default::default()
+            },
+        })
+        .await
+    {
+        Ok(response) if !response.applied => warn!(
+            "Workspace edit was not applied: {}",
+            response.failure_reason.unwrap_or_default()
+        ),
+        Err(error) => warn!("Failed to apply workspace edit: {error}"),
+        Ok(_) => {}
+    }
+    Ok(None)
+}
+
+/// Get the edits made by the given command to each query file of the workspace, reporting progress
+/// as files are processed.
+async fn get_workspace_changes<C: LspClient>(
+    backend: &Backend<C>,
+    command: WorkspaceCommand,
+    token: Option<NumberOrString>,
+) -> HashMap<Url, Vec<TextEdit>> {
+    let dirs = backend
+        .workspace_paths
+        .read()
+        .as_deref()
+        .cloned()
+        .unwrap_or_default();
+
+    let files = get_scm_files(&dirs).collect::<Vec<_>>();
+    let (title, verb) = match command {
+        WorkspaceCommand::FixAll => ("Fixing workspace queries", "fixed"),
+        WorkspaceCommand::Format => ("Formatting workspace queries", "formatted"),
+    };
+    let mut progress = FileProgress::begin(backend, token, title, verb, files.len()).await;
+
+    let mut changes = HashMap::new();
+    for path in files {
+        if let Some((uri, edits)) = get_file_edits(backend, command, &path).await {
+            changes.insert(uri, edits);
+        }
+        progress.advance().await;
+    }
+    progress.end().await;
+
+    changes
+}
+
+/// Get the edits made by the given command to a query file, if there are any.
+async fn get_file_edits<C: LspClient>(
+    backend: &Backend<C>,
+    command: WorkspaceCommand,
+    path: &Path,
+) -> Option<(Url, Vec<TextEdit>)> {
+    let uri = Url::from_file_path(path).ok()?;
+    // Open documents may have unsaved changes, so prefer them to the file on disk
+    let open_document = backend
+        .document_map
+        .get(&uri)
+        .filter(|doc| doc.version.is_some())
+        .map(|doc| (doc.rope.clone(), doc.tree.clone()));
+    let (rope, tree) = if let Some(document) = open_document {
+        document
+    } else {
+        let rope = Rope::from(fs::read_to_string(path).ok()?);
+        let tree = parse(&rope, None);
+        (rope, tree)
+    };
+
+    let edits = match command {
+        WorkspaceCommand::FixAll => {
+            let options = backend.options.read().await.clone();
+            let workspace_dirs = backend
+                .workspace_paths
+                .read()
+                .as_deref()
+                .cloned()
+                .unwrap_or_default();
+            let language_name = get_language_name(&uri, &options);
+            // The language may not be loaded yet if none of its documents have been opened
+            populate_language_info(backend, language_name.clone(), &options);
+            let language_data = language_name
+                .as_ref()
+                .and_then(|name| backend.language_map.get(name).map(|data| data.clone()));
+            let imported_uris = get_imported_uris(&workspace_dirs, &options, &uri, &rope, &tree);
+            // As with `lint`, imported modules are read from disk into a separate document map
+            let document_map = DashMap::new();
+            populate_import_documents(&document_map, &workspace_dirs, &options, &imported_uris);
+            let document = DocumentData {
+                tree,
+                rope,
+                language_name,
+                version: Option::default(),
+                imported_uris,
+            };
+            let ignore_missing_language = true;
+            let cache = false;
+            let diagnostics = get_diagnostics(
+                &uri,
+                &document_map,
+                document.clone(),
+                language_data,
+                backend.options.clone(),
+                &backend.parser_names_map,
+                ignore_missing_language,
+                cache,
+            )
+            .await;
+            get_fix_all_edits(&document.tree, &document.rope, diagnostics, &uri)?
+        }
+        WorkspaceCommand::Format => {
+            let options = backend.options.read().await.format_options.clone();
+            let formatted = format_document(&rope, &tree.root_node(), &options)?;
+            diffs(&rope.to_string(), &formatted, rope.clone()).collect()
+        }
+    };
+    if edits.is_empty() {
+        None
+    } else {
+        Some((uri, edits))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use pretty_assertions::assert_eq;
+    use tower_lsp::lsp_types::{
+        ApplyWorkspaceEditParams, DidChangeConfigurationParams, ExecuteCommandParams,
+        NumberOrString, Position, ProgressParams, ProgressParamsValue, Range, TextEdit, Url,
+        WorkDoneProgress, WorkDoneProgressEnd, WorkDoneProgressParams, WorkspaceEdit,
+        notification::{DidChangeConfiguration, Progress},
+        request::{ApplyWorkspaceEdit, ExecuteCommand},
+    };
+
+    use crate::{
+        Options,
+        test_helpers::helpers::{
+            CPP_HIGHLIGHTS_WS_URI, MockRequest, TestService, initialize_server,
+        },
+    };
+
+    use super::{FIX_ALL_WORKSPACE_COMMAND, FORMAT_WORKSPACE_COMMAND};
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn format_workspace() {
+        // Arrange
+        let other_uri = Url::from_file_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/test_workspace/queries/other/test.scm"
+        ))
+        .unwrap();
+        let mut service = initialize_server(
+            &[
+                (
+                    CPP_HIGHLIGHTS_WS_URI.clone(),
+                    "; test query\n(squid     )\n",
+                ),
+                (
+                    other_uri,
+                    "; should match \"query\" language nodes\n(definition)\n",
+                ),
+            ],
+            &Options::default(),
+        )
+        .await;
+
+        // Act
+        let result = service
+            .request::<ExecuteCommand>(ExecuteCommandParams {
+                command: String::from(FORMAT_WORKSPACE_COMMAND),
+                arguments: Vec::new(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await;
+
+        // Assert
+        assert!(result.is_none());
+        // Open documents are formatted instead of their contents on disk, and files which are
+        // already formatted are left out
+        let requests = service.inner().client.get_requests();
+        assert_eq!(
+            requests.last(),
+            Some(&MockRequest::from_request::<ApplyWorkspaceEdit>(
+                ApplyWorkspaceEditParams {
+                    label: Some(String::from("Format the workspace")),
+                    edit: WorkspaceEdit {
+                        changes: Some(HashMap::from([(
+                            CPP_HIGHLIGHTS_WS_URI.clone(),
+                            vec![TextEdit {
+                                range: Range::new(Position::new(1, 6), Position::new(1, 11)),
+                                new_text: String::new(),
+                            }],
+                        )])),
+                        ..Default::default()
+                    },
+                }
+            ))
+        );
+        assert!(service.inner().client.get_notifications().contains(
+            &MockRequest::from_notification::<Progress>(ProgressParams {
+                token: NumberOrString::String(String::from("00000000-1111-2222-3333-444444444444")),
+                value: ProgressParamsValue::WorkDone(WorkDoneProgress::End(WorkDoneProgressEnd {
+                    message: Some(String::from("4/4 files formatted"))
+                }))
+            })
+        ));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn fix_all_workspace_loads_languages() {
+        // Arrange
+        let mut service = initialize_server(
+            &[(CPP_HIGHLIGHTS_WS_URI.clone(), "(nmed_node) @node\n")],
+            &Options::default(),
+        )
+        .await;
+        // The document's language is only configured after it was opened, so it is not loaded yet
+        service
+            .notify::<DidChangeConfiguration>(DidChangeConfigurationParams {
+                settings: serde_json::json!({ "parser_aliases": { "cpp": "query" } }),
+            })
+            .await;
+
+        // Act
+        service
+            .request::<ExecuteCommand>(ExecuteCommandParams {
+                command: String::from(FIX_ALL_WORKSPACE_COMMAND),
+                arguments: Vec::new(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await;
+
+        // Assert
+        let other_uri = Url::from_file_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/test_workspace/queries/other/test.scm"
+        ))
+        .unwrap();
+        let requests = service.inner().client.get_requests();
+        assert_eq!(
+            requests.last(),
+            Some(&MockRequest::from_request::<ApplyWorkspaceEdit>(
+                ApplyWorkspaceEditParams {
+                    label: Some(String::from(
+                        "Fix all auto-fixable problems in the workspace"
+                    )),
+                    edit: WorkspaceEdit {
+                        changes: Some(HashMap::from([
+                            (
+                                CPP_HIGHLIGHTS_WS_URI.clone(),
+                                vec![TextEdit {
+                                    range: Range::new(Position::new(0, 2), Position::new(0, 2)),
+                                    new_text: String::from("a"),
+                                }],
+                            ),
+                            (
+                                other_uri,
+                                vec![TextEdit {
+                                    range: Range::new(Position::new(2, 0), Position::new(2, 12)),
+                                    new_text: String::new(),
+                                }],
+                            ),
+                        ])),
+                        ..Default::default()
+                    },
+                }
+            ))
+        );
+    }
+}
@@ -1,25 +1,32 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
 
 use regex::Regex;
 use ropey::Rope;
 use tower_lsp::lsp_types::{
-    DocumentFormattingParams, DocumentRangeFormattingParams, Range, TextEdit,
+    DocumentFormattingParams, DocumentOnTypeFormattingParams, DocumentRangeFormattingParams,
+    FormattingOptions, Range, TextEdit,
 };
 use tracing::warn;
 use tree_sitter::{
     Node, Query, QueryCursor, QueryMatch, QueryPredicateArg, StreamingIterator as _, TreeCursor,
 };
 
+use ts_query_ls::{BlankLinePolicy, FormatOptions};
+
 use crate::QUERY_LANGUAGE;
-use crate::util::{ByteUtil, NodeUtil as _, TextProviderRope};
+use crate::util::{
+    ByteUtil, FORMAT_IGNORE_REGEX, NodeUtil as _, PosUtil as _, TextProviderRope,
+    get_format_ignore_regions,
+};
 use crate::{Backend, LspClient};
 
-pub fn formatting<C: LspClient>(
+pub async fn formatting<C: LspClient>(
     backend: &Backend<C>,
     params: &DocumentFormattingParams,
 ) -> Option<Vec<TextEdit>> {
     let uri = &params.text_document.uri;
+    let options = get_format_options(backend, &params.options).await;
     let Some(doc) = backend.document_map.get(uri) else {
         warn!("No document found for URI: {uri} when handling formatting");
         return None;
@@ -27,16 +34,17 @@ pub fn formatting<C: LspClient>(
     let rope = &doc.rope;
     let root = &doc.tree.root_node();
 
-    format_document(rope, root).map(|formatted_doc| {
+    format_document(rope, root, &options).map(|formatted_doc| {
         diffs(rope.to_string().as_str(), &formatted_doc, rope.clone()).collect()
     })
 }
 
-pub fn range_formatting<C: LspClient>(
+pub async fn range_formatting<C: LspClient>(
     backend: &Backend<C>,
     params: &DocumentRangeFormattingParams,
 ) -> Option<Vec<TextEdit>> {
     let uri = &params.text_document.uri;
+    let options = get_format_options(backend, &params.options).await;
     let Some(doc) = backend.document_map.get(uri) else {
         warn!("No document found for URI: {uri} when handling formatting");
         return None;
@@ -45,18 +53,178 @@ pub fn range_formatting<C: LspClient>(
     let root = &doc.tree.root_node();
     let range = params.range;
 
-    format_document(rope, root).map(|formatted_doc| {
+    if root.has_error() {
+        let formatted_doc = format_definitions(rope, root, range, &options);
+        return Some(diffs(rope.to_string().as_str(), &formatted_doc, rope.clone()).collect());
+    }
+    format_document(rope, root, &options).map(|formatted_doc| {
         diffs(rope.to_string().as_str(), &formatted_doc, rope.clone())
             .filter(|d| d.range.end >= range.start && d.range.start <= range.end)
             .collect()
     })
 }
 
+/// Format each valid top-level definition intersecting the given range on its own, leaving the
+/// rest of the document untouched. This allows formatting parts of a document which contains
+/// syntax errors elsewhere.
+fn format_definitions(rope: &Rope, root: &Node, range: Range, options: &FormatOptions) -> String {
+    let ignore_regions: Vec<_> = get_format_ignore_regions(rope, *root)
+        .into_iter()
+        .map(|(start, end)| start.start_byte()..get_ignore_region_end(start, end))
+        .collect();
+    let mut formatted_doc = String::new();
+    let mut offset = 0;
+    for definition in root.children(&mut root.walk()) {
+        let definition_range = definition.lsp_range(rope);
+        if !DEFINITION_KINDS.contains(&definition.kind())
+            || definition.has_error()
+            || definition_range.end < range.start
+            || definition_range.start > range.end
+            || definition.prev_sibling().is_some_and(|prev| {
+                prev.kind() == "comment" && FORMAT_IGNORE_REGEX.is_match(&prev.text(rope))
+            })
+            || ignore_regions
+                .iter()
+                .any(|region| region.contains(&definition.start_byte()))
+        {
+            continue;
+        }
+        let Some(formatted) = format_document(rope, &definition, options) else {
+            continue;
+        };
+        formatted_doc.push_str(&rope.byte_slice(offset..definition.start_byte()).to_string());
+        formatted_doc.push_str(formatted.trim_end_matches('\n'));
+        offset = definition.end_byte();
+    }
+    formatted_doc.push_str(&rope.byte_slice(offset..).to_string());
+    formatted_doc
+}
+
+pub async fn on_type_formatting<C: LspClient>(
+    backend: &Backend<C>,
+    params: &DocumentOnTypeFormattingParams,
+) -> Option<Vec<TextEdit>> {
+    let uri = &params.text_document_position.text_document.uri;
+    let position = params.text_document_position.position;
+    let options = get_format_options(backend, &params.options).await;
+    let Some(doc) = backend.document_map.get(uri) else {
+        warn!("No document found for URI: {uri} when handling on type formatting");
+        return None;
+    };
+    let rope = &doc.rope;
+    let root = &doc.tree.root_node();
+
+    if params.ch == "\n" {
+        return get_indent_edit(rope, root, position.line as usize, &options)
+            .map(|edit| vec![edit]);
+    }
+    // Brackets typed within strings and comments do not close anything
+    let end = position.to_byte_offset(rope);
+    let start = end.checked_sub(params.ch.len())?;
+    let bracket = root.descendant_for_byte_range(start, end)?;
+    if bracket.kind() != params.ch {
+        return None;
+    }
+    let formatted_doc = format_definitions(rope, root, bracket.lsp_range(rope), &options);
+    Some(diffs(rope.to_string().as_str(), &formatted_doc, rope.clone()).collect())
+}
+
+/// Get the end byte of an ignored region, which is the end of the enclosing node if the region is
+/// unterminated.
+fn get_ignore_region_end(start: Node, end: Option<Node>) -> usize {
+    end.or_else(|| start.parent())
+        .map_or_else(|| start.end_byte(), |node| node.end_byte())
+}
+
+/// Get an edit which indents the given line as the formatter would, according to the brackets
+/// which are open at its start. This does not rely on the syntax tree being valid, because the
+/// pattern being typed is usually incomplete.
+fn get_indent_edit(
+    rope: &Rope,
+    root: &Node,
+    line: usize,
+    options: &FormatOptions,
+) -> Option<TextEdit> {
+    let text = rope.get_line(line)?.to_string();
+    let content = text.trim_start_matches([' ', '\t']);
+    let indent_len = text.len() - content.len();
+    // Closing brackets are dedented to the level of their opening bracket
+    let closing_brackets = content
+        .chars()
+        .take_while(|c| matches!(c, ')' | ']'))
+        .count();
+    let depth = get_bracket_depth(root, rope.line_to_byte(line)).saturating_sub(closing_brackets);
+    let indent = " ".repeat(depth * options.indent_width.unwrap_or(DEFAULT_INDENT_WIDTH) as usize);
+    if text[..indent_len] == indent {
+        return None;
+    }
+    let line_start = rope.line_to_byte(line);
+    Some(TextEdit {
+        range: Range::new(
+            line_start.to_lsp_pos(rope),
+            (line_start + indent_len).to_lsp_pos(rope),
+        ),
+        new_text: indent,
+    })
+}
+
+/// Get the number of brackets which are open at the given byte.
+fn get_bracket_depth(root: &Node, byte: usize) -> usize {
+    let mut depth: usize = 0;
+    let mut cursor = root.walk();
+    'traversal: loop {
+        let node = cursor.node();
+        if node.start_byte() >= byte {
+            break;
+        }
+        if node.child_count() > 0 {
+            cursor.goto_first_child();
+            continue;
+        }
+        if !node.is_missing() {
+            match node.kind() {
+                "(" | "[" => depth += 1,
+                ")" | "]" => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'traversal;
+            }
+        }
+    }
+    depth
+}
+
+/// Get the configured format options, using the tab size requested by the client when no
+/// indentation width is configured.
+async fn get_format_options<C: LspClient>(
+    backend: &Backend<C>,
+    client_options: &FormattingOptions,
+) -> FormatOptions {
+    let mut options = backend.options.read().await.format_options.clone();
+    if options.indent_width.is_none() && client_options.tab_size > 0 {
+        options.indent_width = Some(client_options.tab_size);
+    }
+    options
+}
+
 static LINE_START: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([^\S\r\n]*)").unwrap());
 static NEWLINES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n+").unwrap());
 static COMMENT_PAT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^;+(\s*.*?)\s*$").unwrap());
 static CRLF: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\r\n?").unwrap());
 
+/// The kinds of nodes which can appear at the top level of a query.
+const DEFINITION_KINDS: &[&str] = &[
+    "list",
+    "grouping",
+    "named_node",
+    "anonymous_node",
+    "missing_node",
+    "field_definition",
+];
+
 static FORMAT_QUERY: LazyLock<Query> = LazyLock::new(|| {
     Query::new(
         &QUERY_LANGUAGE,
@@ -119,8 +287,7 @@ pub fn diffs<'a>(left: &'a str, right: &'a str, rope: Rope) -> impl Iterator<Ite
     })
 }
 
-const INDENT_STR: &str = "  ";
-const TEXT_WIDTH: usize = 100;
+const DEFAULT_INDENT_WIDTH: u32 = 2;
 
 fn append_lines(lines: &mut Vec<String>, lines_to_append: &[String]) {
     for (i, line) in lines_to_append.iter().enumerate() {
@@ -147,9 +314,11 @@ struct FormatMap {
     comment_fix: HashSet<usize>,
     make_pound: HashSet<usize>,
     remove: HashSet<usize>,
+    /// The end byte of each region started by a `format-ignore-start` comment
+    ignore_regions: HashMap<usize, usize>,
 }
 
-pub fn format_document(rope: &Rope, root: &Node) -> Option<String> {
+pub fn format_document(rope: &Rope, root: &Node, options: &FormatOptions) -> Option<String> {
     if root.has_error() {
         return None;
     }
@@ -191,9 +360,14 @@ pub fn format_document(rope: &Rope, root: &Node) -> Option<String> {
         }
     }
 
+    for (start, end) in get_format_ignore_regions(rope, *root) {
+        map.ignore_regions
+            .insert(start.id(), get_ignore_region_end(start, end));
+    }
+
     let mut lines = vec![String::new()];
 
-    format_iter(rope, root, &mut lines, &map, 0, &mut root.walk());
+    format_iter(rope, root, &mut lines, &map, options, 0, &mut root.walk());
 
     Some(lines.join("\n") + "\n")
 }
@@ -203,6 +377,7 @@ fn format_iter<'a>(
     node: &Node<'a>,
     lines: &mut Vec<String>,
     map: &FormatMap,
+    options: &FormatOptions,
     mut level: usize,
     cursor: &mut TreeCursor<'a>,
 ) {
@@ -210,14 +385,25 @@ fn format_iter<'a>(
         return;
     }
 
+    let indent = " ".repeat(options.indent_width.unwrap_or(DEFAULT_INDENT_WIDTH) as usize);
+    let text_width = options.max_line_width;
+    let split_arguments = node.kind() == "parameters"
+        && options.max_inline_predicate_arguments.is_some_and(|max| {
+            node.named_children(&mut node.walk())
+                .filter(|arg| arg.kind() != "comment")
+                .count()
+                > max
+        });
+
     // Sometimes 2 queries apply append twice. This is to prevent the case from happening
     let mut apply_newline = false;
+    let mut ignore_region_end = None;
     loop {
         let child = cursor.node();
         let id = &child.id();
         if apply_newline {
             apply_newline = false;
-            lines.push(INDENT_STR.repeat(level));
+            lines.push(indent.repeat(level));
         }
         if map.ignore.contains(id) {
             let text = CRLF
@@ -230,23 +416,41 @@ fn format_iter<'a>(
         } else if !map.remove.contains(id) {
             if !map.cancel_prepend.contains(id) {
                 if map.prepend_newline.contains(id) {
-                    lines.push(INDENT_STR.repeat(level));
+                    if options.blank_lines_between_patterns == BlankLinePolicy::Always
+                        || node.kind() != "program"
+                        || follows_blank_line(&child)
+                    {
+                        lines.push(indent.repeat(level));
+                    }
                 } else if map.prepend_space.contains(id) {
                     let byte_length = child.end_byte() - child.start_byte();
                     let broader_byte_length = node.end_byte() - child.start_byte();
                     if !map.conditional_newline.contains(id) {
                         lines.last_mut().unwrap().push(' ');
-                    } else if byte_length + 1 + lines.last().unwrap().len() > TEXT_WIDTH
+                    } else if (split_arguments && is_split_argument(&child))
+                        || byte_length + 1 + lines.last().unwrap().len() > text_width
                         || (map.lookahead_newline.contains(id)
-                            && broader_byte_length + lines.last().unwrap().len() > TEXT_WIDTH)
+                            && broader_byte_length + lines.last().unwrap().len() > text_width)
                     {
-                        lines.push(INDENT_STR.repeat(level));
+                        lines.push(indent.repeat(level));
                     } else {
                         lines.last_mut().unwrap().push(' ');
                     }
                 }
             }
-            if map.comment_fix.contains(id) {
+            if let Some(&region_end) = map.ignore_regions.get(id) {
+                let text = CRLF
+                    .replace_all(
+                        &rope.byte_slice(child.start_byte()..region_end).to_string(),
+                        "\n",
+                    )
+                    .trim_end()
+                    .split('\n')
+                    .map(ToOwned::to_owned)
+                    .collect::<Vec<String>>();
+                append_lines(lines, &text);
+                ignore_region_end = Some(region_end);
+            } else if map.comment_fix.contains(id) {
                 if let Some(mat) = COMMENT_PAT.captures(&child.text(rope)) {
                     lines
                         .last_mut()
@@ -267,13 +471,13 @@ fn format_iter<'a>(
                     .collect::<Vec<String>>();
                 append_lines(lines, &text);
             } else {
-                format_iter(rope, &child, lines, map, level, cursor);
+                format_iter(rope, &child, lines, map, options, level, cursor);
             }
             if map.indent_begin.contains(id) {
                 level += 1;
                 apply_newline = true;
             } else if map.indent_dedent.contains(id) {
-                lines.last_mut().unwrap().drain(0..2);
+                lines.last_mut().unwrap().drain(0..indent.len());
             }
         }
         if map.cancel_append.contains(id) {
@@ -284,6 +488,10 @@ fn format_iter<'a>(
             lines.last_mut().unwrap().push(' ');
         }
 
+        // Skip the nodes of an ignored region, which were written along with its first comment
+        if let Some(region_end) = ignore_region_end.take() {
+            while cursor.node().end_byte() < region_end && cursor.goto_next_sibling() {}
+        }
         if !cursor.goto_next_sibling() {
             break;
         }
@@ -292,6 +500,18 @@ fn format_iter<'a>(
     cursor.goto_parent();
 }
 
+/// Whether the given predicate argument goes on its own line when a predicate's arguments are split.
+/// As when wrapping, a leading capture stays on the line of the predicate name.
+fn is_split_argument(node: &Node) -> bool {
+    node.kind() != "comment" && (node.kind() != "capture" || node.prev_named_sibling().is_some())
+}
+
+/// Whether the given node is separated from its previous sibling by a blank line.
+fn follows_blank_line(node: &Node) -> bool {
+    node.prev_sibling()
+        .is_none_or(|prev| node.start_position().row > prev.end_position().row + 1)
+}
+
 fn handle_predicate(
     match_: &QueryMatch,
     directive: &str,
@@ -344,13 +564,16 @@ mod test {
     use pretty_assertions::assert_eq;
     use rstest::rstest;
     use tower_lsp::lsp_types::{
-        DidChangeTextDocumentParams, DocumentFormattingParams, DocumentRangeFormattingParams,
-        FormattingOptions, Position, Range, TextDocumentContentChangeEvent, TextDocumentIdentifier,
+        DidChangeTextDocumentParams, DocumentFormattingParams, DocumentOnTypeFormattingParams,
+        DocumentRangeFormattingParams, FormattingOptions, Position, Range,
+        TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentPositionParams,
         VersionedTextDocumentIdentifier, WorkDoneProgressParams,
         notification::DidChangeTextDocument,
-        request::{Formatting, RangeFormatting},
+        request::{Formatting, OnTypeFormatting, RangeFormatting},
     };
 
+    use ts_query_ls::{BlankLinePolicy, FormatOptions};
+
     use crate::{
         Options,
         test_helpers::helpers::{TEST_URI, TestService, initialize_server},
@@ -377,6 +600,10 @@ mod test {
         include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/formatting_test_files/before_complex.scm")),
         include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/formatting_test_files/after_complex.scm")),
     )]
+    #[case(
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/formatting_test_files/before_ignore_region.scm")),
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/formatting_test_files/after_ignore_region.scm")),
+    )]
     #[tokio::test(flavor = "current_thread")]
     async fn server_formatting(#[case] before: &str, #[case] after: &str) {
         // Arrange
@@ -421,12 +648,156 @@ mod test {
         assert_eq!(doc.rope.to_string(), String::from(after));
     }
 
+    #[rstest]
+    #[case(
+        "(a (b) @c)\n",
+        "(a\n    (b) @c)\n",
+        FormatOptions {
+            indent_width: Some(4),
+            ..Default::default()
+        },
+        0
+    )]
+    #[case("(a (b) @c)\n", "(a\n    (b) @c)\n", FormatOptions::default(), 4)]
+    #[case(
+        "(a (b) @c)\n",
+        "(a\n  (b) @c)\n",
+        FormatOptions {
+            indent_width: Some(2),
+            ..Default::default()
+        },
+        8
+    )]
+    #[case(
+        "(a) @x\n(b) @y\n\n\n\n(c) @z\n",
+        "(a) @x\n\n(b) @y\n\n(c) @z\n",
+        FormatOptions::default(),
+        0
+    )]
+    #[case(
+        "(a) @x\n(b) @y\n\n\n\n(c) @z\n",
+        "(a) @x\n(b) @y\n\n(c) @z\n",
+        FormatOptions {
+            blank_lines_between_patterns: BlankLinePolicy::Preserve,
+            ..Default::default()
+        },
+        0
+    )]
+    #[case(
+        r#"((identifier) @x (#eq? @x "some long string"))
+"#,
+        r#"((identifier) @x
+  (#eq? @x
+    "some long string"))
+"#,
+        FormatOptions {
+            max_line_width: 20,
+            ..Default::default()
+        },
+        0
+    )]
+    #[case(
+        r#"((identifier) @x (#eq? @x "a") (#any-of? @x "a" "b"))
+"#,
+        r#"((identifier) @x
+  (#eq? @x "a")
+  (#any-of? @x
+    "a"
+    "b"))
+"#,
+        FormatOptions {
+            max_inline_predicate_arguments: Some(2),
+            ..Default::default()
+        },
+        0
+    )]
+    #[tokio::test(flavor = "current_thread")]
+    async fn server_formatting_style(
+        #[case] before: &str,
+        #[case] after: &str,
+        #[case] format_options: FormatOptions,
+        #[case] tab_size: u32,
+    ) {
+        // Arrange
+        let mut service = initialize_server(
+            &[(TEST_URI.clone(), before)],
+            &Options {
+                format_options,
+                ..Default::default()
+            },
+        )
+        .await;
+
+        // Act
+        let mut edits = service
+            .request::<Formatting>(DocumentFormattingParams {
+                text_document: TextDocumentIdentifier {
+                    uri: TEST_URI.clone(),
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                options: FormattingOptions {
+                    tab_size,
+                    insert_spaces: true,
+                    ..Default::default()
+                },
+            })
+            .await
+            .unwrap_or_default();
+        edits.sort_by(|a, b| {
+            let range_a = a.range;
+            let range_b = b.range;
+            range_b.start.cmp(&range_a.start)
+        });
+        service
+            .notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: TEST_URI.clone(),
+                    version: 1,
+                },
+                content_changes: edits
+                    .iter()
+                    .map(|e| TextDocumentContentChangeEvent {
+                        range: Some(e.range),
+                        text: e.new_text.clone(),
+                        range_length: None,
+                    })
+                    .collect(),
+            })
+            .await;
+
+        // Assert
+        let doc = service.inner().document_map.get(&TEST_URI).unwrap();
+        assert_eq!(doc.rope.to_string(), String::from(after));
+    }
+
     #[rstest]
     #[case(
         include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/formatting_test_files/before_syntax_error.scm")),
-        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/formatting_test_files/after_syntax_error.scm")),
+        r#" (MISSING "somenode"))    @missing
+ (cap) @node
+"#,
         Range::new(Position::new(0, 0), Position::new(1, 0)),
     )]
+    #[case(
+        "(a   (b) @c)\n\n((identifier @x\n\n(d  (e))   @f\n",
+        "(a\n  (b) @c)\n\n((identifier @x\n\n(d  (e))   @f\n",
+        Range::new(Position::new(0, 2), Position::new(0, 3))
+    )]
+    #[case(
+        "(a   (b) @c)\n\n((identifier @x\n\n(d  (e))   @f\n",
+        "(a   (b) @c)\n\n((identifier @x\n\n(d  (e))   @f\n",
+        Range::new(Position::new(2, 0), Position::new(2, 3))
+    )]
+    #[case(
+        "(a   (b) @c)\n\n((identifier @x\n\n; format-ignore\n(d  (e))   @f\n",
+        "(a\n  (b) @c)\n\n((identifier @x\n\n; format-ignore\n(d  (e))   @f\n",
+        Range::new(Position::new(0, 0), Position::new(5, 0))
+    )]
+    #[case(
+        "; format-ignore-start\n(a   (b) @c)\n(d  (e))   @f\n; format-ignore-end\n((identifier @x\n\n(g  (h))   @i\n",
+        "; format-ignore-start\n(a   (b) @c)\n(d  (e))   @f\n; format-ignore-end\n((identifier @x\n\n(g\n  (h)) @i\n",
+        Range::new(Position::new(0, 0), Position::new(7, 0))
+    )]
     #[case(
         r#"   (
         (   identifier  )
@@ -580,4 +951,95 @@ mod test {
         let doc = service.inner().document_map.get(&TEST_URI).unwrap();
         assert_eq!(doc.rope.to_string(), String::from(after));
     }
+
+    #[rstest]
+    #[case("(a   (b) @c)\n", Position::new(0, 12), ")", 0, "(a\n  (b) @c)\n")]
+    #[case("[(a)   (b)]\n", Position::new(0, 11), "]", 0, "[\n  (a)\n  (b)\n]\n")]
+    #[case(
+        "(x  (y))\n\n(foo\n",
+        Position::new(0, 8),
+        ")",
+        0,
+        "(x\n  (y))\n\n(foo\n"
+    )]
+    #[case(
+        r#"((a)   @x (#eq? @x ")"))"#,
+        Position::new(0, 21),
+        ")",
+        0,
+        r#"((a)   @x (#eq? @x ")"))"#
+    )]
+    #[case("(a\n  (b\n", Position::new(2, 0), "\n", 0, "(a\n  (b\n    ")]
+    #[case("(a\n(b", Position::new(1, 0), "\n", 4, "(a\n    (b")]
+    #[case(
+        "[\n  (a)\n      (b)\n] @x\n",
+        Position::new(2, 6),
+        "\n",
+        0,
+        "[\n  (a)\n  (b)\n] @x\n"
+    )]
+    #[case("[\n  (a)\n  ] @x\n", Position::new(2, 2), "\n", 0, "[\n  (a)\n] @x\n")]
+    #[case(
+        "((a) @x\n  (#eq? @x\n\"b\"))\n",
+        Position::new(2, 0),
+        "\n",
+        0,
+        "((a) @x\n  (#eq? @x\n    \"b\"))\n"
+    )]
+    #[tokio::test(flavor = "current_thread")]
+    async fn server_on_type_formatting(
+        #[case] before: &str,
+        #[case] position: Position,
+        #[case] ch: &str,
+        #[case] tab_size: u32,
+        #[case] after: &str,
+    ) {
+        // Arrange
+        let mut service =
+            initialize_server(&[(TEST_URI.clone(), before)], &Options::default()).await;
+
+        // Act
+        let mut edits = service
+            .request::<OnTypeFormatting>(DocumentOnTypeFormattingParams {
+                text_document_position: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: TEST_URI.clone(),
+                    },
+                    position,
+                },
+                ch: String::from(ch),
+                options: FormattingOptions {
+                    tab_size,
+                    insert_spaces: true,
+                    ..Default::default()
+                },
+            })
+            .await
+            .unwrap_or_default();
+        edits.sort_by(|a, b| {
+            let range_a = a.range;
+            let range_b = b.range;
+            range_b.start.cmp(&range_a.start)
+        });
+        service
+            .notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: TEST_URI.clone(),
+                    version: 1,
+                },
+                content_changes: edits
+                    .iter()
+                    .map(|e| TextDocumentContentChangeEvent {
+                        range: Some(e.range),
+                        text: e.new_text.clone(),
+                        range_length: None,
+                    })
+                    .collect(),
+            })
+            .await;
+
+        // Assert
+        let doc = service.inner().document_map.get(&TEST_URI).unwrap();
+        assert_eq!(doc.rope.to_string(), String::from(after));
+    }
 }
@@ -1,21 +1,32 @@
-use tower_lsp::lsp_types::{GotoDefinitionParams, GotoDefinitionResponse, Location, Range};
+use std::{fs, path::Path};
+
+use regex::Regex;
+use ropey::Rope;
+use tower_lsp::lsp_types::{GotoDefinitionParams, GotoDefinitionResponse, Location, Range, Url};
 use tracing::{info, warn};
 use tree_sitter::QueryCursor;
+use ts_query_ls::Options;
 
 use crate::{
-    Backend, LspClient,
+    Backend, LspClient, SymbolInfo,
     util::{
-        CAPTURES_QUERY, NodeUtil, PosUtil, TextProviderRope, get_current_capture_node,
-        get_imported_module_under_cursor, get_references,
+        ByteUtil, CAPTURES_QUERY, NodeUtil, PosUtil, TextProviderRope, get_current_capture_node,
+        get_grammar_directories, get_imported_module_under_cursor, get_references,
     },
 };
 
-pub fn goto_definition<C: LspClient>(
+use super::references::{LanguageSymbol, get_language_symbol};
+
+/// The grammar source files which may define a node or field, in order of precedence.
+const GRAMMAR_FILES: [&str; 3] = ["grammar.js", "src/grammar.json", "src/node-types.json"];
+
+pub async fn goto_definition<C: LspClient>(
     backend: &Backend<C>,
     params: &GotoDefinitionParams,
 ) -> Option<GotoDefinitionResponse> {
     info!("ts_query_ls goto_definition: {params:?}");
     let uri = &params.text_document_position_params.text_document.uri;
+    let options = backend.options.read().await;
     let Some(doc) = backend.document_map.get(uri) else {
         warn!("No document found for URI: {uri} when handling goto_definition");
         return None;
@@ -34,8 +45,12 @@ pub fn goto_definition<C: LspClient>(
         });
     }
 
-    let current_node = get_current_capture_node(tree.root_node(), cur_pos.to_ts_point(rope))?;
-
+    let point = cur_pos.to_ts_point(rope);
+    let Some(current_node) = get_current_capture_node(tree.root_node(), point) else {
+        let symbol = get_language_symbol(tree.root_node(), point, rope, &options)?;
+        let language_name = doc.language_name.as_deref()?;
+        return get_grammar_definition(uri, language_name, &symbol, &options).map(Into::into);
+    };
     let query = &CAPTURES_QUERY;
     let mut cursor = QueryCursor::new();
     let provider = TextProviderRope(rope);
@@ -58,6 +73,57 @@ pub fn goto_definition<C: LspClient>(
     Some(GotoDefinitionResponse::Array(defs))
 }
 
+/// Find the rule defining a named node or field in the grammar sources of a language.
+fn get_grammar_definition(
+    uri: &Url,
+    language_name: &str,
+    symbol: &LanguageSymbol,
+    options: &Options,
+) -> Option<Location> {
+    let patterns = match symbol {
+        LanguageSymbol::Node(SymbolInfo { label, named: true }) => {
+            let name = regex::escape(label);
+            [
+                format!(r"(?m)^\s*({name})\s*:\s*\(?\s*[$_]"),
+                format!(r#"(?m)^\s*"({name})"\s*:\s*\{{"#),
+                format!(r#""type"\s*:\s*"({name})",\s*"named"\s*:\s*true"#),
+            ]
+        }
+        LanguageSymbol::Field(field) => {
+            let name = regex::escape(field);
+            [
+                format!(r#"field\(\s*['"]({name})['"]"#),
+                format!(r#""type"\s*:\s*"FIELD",\s*"name"\s*:\s*"({name})""#),
+                format!(r#""({name})"\s*:\s*\{{\s*"multiple""#),
+            ]
+        }
+        LanguageSymbol::Node(SymbolInfo { named: false, .. }) => return None,
+    };
+
+    get_grammar_directories(uri, language_name, options)
+        .into_iter()
+        .find_map(|dir| {
+            GRAMMAR_FILES
+                .iter()
+                .zip(&patterns)
+                .find_map(|(file, pattern)| find_in_file(&dir.join(file), pattern))
+        })
+}
+
+/// Get the location of the first capture group of the pattern within the given file.
+fn find_in_file(path: &Path, pattern: &str) -> Option<Location> {
+    let contents = fs::read_to_string(path).ok()?;
+    let matched = Regex::new(pattern).ok()?.captures(&contents)?.get(1)?;
+    let rope = Rope::from_str(&contents);
+    Some(Location {
+        uri: Url::from_file_path(path).ok()?,
+        range: Range::new(
+            matched.start().to_lsp_pos(&rope),
+            matched.end().to_lsp_pos(&rope),
+        ),
+    })
+}
+
 #[cfg(test)]
 mod test {
     use pretty_assertions::assert_eq;
@@ -75,6 +141,8 @@ mod test {
         },
     };
 
+    const GRAMMARS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/grammars");
+
     type Coordinate = ((u32, u32), (u32, u32));
 
     #[rstest]
@@ -145,4 +213,108 @@ mod test {
         };
         assert_eq!(expected, refs);
     }
+
+    fn grammar_location(path: &str, range: Coordinate) -> Location {
+        Location {
+            uri: Url::from_file_path(format!("{GRAMMARS_DIR}/{path}")).unwrap(),
+            range: Range::new(
+                Position::new(range.0.0, range.0.1),
+                Position::new(range.1.0, range.1.1),
+            ),
+        }
+    }
+
+    #[rstest]
+    #[case(
+        "file:///tmp/queries/foo/highlights.scm",
+        "(call_expression\n  function: (identifier) @function)",
+        Position::new(0, 3),
+        true,
+        Some(grammar_location("tree-sitter-foo/grammar.js", ((10, 4), (10, 19))))
+    )]
+    #[case(
+        "file:///tmp/queries/foo/highlights.scm",
+        "(call_expression\n  function: (identifier) @function)",
+        Position::new(1, 4),
+        true,
+        Some(grammar_location("tree-sitter-foo/grammar.js", ((11, 13), (11, 21))))
+    )]
+    #[case(
+        "file:///tmp/queries/foo/highlights.scm",
+        "(comment) @comment",
+        Position::new(0, 3),
+        true,
+        Some(grammar_location("tree-sitter-foo/src/node-types.json", ((70, 13), (70, 20))))
+    )]
+    #[case(
+        "file:///tmp/queries/foo/highlights.scm",
+        "(call_expression \"(\")",
+        Position::new(0, 18),
+        true,
+        None
+    )]
+    #[case(
+        "file:///tmp/queries/foo/highlights.scm",
+        "(call_expression)",
+        Position::new(0, 3),
+        false,
+        None
+    )]
+    #[case(
+        concat!(
+            "file://",
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/grammars/tree-sitter-bar/queries/highlights.scm"
+        ),
+        include_str!("../../tests/fixtures/grammars/tree-sitter-bar/queries/highlights.scm"),
+        Position::new(0, 2),
+        false,
+        Some(grammar_location("tree-sitter-bar/src/grammar.json", ((10, 5), (10, 9))))
+    )]
+    #[case(
+        concat!(
+            "file://",
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/grammars/tree-sitter-bar/queries/highlights.scm"
+        ),
+        include_str!("../../tests/fixtures/grammars/tree-sitter-bar/queries/highlights.scm"),
+        Position::new(1, 3),
+        false,
+        Some(grammar_location("tree-sitter-bar/src/grammar.json", ((15, 19), (15, 22))))
+    )]
+    #[tokio::test(flavor = "current_thread")]
+    async fn goto_grammar_definition(
+        #[case] uri: &str,
+        #[case] input: &str,
+        #[case] position: Position,
+        #[case] use_grammar_directories: bool,
+        #[case] expected: Option<Location>,
+    ) {
+        // Arrange
+        let uri = Url::parse(uri).unwrap();
+        let options = Options {
+            grammar_directories: if use_grammar_directories {
+                vec![String::from(GRAMMARS_DIR)]
+            } else {
+                Vec::new()
+            },
+            ..Default::default()
+        };
+        let mut service = initialize_server(&[(uri.clone(), input)], &options).await;
+
+        // Act
+        let definition = service
+            .request::<GotoDefinition>(GotoDefinitionParams {
+                partial_result_params: PartialResultParams::default(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier { uri },
+                    position,
+                },
+            })
+            .await;
+
+        // Assert
+        assert_eq!(definition, expected.map(GotoDefinitionResponse::Scalar));
+    }
 }
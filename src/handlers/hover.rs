@@ -6,13 +6,15 @@ use tower_lsp::{
 };
 use tracing::warn;
 use tree_sitter::Query;
-use ts_query_ls::{ParameterConstraint, PredicateParameterType};
+use ts_query_ls::{CaptureResolution, ParameterConstraint, PredicateParameterType};
 
 use crate::{
     Backend, LspClient, QUERY_LANGUAGE, SymbolInfo,
+    node_types::get_node_info,
     util::{
-        FORMAT_IGNORE_REGEX, INHERITS_REGEX, NodeUtil, PosUtil, capture_at_pos,
-        get_imported_module_under_cursor, remove_unnecessary_escapes, uri_to_basename,
+        FORMAT_IGNORE_REGEX, FORMAT_IGNORE_REGION_REGEX, INHERITS_REGEX, NodeUtil, PosUtil,
+        capture_at_pos, get_imported_module_under_cursor, remove_unnecessary_escapes,
+        uri_to_basename,
     },
 };
 
@@ -48,6 +50,7 @@ static DOCS: LazyLock<HashMap<&'static str, &'static str>> = include_docs_map!(
     "negation",
     "inherits",
     "format-ignore",
+    "format-ignore-region",
 );
 
 pub async fn hover<C: LspClient>(
@@ -90,24 +93,28 @@ pub async fn hover<C: LspClient>(
             })
         }
         "capture" => {
-            let options = backend.options.read().await;
-            if let Some(description) = uri_to_basename(uri).and_then(|base| {
-                options
-                    .valid_captures
-                    .get(&base)
-                    .and_then(|c| c.get(&capture_text[1..].to_string()))
-            }) {
-                let value = format!("## `{capture_text}`\n\n{description}");
-                Some(Hover {
-                    range,
-                    contents: HoverContents::Markup(MarkupContent {
-                        kind: MarkupKind::Markdown,
-                        value,
-                    }),
-                })
-            } else {
-                None
-            }
+            let capture_name = &capture_text[1..];
+            let value = uri_to_basename(uri)
+                .and_then(|base| options.resolve_capture(&base, capture_name))
+                .map(|resolution| match resolution {
+                    CaptureResolution::Declared { name, description } if name == capture_name => {
+                        format!("## `{capture_text}`\n\n{description}")
+                    }
+                    CaptureResolution::Declared { name, description } => {
+                        format!("## `{capture_text}`\n\nResolved to `@{name}`\n\n{description}")
+                    }
+                    CaptureResolution::Pattern(pattern) => format!(
+                        "## `{capture_text}`\n\nMatched by the capture pattern `{}`",
+                        pattern.as_str()
+                    ),
+                });
+            value.map(|value| Hover {
+                range,
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value,
+                }),
+            })
         }
         "identifier.node" => {
             let sym = SymbolInfo {
@@ -130,6 +137,22 @@ pub async fn hover<C: LspClient>(
                         value,
                     }),
                 })
+            } else if let Some(node_info) = doc
+                .language_name
+                .as_deref()
+                .and_then(|name| get_node_info(&backend.node_types_map, uri, name, &options, &sym))
+            {
+                Some(Hover {
+                    range,
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: format!(
+                            "Structure of `({})`:\n\n```query\n{}\n```",
+                            sym.label,
+                            node_info.structure()
+                        ),
+                    }),
+                })
             } else if let Some(language) = language_data.as_ref().map(|ld| &ld.language) {
                 let syms = (0..language.node_kind_count() as u16)
                     .filter(|&id| {
@@ -204,7 +227,42 @@ pub async fn hover<C: LspClient>(
             }
         }
         "field" => {
-            if let Some(language) = language_data.as_ref().map(|ld| &ld.language) {
+            let parent_node = capture
+                .node
+                .parent()
+                .and_then(|field| field.parent())
+                .filter(|parent| parent.kind() == "named_node")
+                .and_then(|parent| parent.child_by_field_name("name"));
+            let field_info = parent_node.zip(doc.language_name.as_deref()).and_then(
+                |(parent_node, language_name)| {
+                    let parent = SymbolInfo {
+                        label: parent_node.text(rope),
+                        named: true,
+                    };
+                    get_node_info(
+                        &backend.node_types_map,
+                        uri,
+                        language_name,
+                        &options,
+                        &parent,
+                    )?
+                    .fields
+                    .remove(&capture_text)
+                    .map(|field| (parent, field))
+                },
+            );
+            if let Some((parent, field)) = field_info {
+                Some(Hover {
+                    range,
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: format!(
+                            "Field `{capture_text}` of `{parent}`:\n\n```query\n{capture_text}: {}\n```",
+                            field.pattern()
+                        ),
+                    }),
+                })
+            } else if let Some(language) = language_data.as_ref().map(|ld| &ld.language) {
                 let sym = (1..=language.field_count() as u16).find(|&id| {
                     language
                         .field_name_for_id(id)
@@ -304,17 +362,20 @@ pub async fn hover<C: LspClient>(
                     }),
                 }));
             }
-            if FORMAT_IGNORE_REGEX.is_match(&capture_text) {
-                Some(Hover {
-                    range,
-                    contents: HoverContents::Markup(MarkupContent {
-                        kind: MarkupKind::Markdown,
-                        value: (*DOCS.get("format-ignore").unwrap()).to_string(),
-                    }),
-                })
+            let doc_name = if FORMAT_IGNORE_REGEX.is_match(&capture_text) {
+                "format-ignore"
+            } else if FORMAT_IGNORE_REGION_REGEX.is_match(&capture_text) {
+                "format-ignore-region"
             } else {
-                None
-            }
+                return Ok(None);
+            };
+            Some(Hover {
+                range,
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: (*DOCS.get(doc_name).unwrap()).to_string(),
+                }),
+            })
         }
         _ => None,
     })
@@ -330,10 +391,11 @@ mod test {
     };
 
     use pretty_assertions::assert_eq;
+    use regex::Regex;
     use rstest::rstest;
     use tower_lsp::lsp_types::{
         Hover, HoverContents, HoverParams, MarkupContent, MarkupKind, Position, Range,
-        TextDocumentIdentifier, TextDocumentPositionParams, WorkDoneProgressParams,
+        TextDocumentIdentifier, TextDocumentPositionParams, Url, WorkDoneProgressParams,
         request::HoverRequest,
     };
 
@@ -525,6 +587,14 @@ An error node", BTreeMap::from([(String::from("error"), String::from("An error n
         end: Position::new(0, 17)
     },
     "## `; format-ignore`\n\nThe formatter will ignore nodes that are preceeded by a comment starting with\n`format-ignore`.\n\n```query\n((call_expression\n  function: (identifier) @function.builtin)\n  ; format-ignore\n  (#any-of? @function.builtin\n    \"printf\"   \"printf_s\"\n    \"vprintf\"  \"vprintf_s\"\n    \"scanf\"    \"scanf_s\"\n    \"vscanf\"   \"vscanf_s\"\n    \"wprintf\"  \"wprintf_s\"\n    \"vwprintf\" \"vwprintf_s\"\n    \"wscanf\"   \"wscanf_s\"\n    \"vwscanf\"  \"vwscanf_s\"\n    \"cscanf\"   \"_cscanf\"\n    \"printw\"\n    \"scanw\"))\n```\n", BTreeMap::default())]
+    #[case(";; format-ignore-end", Position { line: 0, character: 5 }, Range {
+        start: Position::new(0, 0),
+        end: Position::new(0, 20)
+    },
+    include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/docs/format-ignore-region.md"
+    )), BTreeMap::default())]
     #[case("; inherits: cpp", Position { line: 0, character: 13 }, Range {
         start: Position::new(0, 12),
         end: Position::new(0, 15)
@@ -598,6 +668,7 @@ An error node", BTreeMap::from([(String::from("error"), String::from("An error n
                                 constraint: ParameterConstraint::None,
                             },
                         ],
+                        ..Default::default()
                     },
                 )]),
                 valid_directives: BTreeMap::from([(
@@ -613,6 +684,7 @@ An error node", BTreeMap::from([(String::from("error"), String::from("An error n
                                 String::from("there"),
                             ]),
                         }],
+                        ..Default::default()
                     },
                 )]),
                 ..Default::default()
@@ -647,4 +719,155 @@ An error node", BTreeMap::from([(String::from("error"), String::from("An error n
         };
         assert_eq!(expected, tokens,);
     }
+
+    #[rstest]
+    #[case(
+        Position::new(0, 3),
+        Range::new(Position::new(0, 1), Position::new(0, 16)),
+        r"Structure of `(call_expression)`:
+
+```query
+(call_expression
+  arguments: (arguments)
+  function: (identifier))
+```"
+    )]
+    #[case(
+        Position::new(1, 4),
+        Range::new(Position::new(1, 2), Position::new(1, 10)),
+        r"Field `function` of `(call_expression)`:
+
+```query
+function: (identifier)
+```"
+    )]
+    #[case(
+        Position::new(1, 15),
+        Range::new(Position::new(1, 13), Position::new(1, 23)),
+        r"Structure of `(identifier)`:
+
+```query
+(identifier)
+```"
+    )]
+    #[case(
+        Position::new(2, 4),
+        Range::new(Position::new(2, 1), Position::new(2, 12)),
+        r"Structure of `(source_file)`:
+
+```query
+(source_file
+  [(call_expression) (identifier)]*)
+```"
+    )]
+    #[case(Position::new(2, 18), Range::default(), "")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn hover_node_types(
+        #[case] position: Position,
+        #[case] range: Range,
+        #[case] hover_content: &str,
+    ) {
+        // Arrange
+        let uri = Url::parse("file:///tmp/queries/foo/highlights.scm").unwrap();
+        let source = "(call_expression\n  function: (identifier) @function)\n(source_file !body)";
+        let mut service = initialize_server(
+            &[(uri.clone(), source)],
+            &Options {
+                grammar_directories: vec![String::from(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/tests/fixtures/grammars"
+                ))],
+                ..Default::default()
+            },
+        )
+        .await;
+
+        // Act
+        let hover = service
+            .request::<HoverRequest>(HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier { uri },
+                    position,
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await;
+
+        // Assert
+        let expected = (!hover_content.is_empty()).then(|| Hover {
+            range: Some(range),
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: String::from(hover_content),
+            }),
+        });
+        assert_eq!(expected, hover);
+    }
+
+    #[rstest]
+    #[case(
+        Position::new(0, 16),
+        Range::new(Position::new(0, 13), Position::new(0, 34)),
+        "## `@function.method.call`\n\nResolved to `@function`\n\nFunction calls"
+    )]
+    #[case(
+        Position::new(0, 40),
+        Range::new(Position::new(0, 35), Position::new(0, 54)),
+        "## `@injection.language`\n\nMatched by the capture pattern `^injection\\..*`"
+    )]
+    #[case(
+        Position::new(0, 58),
+        Range::new(Position::new(0, 55), Position::new(0, 64)),
+        "## `@function`\n\nFunction calls"
+    )]
+    #[tokio::test(flavor = "current_thread")]
+    async fn hover_capture_resolution(
+        #[case] position: Position,
+        #[case] range: Range,
+        #[case] hover_content: &str,
+    ) {
+        // Arrange
+        let source = "(identifier) @function.method.call @injection.language @function";
+        let mut service = initialize_server(
+            &[(QUERY_TEST_URI.clone(), source)],
+            &Options {
+                valid_captures: HashMap::from([(
+                    String::from("test"),
+                    BTreeMap::from([(String::from("function"), String::from("Function calls"))]),
+                )]),
+                valid_capture_patterns: HashMap::from([(
+                    String::from("test"),
+                    vec![Regex::new(r"^injection\..*").unwrap().into()],
+                )]),
+                hierarchical_captures: true,
+                ..Default::default()
+            },
+        )
+        .await;
+
+        // Act
+        let hover = service
+            .request::<HoverRequest>(HoverParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: QUERY_TEST_URI.clone(),
+                    },
+                    position,
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await;
+
+        // Assert
+        assert_eq!(
+            Some(Hover {
+                range: Some(range),
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: String::from(hover_content),
+                }),
+            }),
+            hover
+        );
+    }
 }
@@ -41,7 +41,7 @@ pub async fn initialize<C: LspClient>(
     set_configuration_options(
         backend,
         params.initialization_options,
-        backend
+        &backend
             .workspace_paths
             .read()
             .map(|r| r.to_vec())
@@ -74,6 +74,7 @@ mod test {
 
     use crate::{
         Backend, Options, SERVER_CAPABILITIES,
+        handlers::workspace_symbol::SymbolIndex,
         test_helpers::helpers::{MockClient, TestService},
     };
 
@@ -88,6 +89,10 @@ mod test {
             workspace_paths: Arc::default(),
             dependents: DashMap::default(),
             options: Arc::default(),
+            symbol_index: SymbolIndex::default(),
+            semantic_tokens_map: DashMap::default(),
+            node_types_map: DashMap::default(),
+            parser_names_map: DashMap::default(),
         })
         .finish();
         unsafe { env::set_var("HOME", "/home/jdoe") };
@@ -0,0 +1,162 @@
+use tower_lsp::lsp_types::{
+    DidChangeWatchedFilesRegistrationOptions, FileSystemWatcher, GlobPattern, InitializedParams,
+    Registration, RegistrationParams, TypeHierarchyRegistrationOptions,
+    request::RegisterCapability,
+};
+use tracing::warn;
+
+use crate::{Backend, LspClient};
+
+/// The ID used when dynamically registering type hierarchy support.
+pub const TYPE_HIERARCHY_REGISTRATION_ID: &str = "ts_query_ls/typeHierarchy";
+/// The ID used when dynamically registering the query file watcher.
+pub const WATCHED_FILES_REGISTRATION_ID: &str = "ts_query_ls/watchedFiles";
+
+pub async fn initialized<C: LspClient>(backend: &Backend<C>, _params: InitializedParams) {
+    let mut registrations = Vec::new();
+    let client_capabilities = backend.client_capabilities.read().await;
+
+    // The version of `lsp-types` that we depend on has no static server capability for type
+    // hierarchies, so it must be registered dynamically.
+    if client_capabilities
+        .text_document
+        .as_ref()
+        .and_then(|td| td.type_hierarchy.as_ref())
+        .and_then(|th| th.dynamic_registration)
+        .unwrap_or_default()
+    {
+        registrations.push(Registration {
+            id: String::from(TYPE_HIERARCHY_REGISTRATION_ID),
+            method: String::from("textDocument/prepareTypeHierarchy"),
+            register_options: serde_json::to_value(TypeHierarchyRegistrationOptions::default())
+                .ok(),
+        });
+    }
+
+    // Keep the workspace symbol index up to date with query files changed outside of the editor.
+    if client_capabilities
+        .workspace
+        .as_ref()
+        .and_then(|ws| ws.did_change_watched_files.as_ref())
+        .and_then(|wf| wf.dynamic_registration)
+        .unwrap_or_default()
+    {
+        registrations.push(Registration {
+            id: String::from(WATCHED_FILES_REGISTRATION_ID),
+            method: String::from("workspace/didChangeWatchedFiles"),
+            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(String::from("**/*.scm")),
+                    kind: None,
+                }],
+            })
+            .ok(),
+        });
+    }
+    drop(client_capabilities);
+
+    if registrations.is_empty() {
+        return;
+    }
+    if let Err(err) = backend
+        .client
+        .send_request::<RegisterCapability>(RegistrationParams { registrations })
+        .await
+    {
+        warn!("Failed to register capabilities: {err}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use tower_lsp::lsp_types::{
+        DidChangeWatchedFilesClientCapabilities, DidChangeWatchedFilesRegistrationOptions,
+        FileSystemWatcher, GlobPattern, InitializedParams, Registration, RegistrationParams,
+        TextDocumentClientCapabilities, TypeHierarchyClientCapabilities,
+        TypeHierarchyRegistrationOptions, WorkspaceClientCapabilities, notification::Initialized,
+        request::RegisterCapability,
+    };
+
+    use crate::{
+        Options,
+        test_helpers::helpers::{MockRequest, TestService, initialize_server},
+    };
+
+    use super::{TYPE_HIERARCHY_REGISTRATION_ID, WATCHED_FILES_REGISTRATION_ID};
+
+    #[rstest]
+    #[case(Some(true), None, vec![TYPE_HIERARCHY_REGISTRATION_ID])]
+    #[case(Some(false), Some(true), vec![WATCHED_FILES_REGISTRATION_ID])]
+    #[case(
+        Some(true),
+        Some(true),
+        vec![TYPE_HIERARCHY_REGISTRATION_ID, WATCHED_FILES_REGISTRATION_ID]
+    )]
+    #[case(None, Some(false), vec![])]
+    #[tokio::test(flavor = "current_thread")]
+    async fn initialized_registers_capabilities(
+        #[case] type_hierarchy_dynamic_registration: Option<bool>,
+        #[case] watched_files_dynamic_registration: Option<bool>,
+        #[case] registered: Vec<&str>,
+    ) {
+        // Arrange
+        let mut service = initialize_server(&[], &Options::default()).await;
+        {
+            let mut client_capabilities = service.inner().client_capabilities.write().await;
+            client_capabilities.text_document = Some(TextDocumentClientCapabilities {
+                type_hierarchy: Some(TypeHierarchyClientCapabilities {
+                    dynamic_registration: type_hierarchy_dynamic_registration,
+                }),
+                ..Default::default()
+            });
+            client_capabilities.workspace = Some(WorkspaceClientCapabilities {
+                did_change_watched_files: Some(DidChangeWatchedFilesClientCapabilities {
+                    dynamic_registration: watched_files_dynamic_registration,
+                    relative_pattern_support: None,
+                }),
+                ..Default::default()
+            });
+        }
+
+        // Act
+        service.notify::<Initialized>(InitializedParams {}).await;
+
+        // Assert
+        let registrations = registered
+            .into_iter()
+            .map(|id| match id {
+                TYPE_HIERARCHY_REGISTRATION_ID => Registration {
+                    id: String::from(id),
+                    method: String::from("textDocument/prepareTypeHierarchy"),
+                    register_options: serde_json::to_value(
+                        TypeHierarchyRegistrationOptions::default(),
+                    )
+                    .ok(),
+                },
+                _ => Registration {
+                    id: String::from(id),
+                    method: String::from("workspace/didChangeWatchedFiles"),
+                    register_options: serde_json::to_value(
+                        DidChangeWatchedFilesRegistrationOptions {
+                            watchers: vec![FileSystemWatcher {
+                                glob_pattern: GlobPattern::String(String::from("**/*.scm")),
+                                kind: None,
+                            }],
+                        },
+                    )
+                    .ok(),
+                },
+            })
+            .collect::<Vec<_>>();
+        let expected = if registrations.is_empty() {
+            Vec::new()
+        } else {
+            vec![MockRequest::from_request::<RegisterCapability>(
+                RegistrationParams { registrations },
+            )]
+        };
+        assert_eq!(service.inner().client.get_requests(), expected);
+    }
+}
@@ -3,18 +3,23 @@ pub mod completion;
 pub mod diagnostic;
 pub mod did_change;
 pub mod did_change_configuration;
+pub mod did_change_watched_files;
 pub mod did_close;
 pub mod did_open;
 pub mod did_save;
 pub mod document_highlight;
+pub mod document_link;
 pub mod document_symbol;
+pub mod execute_command;
 pub mod formatting;
 pub mod goto_definition;
 pub mod hover;
 pub mod initialize;
+pub mod initialized;
 pub mod references;
 pub mod rename;
 pub mod selection_range;
 pub mod semantic_tokens;
 pub mod shutdown;
+pub mod type_hierarchy;
 pub mod workspace_symbol;
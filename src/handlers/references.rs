@@ -1,19 +1,54 @@
-use tower_lsp::lsp_types::{Location, ReferenceParams};
+use std::{collections::BTreeMap, sync::LazyLock};
+
+use ropey::Rope;
+use streaming_iterator::StreamingIterator;
+use tower_lsp::lsp_types::{Location, ProgressToken, Range, ReferenceParams, Url};
 use tracing::warn;
-use tree_sitter::QueryCursor;
+use tree_sitter::{Node, Point, Query, QueryCursor, Tree};
+
+use ts_query_ls::{Options, ParameterConstraint, PredicateParameter, PredicateParameterArity};
 
-use crate::LspClient;
-use crate::util::{CAPTURES_QUERY, NodeUtil, PosUtil};
 use crate::{
-    Backend,
-    util::{TextProviderRope, get_current_capture_node, get_references},
+    Backend, LspClient, QUERY_LANGUAGE, SymbolInfo,
+    util::{
+        CAPTURES_QUERY, NodeUtil, PosUtil, TextProviderRope, get_current_capture_node,
+        get_imported_uris, get_language_name, get_references,
+    },
 };
 
-pub fn references<C: LspClient>(
+use super::workspace_symbol::{ensure_index, get_indexed_file, get_indexed_uris};
+
+/// Matches every node which may name a node kind or a field.
+static LANGUAGE_SYMBOLS_QUERY: LazyLock<Query> = LazyLock::new(|| {
+    Query::new(
+        &QUERY_LANGUAGE,
+        r"
+(named_node (identifier) @node)
+(missing_node name: (identifier) @node)
+(anonymous_node name: (string) @node)
+(missing_node name: (string) @node)
+(field_definition name: (identifier) @field)
+(negated_field (identifier) @field)
+(predicate) @predicate
+",
+    )
+    .unwrap()
+});
+
+/// A node kind or field name of a language. Unlike captures, these are shared by every query of
+/// the language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LanguageSymbol {
+    Node(SymbolInfo),
+    Field(String),
+}
+
+pub async fn references<C: LspClient>(
     backend: &Backend<C>,
     params: &ReferenceParams,
 ) -> Option<Vec<Location>> {
     let uri = &params.text_document_position.text_document.uri;
+    let options = backend.options.read().await;
 
     let Some(doc) = backend.document_map.get(uri) else {
         warn!("No document found for URI: {uri} when handling references");
@@ -22,7 +57,21 @@ pub fn references<C: LspClient>(
     let rope = &doc.rope;
     let tree = &doc.tree;
     let cur_pos = params.text_document_position.position.to_ts_point(rope);
-    let current_node = get_current_capture_node(tree.root_node(), cur_pos)?;
+    let Some(current_node) = get_current_capture_node(tree.root_node(), cur_pos) else {
+        let symbol = get_language_symbol(tree.root_node(), cur_pos, rope, &options)?;
+        let language_name = doc.language_name.clone()?;
+        drop(doc);
+        return Some(
+            get_language_references(
+                backend,
+                &language_name,
+                &symbol,
+                &options,
+                params.work_done_progress_params.work_done_token.clone(),
+            )
+            .await,
+        );
+    };
 
     let include_def = params.context.include_declaration;
     let query = &CAPTURES_QUERY;
@@ -52,19 +101,262 @@ pub fn references<C: LspClient>(
     )
 }
 
+/// Get the node kind or field name at the given point, if any.
+pub fn get_language_symbol(
+    root: Node,
+    point: Point,
+    rope: &Rope,
+    options: &Options,
+) -> Option<LanguageSymbol> {
+    let mut node = root.named_descendant_for_point_range(point, point)?;
+    while matches!(node.kind(), "string_content" | "escape_sequence") {
+        node = node.parent()?;
+    }
+    if let Some((symbol, _)) = node_symbol(node, rope) {
+        return Some(symbol);
+    }
+    let predicate = node
+        .parent()
+        .filter(|parent| parent.kind() == "parameters")?
+        .parent()?;
+    named_node_arguments(predicate, rope, options)
+        .into_iter()
+        .find(|arg| *arg == node)
+        .and_then(|arg| argument_symbol(arg, rope))
+        .map(|(symbol, _)| symbol)
+}
+
+/// Find every reference to the given symbol in the queries of a language, including the modules
+/// that they inherit.
+pub async fn get_language_references<C: LspClient>(
+    backend: &Backend<C>,
+    language_name: &str,
+    symbol: &LanguageSymbol,
+    options: &Options,
+    work_done_token: Option<ProgressToken>,
+) -> Vec<Location> {
+    get_language_documents(backend, language_name, options, work_done_token)
+        .await
+        .into_iter()
+        .flat_map(|(uri, rope, tree)| {
+            get_symbol_ranges(&rope, &tree, symbol, options)
+                .into_iter()
+                .map(move |range| Location {
+                    uri: uri.clone(),
+                    range,
+                })
+        })
+        .collect()
+}
+
+/// Get the ranges of every reference to the given symbol in a document. The ranges of anonymous
+/// node kinds and string arguments exclude the surrounding quotes.
+pub fn get_symbol_ranges(
+    rope: &Rope,
+    tree: &Tree,
+    symbol: &LanguageSymbol,
+    options: &Options,
+) -> Vec<Range> {
+    let mut cursor = QueryCursor::new();
+    let provider = TextProviderRope(rope);
+    let mut captures = cursor.captures(&LANGUAGE_SYMBOLS_QUERY, tree.root_node(), &provider);
+    let mut ranges = Vec::new();
+    while let Some((match_, index)) = captures.next() {
+        let node = match_.captures[*index].node;
+        let symbols = if node.kind() == "predicate" {
+            named_node_arguments(node, rope, options)
+                .into_iter()
+                .filter_map(|arg| argument_symbol(arg, rope))
+                .collect()
+        } else {
+            node_symbol(node, rope).into_iter().collect::<Vec<_>>()
+        };
+        ranges.extend(
+            symbols
+                .into_iter()
+                .filter(|(found, _)| found == symbol)
+                .map(|(_, node)| node.lsp_range(rope)),
+        );
+    }
+    ranges
+}
+
+/// Get the text and syntax tree of every query of a language: the workspace files and open
+/// documents whose language resolves to it, and the modules that they inherit. Workspace files
+/// are taken from the symbol index, which is built on first use, but open documents are preferred
+/// to them, since they may have unsaved changes.
+pub async fn get_language_documents<C: LspClient>(
+    backend: &Backend<C>,
+    language_name: &str,
+    options: &Options,
+    work_done_token: Option<ProgressToken>,
+) -> Vec<(Url, Rope, Tree)> {
+    ensure_index(backend, work_done_token).await;
+    let dirs = backend
+        .workspace_paths
+        .read()
+        .as_deref()
+        .cloned()
+        .unwrap_or_default();
+    let mut pending = get_indexed_uris(backend)
+        .into_iter()
+        .filter(|uri| get_language_name(uri, options).as_deref() == Some(language_name))
+        .collect::<Vec<_>>();
+    pending.extend(
+        backend
+            .document_map
+            .iter()
+            .filter(|doc| doc.language_name.as_deref() == Some(language_name))
+            .map(|doc| doc.key().clone()),
+    );
+
+    let mut documents = BTreeMap::new();
+    while let Some(uri) = pending.pop() {
+        if documents.contains_key(&uri) {
+            continue;
+        }
+        let contents = backend
+            .document_map
+            .get(&uri)
+            .filter(|doc| doc.version.is_some())
+            .map(|doc| (doc.rope.clone(), doc.tree.clone()))
+            .or_else(|| {
+                get_indexed_file(backend, &uri).map(|file| (file.rope.clone(), file.tree.clone()))
+            })
+            .or_else(|| {
+                backend
+                    .document_map
+                    .get(&uri)
+                    .map(|doc| (doc.rope.clone(), doc.tree.clone()))
+            });
+        let Some((rope, tree)) = contents else {
+            continue;
+        };
+        pending.extend(
+            get_imported_uris(&dirs, options, &uri, &rope, &tree)
+                .into_iter()
+                .filter_map(|import| import.uri),
+        );
+        documents.insert(uri, (rope, tree));
+    }
+
+    documents
+        .into_iter()
+        .map(|(uri, (rope, tree))| (uri, rope, tree))
+        .collect()
+}
+
+/// Get the symbol named by an identifier or string within a node or field definition, along with
+/// the node spanning its name.
+fn node_symbol<'t>(node: Node<'t>, rope: &Rope) -> Option<(LanguageSymbol, Node<'t>)> {
+    match (node.kind(), node.parent()?.kind()) {
+        ("identifier", "named_node" | "missing_node") => Some((
+            LanguageSymbol::Node(SymbolInfo {
+                label: node.text(rope),
+                named: true,
+            }),
+            node,
+        )),
+        ("string", "anonymous_node" | "missing_node") => {
+            let content = node
+                .named_child(0)
+                .filter(|child| child.kind() == "string_content")?;
+            Some((
+                LanguageSymbol::Node(SymbolInfo {
+                    label: content.text(rope),
+                    named: false,
+                }),
+                content,
+            ))
+        }
+        ("identifier", "field_definition" | "negated_field") => {
+            Some((LanguageSymbol::Field(node.text(rope)), node))
+        }
+        _ => None,
+    }
+}
+
+/// Get the named node kind given as a predicate argument, along with the node spanning it.
+fn argument_symbol<'t>(arg: Node<'t>, rope: &Rope) -> Option<(LanguageSymbol, Node<'t>)> {
+    let name = if arg.kind() == "string" {
+        arg.named_child(0)
+            .filter(|child| child.kind() == "string_content")?
+    } else {
+        arg
+    };
+    Some((
+        LanguageSymbol::Node(SymbolInfo {
+            label: name.text(rope),
+            named: true,
+        }),
+        name,
+    ))
+}
+
+/// Get the arguments of a predicate whose parameters are constrained to named node kinds.
+fn named_node_arguments<'t>(predicate: Node<'t>, rope: &Rope, options: &Options) -> Vec<Node<'t>> {
+    let mut cursor = predicate.walk();
+    let Some(name) = predicate
+        .children_by_field_name("name", &mut cursor)
+        .find(|child| child.kind() == "identifier")
+    else {
+        return Vec::new();
+    };
+    let validator = match predicate.child_by_field_name("type") {
+        Some(predicate_type) if predicate_type.text(rope) == "?" => &options.valid_predicates,
+        Some(_) => &options.valid_directives,
+        None => return Vec::new(),
+    };
+    let (Some(spec), Some(params)) = (
+        validator.get(&name.text(rope)),
+        predicate.child_by_field_name("parameters"),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut param_spec_iter = spec.parameters.iter();
+    let mut prev_param_spec = None;
+    let mut args = Vec::new();
+    for param in params.named_children(&mut cursor) {
+        let param_spec = param_spec_iter.next().or_else(|| {
+            prev_param_spec
+                .filter(|p: &&PredicateParameter| p.arity == PredicateParameterArity::Variadic)
+        });
+        let Some(param_spec) = param_spec else {
+            break;
+        };
+        prev_param_spec = Some(param_spec);
+        if param_spec.constraint == ParameterConstraint::NamedNode
+            && matches!(param.kind(), "string" | "identifier")
+        {
+            args.push(param);
+        }
+    }
+    args
+}
+
 #[cfg(test)]
 mod test {
     use pretty_assertions::assert_eq;
     use rstest::rstest;
+    use std::collections::BTreeMap;
+
     use tower_lsp::lsp_types::{
-        Location, PartialResultParams, Position, Range, ReferenceContext, ReferenceParams,
-        TextDocumentIdentifier, TextDocumentPositionParams, WorkDoneProgressParams,
-        request::References,
+        DidChangeWatchedFilesParams, FileChangeType, FileEvent, Location, PartialResultParams,
+        Position, Range, ReferenceContext, ReferenceParams, TextDocumentIdentifier,
+        TextDocumentPositionParams, Url, WorkDoneProgressParams,
+        notification::DidChangeWatchedFiles, request::References,
+    };
+    use ts_query_ls::{
+        ParameterConstraint, Predicate, PredicateParameter, PredicateParameterArity,
+        PredicateParameterType,
     };
 
     use crate::{
         Options,
-        test_helpers::helpers::{COMPLEX_FILE, TEST_URI, TestService, initialize_server},
+        test_helpers::helpers::{
+            COMPLEX_FILE, CPP_HIGHLIGHTS_WS_URI, TEST_URI, TestService, initialize_server,
+        },
     };
 
     type Coordinate = ((u32, u32), (u32, u32));
@@ -94,7 +386,7 @@ mod test {
 function: (identifier) @function)",
         Position { line: 0, character: 1 },
         true,
-        &[]
+        &[((0, 1), (0, 16))]
     )]
     #[case(
         &COMPLEX_FILE,
@@ -163,4 +455,181 @@ function: (identifier) @function)",
         };
         assert_eq!(expected, refs);
     }
+
+    fn workspace_uri(path: &str) -> Url {
+        Url::from_file_path(format!(
+            "{}/tests/fixtures/test_workspace/queries/{path}",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    fn kind_eq_options() -> Options {
+        Options {
+            valid_predicates: BTreeMap::from([(
+                String::from("kind-eq"),
+                Predicate {
+                    description: String::from("Checks the kind of a node"),
+                    parameters: vec![
+                        PredicateParameter {
+                            description: None,
+                            type_: PredicateParameterType::Capture,
+                            arity: PredicateParameterArity::Required,
+                            constraint: ParameterConstraint::None,
+                        },
+                        PredicateParameter {
+                            description: None,
+                            type_: PredicateParameterType::String,
+                            arity: PredicateParameterArity::Variadic,
+                            constraint: ParameterConstraint::NamedNode,
+                        },
+                    ],
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(
+        "(function_definition\n  name: (identifier)) @x\n\n(import_statement !name)",
+        Position::new(0, 3),
+        Options::default(),
+        &[
+            ("cpp/folds.scm", ((0, 1), (0, 20))),
+            ("cpp/test.scm", ((0, 1), (0, 20))),
+        ]
+    )]
+    #[case(
+        "(function_definition\n  name: (identifier)) @x\n\n(import_statement !name)",
+        Position::new(1, 3),
+        Options::default(),
+        &[("cpp/test.scm", ((1, 2), (1, 6))), ("cpp/test.scm", ((3, 19), (3, 23)))]
+    )]
+    #[case(
+        "[\"return\" (MISSING \"return\")] @keyword",
+        Position::new(0, 4),
+        Options::default(),
+        &[("cpp/test.scm", ((0, 2), (0, 8))), ("cpp/test.scm", ((0, 20), (0, 26)))]
+    )]
+    #[case(
+        "; inherits: other\n\n(definition) @x",
+        Position::new(2, 2),
+        Options::default(),
+        &[
+            ("cpp/test.scm", ((2, 1), (2, 11))),
+            ("other/test.scm", ((2, 1), (2, 11))),
+        ]
+    )]
+    #[case(
+        "((function_definition) @x\n  (#kind-eq? @x \"import_statement\" function_definition))",
+        Position::new(1, 20),
+        kind_eq_options(),
+        &[
+            ("cpp/folds.scm", ((2, 1), (2, 17))),
+            ("cpp/test.scm", ((1, 17), (1, 33))),
+        ]
+    )]
+    #[case(
+        "((function_definition) @x\n  (#kind-eq? @x \"import_statement\" function_definition))",
+        Position::new(0, 3),
+        kind_eq_options(),
+        &[
+            ("cpp/folds.scm", ((0, 1), (0, 20))),
+            ("cpp/test.scm", ((0, 2), (0, 21))),
+            ("cpp/test.scm", ((1, 35), (1, 54))),
+        ]
+    )]
+    #[tokio::test(flavor = "current_thread")]
+    async fn language_references(
+        #[case] input: &str,
+        #[case] position: Position,
+        #[case] options: Options,
+        #[case] locations: &[(&str, Coordinate)],
+    ) {
+        // Arrange
+        let mut service =
+            initialize_server(&[(CPP_HIGHLIGHTS_WS_URI.clone(), input)], &options).await;
+
+        // Act
+        let refs = service
+            .request::<References>(ReferenceParams {
+                context: ReferenceContext {
+                    include_declaration: true,
+                },
+                partial_result_params: PartialResultParams::default(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                text_document_position: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: CPP_HIGHLIGHTS_WS_URI.clone(),
+                    },
+                    position,
+                },
+            })
+            .await;
+
+        // Assert
+        assert_eq!(
+            refs,
+            Some(
+                locations
+                    .iter()
+                    .map(|(path, r)| Location {
+                        uri: workspace_uri(path),
+                        range: Range::new(Position::new(r.0.0, r.0.1), Position::new(r.1.0, r.1.1)),
+                    })
+                    .collect()
+            )
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn language_references_index_updates() {
+        // Arrange
+        let mut service = initialize_server(
+            &[(CPP_HIGHLIGHTS_WS_URI.clone(), "(function_definition) @x")],
+            &Options::default(),
+        )
+        .await;
+        let params = ReferenceParams {
+            context: ReferenceContext {
+                include_declaration: true,
+            },
+            partial_result_params: PartialResultParams::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: CPP_HIGHLIGHTS_WS_URI.clone(),
+                },
+                position: Position::new(0, 3),
+            },
+        };
+        let initial = service.request::<References>(params.clone()).await;
+
+        // Act
+        service
+            .notify::<DidChangeWatchedFiles>(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent {
+                    uri: workspace_uri("cpp/folds.scm"),
+                    typ: FileChangeType::DELETED,
+                }],
+            })
+            .await;
+        let deleted = service.request::<References>(params).await;
+
+        // Assert
+        let location = |path, start, end| Location {
+            uri: workspace_uri(path),
+            range: Range::new(Position::new(0, start), Position::new(0, end)),
+        };
+        assert_eq!(
+            initial,
+            Some(vec![
+                location("cpp/folds.scm", 1, 20),
+                location("cpp/test.scm", 1, 20),
+            ])
+        );
+        assert_eq!(deleted, Some(vec![location("cpp/test.scm", 1, 20)]));
+    }
 }
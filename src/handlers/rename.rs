@@ -1,39 +1,60 @@
+use std::collections::BTreeMap;
+
 use tower_lsp::{
     jsonrpc::{self, Result},
     lsp_types::{
-        DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier, RenameParams,
-        TextDocumentEdit, TextEdit, WorkspaceEdit,
+        DocumentChanges, MessageType, OneOf, OptionalVersionedTextDocumentIdentifier,
+        ProgressToken, RenameParams, ShowMessageParams, TextDocumentEdit, TextEdit, Url,
+        WorkspaceEdit, notification::ShowMessage,
     },
 };
 use tracing::warn;
 use tree_sitter::QueryCursor;
+use ts_query_ls::Options;
 
 use crate::{
-    Backend, LspClient,
+    Backend, LspClient, SymbolInfo,
     util::{
-        CAPTURES_QUERY, NodeUtil, PosUtil, TextProviderRope, get_current_capture_node,
-        get_references,
+        CAPTURES_QUERY, NodeUtil, PosUtil, TextProviderRope, escape_string_content,
+        get_current_capture_node, get_language_name, get_references,
     },
 };
 
-use super::diagnostic::IDENTIFIER_REGEX;
+use super::{
+    diagnostic::IDENTIFIER_REGEX,
+    references::{LanguageSymbol, get_language_references, get_language_symbol},
+};
 
-pub fn rename<C: LspClient>(
+pub async fn rename<C: LspClient>(
     backend: &Backend<C>,
     params: &RenameParams,
 ) -> Result<Option<WorkspaceEdit>> {
     let uri = &params.text_document_position.text_document.uri;
+    let options = backend.options.read().await;
     let Some(doc) = backend.document_map.get(uri) else {
         warn!("No document found for URI: {uri} when handling rename");
         return Ok(None);
     };
     let rope = &doc.rope;
     let tree = &doc.tree;
-    let Some(current_node) = get_current_capture_node(
-        tree.root_node(),
-        params.text_document_position.position.to_ts_point(rope),
-    ) else {
-        return Ok(None);
+    let point = params.text_document_position.position.to_ts_point(rope);
+    let Some(current_node) = get_current_capture_node(tree.root_node(), point) else {
+        let Some(symbol) = get_language_symbol(tree.root_node(), point, rope, &options) else {
+            return Ok(None);
+        };
+        let Some(language_name) = doc.language_name.clone() else {
+            return Ok(None);
+        };
+        drop(doc);
+        return rename_language_symbol(
+            backend,
+            &language_name,
+            &symbol,
+            &params.new_name,
+            &options,
+            params.work_done_progress_params.work_done_token.clone(),
+        )
+        .await;
     };
     let query = &CAPTURES_QUERY;
     let mut cursor = QueryCursor::new();
@@ -80,20 +101,123 @@ pub fn rename<C: LspClient>(
     }))
 }
 
+/// Rename a node kind or field in every query of its language. The new name is allowed to be
+/// missing from the grammar (e.g. when the queries are updated ahead of the grammar), but a warning
+/// is shown in that case.
+async fn rename_language_symbol<C: LspClient>(
+    backend: &Backend<C>,
+    language_name: &str,
+    symbol: &LanguageSymbol,
+    new_name: &str,
+    options: &Options,
+    work_done_token: Option<ProgressToken>,
+) -> Result<Option<WorkspaceEdit>> {
+    let (new_symbol, new_text) = match symbol {
+        LanguageSymbol::Node(SymbolInfo { named: false, .. }) => (
+            LanguageSymbol::Node(SymbolInfo {
+                label: new_name.to_owned(),
+                named: false,
+            }),
+            escape_string_content(new_name),
+        ),
+        LanguageSymbol::Node(SymbolInfo { named: true, .. }) | LanguageSymbol::Field(_) => {
+            if !IDENTIFIER_REGEX.is_match(new_name) {
+                return Err(jsonrpc::Error::invalid_params(
+                    "New name is not a valid identifier",
+                ));
+            }
+            let new_symbol = if let LanguageSymbol::Field(_) = symbol {
+                LanguageSymbol::Field(new_name.to_owned())
+            } else {
+                LanguageSymbol::Node(SymbolInfo {
+                    label: new_name.to_owned(),
+                    named: true,
+                })
+            };
+            (new_symbol, new_name.to_owned())
+        }
+    };
+
+    let language_data = backend
+        .language_map
+        .get(language_name)
+        .map(|language_data| language_data.clone());
+    if let Some(language_data) = language_data {
+        let description = match &new_symbol {
+            LanguageSymbol::Node(sym) if !language_data.symbols_set.contains(sym) => {
+                Some(format!("Node {sym}"))
+            }
+            LanguageSymbol::Field(field) if !language_data.fields_set.contains(field) => {
+                Some(format!("Field \"{field}\""))
+            }
+            _ => None,
+        };
+        if let Some(description) = description {
+            backend
+                .client
+                .send_notification::<ShowMessage>(ShowMessageParams {
+                    typ: MessageType::WARNING,
+                    message: format!(
+                        "{description} does not exist in the grammar for language \"{language_name}\""
+                    ),
+                })
+                .await;
+        }
+    }
+
+    let mut edits = BTreeMap::<Url, Vec<_>>::new();
+    // Inherited modules may belong to other languages, whose nodes and fields are unrelated
+    for location in
+        get_language_references(backend, language_name, symbol, options, work_done_token)
+            .await
+            .into_iter()
+            .filter(|location| {
+                get_language_name(&location.uri, options).as_deref() == Some(language_name)
+            })
+    {
+        edits
+            .entry(location.uri)
+            .or_default()
+            .push(OneOf::Left(TextEdit {
+                range: location.range,
+                new_text: new_text.clone(),
+            }));
+    }
+
+    Ok(Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Edits(
+            edits
+                .into_iter()
+                .map(|(uri, edits)| TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier {
+                        version: backend.document_map.get(&uri).and_then(|doc| doc.version),
+                        uri,
+                    },
+                    edits,
+                })
+                .collect(),
+        )),
+        changes: None,
+        change_annotations: None,
+    }))
+}
+
 #[cfg(test)]
 mod test {
     use pretty_assertions::assert_eq;
     use rstest::rstest;
     use tower_lsp::lsp_types::{
-        DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier, Position, RenameParams,
-        TextDocumentEdit, TextDocumentIdentifier, TextDocumentPositionParams,
-        WorkDoneProgressParams, WorkspaceEdit, request::Rename,
+        DocumentChanges, MessageType, OneOf, OptionalVersionedTextDocumentIdentifier, Position,
+        RenameParams, ShowMessageParams, TextDocumentEdit, TextDocumentIdentifier,
+        TextDocumentPositionParams, Url, WorkDoneProgressParams, WorkspaceEdit,
+        notification::ShowMessage, request::Rename,
     };
 
     use crate::{
         Options,
         test_helpers::helpers::{
-            COMPLEX_FILE, SIMPLE_FILE, TEST_URI, TestEdit, TestService, initialize_server,
+            COMPLEX_FILE, CPP_HIGHLIGHTS_WS_URI, MockRequest, QUERY_TEST_URI, SIMPLE_FILE,
+            TEST_URI, TestEdit, TestService, initialize_server,
         },
     };
 
@@ -170,4 +294,170 @@ mod test {
         };
         assert_eq!(expected, rename_edits);
     }
+
+    fn rename_params(uri: &Url, position: Position, new_name: &str) -> RenameParams {
+        RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position,
+            },
+            new_name: new_name.to_string(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn server_rename_node_kind_across_workspace() {
+        // Arrange
+        let folds_uri = Url::from_file_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/test_workspace/queries/cpp/folds.scm"
+        ))
+        .unwrap();
+        // Modules of other languages are not renamed, even when they are inherited
+        let other_uri = Url::from_file_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/test_workspace/queries/other/test.scm"
+        ))
+        .unwrap();
+        let mut service = initialize_server(
+            &[
+                (
+                    CPP_HIGHLIGHTS_WS_URI.clone(),
+                    "; inherits: other\n(function_definition) @x\n\n(MISSING function_definition)",
+                ),
+                (other_uri, "(function_definition)"),
+            ],
+            &Options::default(),
+        )
+        .await;
+
+        // Act
+        let rename_edits = service
+            .request::<Rename>(rename_params(
+                &CPP_HIGHLIGHTS_WS_URI,
+                Position::new(3, 12),
+                "function_item",
+            ))
+            .await;
+
+        // Assert
+        assert_eq!(
+            rename_edits,
+            Some(WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Edits(vec![
+                    TextDocumentEdit {
+                        text_document: OptionalVersionedTextDocumentIdentifier {
+                            uri: folds_uri,
+                            version: None,
+                        },
+                        edits: vec![OneOf::Left(
+                            (&TestEdit::new("function_item", (0, 1), (0, 20))).into()
+                        )],
+                    },
+                    TextDocumentEdit {
+                        text_document: OptionalVersionedTextDocumentIdentifier {
+                            uri: CPP_HIGHLIGHTS_WS_URI.clone(),
+                            version: Some(0),
+                        },
+                        edits: vec![
+                            OneOf::Left((&TestEdit::new("function_item", (1, 1), (1, 20))).into()),
+                            OneOf::Left((&TestEdit::new("function_item", (3, 9), (3, 28))).into()),
+                        ],
+                    },
+                ])),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(Position::new(0, 3), "grouping", &[TestEdit::new("grouping", (0, 1), (0, 11))], None)]
+    #[case(
+        Position::new(0, 3),
+        "not_a_node",
+        &[TestEdit::new("not_a_node", (0, 1), (0, 11))],
+        Some("Node (not_a_node) does not exist in the grammar for language \"query\"")
+    )]
+    #[case(
+        Position::new(0, 13),
+        "supertype",
+        &[
+            TestEdit::new("supertype", (0, 12), (0, 16)),
+            TestEdit::new("supertype", (0, 32), (0, 36)),
+        ],
+        None
+    )]
+    #[case(
+        Position::new(0, 13),
+        "label",
+        &[
+            TestEdit::new("label", (0, 12), (0, 16)),
+            TestEdit::new("label", (0, 32), (0, 36)),
+        ],
+        Some("Field \"label\" does not exist in the grammar for language \"query\"")
+    )]
+    #[case(
+        Position::new(0, 39),
+        "@",
+        &[TestEdit::new("@", (0, 39), (0, 40))],
+        None
+    )]
+    #[case(
+        Position::new(0, 39),
+        "\"",
+        &[TestEdit::new("\\\"", (0, 39), (0, 40))],
+        Some("Node \"\"\" does not exist in the grammar for language \"query\"")
+    )]
+    #[tokio::test(flavor = "current_thread")]
+    async fn server_rename_language_symbol(
+        #[case] position: Position,
+        #[case] new_name: &str,
+        #[case] edits: &[TestEdit],
+        #[case] warning: Option<&str>,
+    ) {
+        // Arrange
+        let mut service = initialize_server(
+            &[(
+                QUERY_TEST_URI.clone(),
+                r#"(named_node name: (identifier) !name) "(""#,
+            )],
+            &Options::default(),
+        )
+        .await;
+
+        // Act
+        let rename_edits = service
+            .request::<Rename>(rename_params(&QUERY_TEST_URI, position, new_name))
+            .await;
+
+        // Assert
+        assert_eq!(
+            rename_edits,
+            Some(WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier {
+                        uri: QUERY_TEST_URI.clone(),
+                        version: Some(0),
+                    },
+                    edits: edits.iter().map(|e| OneOf::Left(e.into())).collect(),
+                }])),
+                ..Default::default()
+            })
+        );
+        let warning_notification = warning.map(|message| {
+            MockRequest::from_notification::<ShowMessage>(ShowMessageParams {
+                typ: MessageType::WARNING,
+                message: String::from(message),
+            })
+        });
+        let notifications = service.inner().client.get_notifications();
+        assert_eq!(
+            notifications
+                .into_iter()
+                .filter(|notification| notification.method == "window/showMessage")
+                .collect::<Vec<_>>(),
+            warning_notification.into_iter().collect::<Vec<_>>()
+        );
+    }
 }
@@ -1,47 +1,164 @@
 use std::sync::LazyLock;
 
+use ropey::Rope;
 use tower_lsp::lsp_types::{
-    Range, SemanticToken, SemanticTokens, SemanticTokensParams, SemanticTokensRangeParams,
-    SemanticTokensRangeResult, SemanticTokensResult, Url,
+    Range, SemanticToken, SemanticTokens, SemanticTokensDelta, SemanticTokensDeltaParams,
+    SemanticTokensEdit, SemanticTokensFullDeltaResult, SemanticTokensParams,
+    SemanticTokensRangeParams, SemanticTokensRangeResult, SemanticTokensResult, Url,
 };
 use tracing::warn;
-use tree_sitter::{Query, QueryCursor, StreamingIterator};
+use tree_sitter::{Node, Query, QueryCursor, StreamingIterator};
 
 use crate::{
     Backend, LspClient, QUERY_LANGUAGE, SymbolInfo,
-    util::{FORMAT_IGNORE_REGEX, INHERITS_REGEX, NodeUtil, PosUtil, TextProviderRope},
+    util::{
+        FORMAT_IGNORE_REGEX, FORMAT_IGNORE_REGION_REGEX, INHERITS_REGEX, NodeUtil, PosUtil,
+        TextProviderRope, uri_to_basename,
+    },
 };
 
 static SEM_TOK_QUERY: LazyLock<Query> = LazyLock::new(|| {
     Query::new(
         &QUERY_LANGUAGE,
-        r"(named_node (identifier) @ident)
-(missing_node (identifier) @ident)
-(comment) @comment
-",
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/queries/query/semantic_tokens.scm"
+        )),
     )
     .unwrap()
 });
 
-pub fn semantic_tokens_full<C: LspClient>(
+// Indices into the token type legend advertised in the server capabilities
+const INTERFACE: u32 = 0;
+const VARIABLE: u32 = 1;
+const NAMESPACE: u32 = 2;
+const KEYWORD: u32 = 3;
+const TYPE: u32 = 4;
+const PROPERTY: u32 = 5;
+const FUNCTION: u32 = 6;
+const MACRO: u32 = 7;
+const STRING: u32 = 8;
+const ESCAPE_SEQUENCE: u32 = 9;
+
+// Bits of the token modifier legend advertised in the server capabilities
+const DEFAULT_LIBRARY: u32 = 1;
+const DEPRECATED: u32 = 1 << 1;
+const INVALID: u32 = 1 << 2;
+const SUPERTYPE: u32 = 1 << 3;
+
+/// A semantic token with an absolute position.
+struct AbsoluteToken {
+    line: u32,
+    start: u32,
+    length: u32,
+    token_type: u32,
+    modifiers: u32,
+}
+
+pub async fn semantic_tokens_full<C: LspClient>(
     backend: &Backend<C>,
     params: &SemanticTokensParams,
 ) -> Option<SemanticTokensResult> {
-    get_semantic_tokens(backend, &params.text_document.uri, None).map(Into::into)
+    let uri = &params.text_document.uri;
+    let data = get_semantic_tokens(backend, uri, None).await?;
+    Some(cache_semantic_tokens(backend, uri, data).into())
+}
+
+pub async fn semantic_tokens_full_delta<C: LspClient>(
+    backend: &Backend<C>,
+    params: &SemanticTokensDeltaParams,
+) -> Option<SemanticTokensFullDeltaResult> {
+    let uri = &params.text_document.uri;
+    let data = get_semantic_tokens(backend, uri, None).await?;
+    let previous = backend
+        .semantic_tokens_map
+        .get(uri)
+        .filter(|previous| previous.result_id.as_ref() == Some(&params.previous_result_id))
+        .map(|previous| previous.data.clone());
+    let tokens = cache_semantic_tokens(backend, uri, data);
+
+    Some(match previous {
+        Some(previous) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+            edits: diff_semantic_tokens(&previous, &tokens.data),
+            result_id: tokens.result_id,
+        }),
+        None => SemanticTokensFullDeltaResult::Tokens(tokens),
+    })
 }
 
-pub fn semantic_tokens_range<C: LspClient>(
+pub async fn semantic_tokens_range<C: LspClient>(
     backend: &Backend<C>,
     params: &SemanticTokensRangeParams,
 ) -> Option<SemanticTokensRangeResult> {
-    get_semantic_tokens(backend, &params.text_document.uri, Some(params.range)).map(Into::into)
+    let data = get_semantic_tokens(backend, &params.text_document.uri, Some(params.range)).await?;
+    Some(
+        SemanticTokens {
+            result_id: None,
+            data,
+        }
+        .into(),
+    )
+}
+
+/// Remember the tokens sent for a document, so that later requests can be answered with a delta.
+fn cache_semantic_tokens<C: LspClient>(
+    backend: &Backend<C>,
+    uri: &Url,
+    data: Vec<SemanticToken>,
+) -> SemanticTokens {
+    let result_id = backend
+        .semantic_tokens_map
+        .get(uri)
+        .and_then(|previous| previous.result_id.as_ref()?.parse::<u64>().ok())
+        .map_or(0, |id| id + 1);
+    let tokens = SemanticTokens {
+        result_id: Some(result_id.to_string()),
+        data,
+    };
+    backend
+        .semantic_tokens_map
+        .insert(uri.clone(), tokens.clone());
+    tokens
+}
+
+/// Compute the edit turning the old tokens into the new ones, by trimming their common prefix and
+/// suffix. Edit offsets count integers, of which each token has five.
+fn diff_semantic_tokens(old: &[SemanticToken], new: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    if prefix == old.len() && prefix == new.len() {
+        return Vec::new();
+    }
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    vec![SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: ((old.len() - prefix - suffix) * 5) as u32,
+        data: Some(new[prefix..new.len() - suffix].to_vec()),
+    }]
+}
+
+fn node_token(node: Node, rope: &Rope, token_type: u32, modifiers: u32) -> Option<AbsoluteToken> {
+    let range = node.lsp_range(rope);
+    // Multiline tokens are not supported by all clients
+    (range.start.line == range.end.line).then(|| AbsoluteToken {
+        line: range.start.line,
+        start: range.start.character,
+        length: range.end.character - range.start.character,
+        token_type,
+        modifiers,
+    })
 }
 
-fn get_semantic_tokens<C: LspClient>(
+async fn get_semantic_tokens<C: LspClient>(
     backend: &Backend<C>,
     uri: &Url,
     range: Option<Range>,
-) -> Option<SemanticTokens> {
+) -> Option<Vec<SemanticToken>> {
+    let options = backend.options.read().await;
     let Some(doc) = backend.document_map.get(uri) else {
         warn!("No document found for URI: {uri} when retrieving semantic tokens");
         return None;
@@ -53,7 +170,7 @@ fn get_semantic_tokens<C: LspClient>(
         .language_name
         .as_ref()
         .and_then(|name| backend.language_map.get(name));
-    let supertypes = language_data.as_ref().map(|ld| &ld.supertype_map);
+    let query_name = uri_to_basename(uri);
     let query = &SEM_TOK_QUERY;
     let mut cursor = QueryCursor::new();
     if let Some(range) = range {
@@ -61,145 +178,258 @@ fn get_semantic_tokens<C: LspClient>(
     }
     let provider = TextProviderRope(rope);
     let mut matches = cursor.matches(query, tree.root_node(), &provider);
-    let mut prev_line = 0;
-    let mut prev_col = 0;
 
     while let Some(match_) = matches.next() {
         for cap in match_.captures {
             let capture_name = SEM_TOK_QUERY.capture_names()[cap.index as usize];
-            let node = &cap.node;
+            let node = cap.node;
             let node_text = node.text(rope);
-            let start_row = node.start_position().row as u32;
-            let start_col = node.start_position().column as u32;
-            let delta_line = start_row - prev_line;
-            let delta_start = if start_row - prev_line == 0 {
-                start_col - prev_col
-            } else {
-                start_col
-            };
             match capture_name {
-                // Highlight supertypes and ERROR nodes
-                "ident" => {
-                    // Identifiers are always ASCII characters, meaning they count for the same
-                    // number of code points in each encoding (and we can count them by bytes)
-                    let length = node.byte_range().len() as u32;
-                    if node_text == "ERROR" {
-                        tokens.push(SemanticToken {
-                            delta_line,
-                            delta_start,
-                            length,
-                            token_type: 1,
-                            token_modifiers_bitset: 1,
-                        });
-                        prev_line = start_row;
-                        prev_col = start_col;
-                    } else if supertypes.is_some_and(|supertypes| {
-                        supertypes.contains_key(&SymbolInfo {
-                            label: node_text,
-                            named: true,
+                "node" => {
+                    let sym = SymbolInfo {
+                        label: node_text,
+                        named: true,
+                    };
+                    if sym.label == "ERROR" {
+                        tokens.extend(node_token(node, rope, VARIABLE, DEFAULT_LIBRARY));
+                    } else if language_data
+                        .as_ref()
+                        .is_some_and(|ld| ld.supertype_map.contains_key(&sym))
+                    {
+                        tokens.extend(node_token(node, rope, INTERFACE, SUPERTYPE));
+                    } else if language_data
+                        .as_ref()
+                        .is_some_and(|ld| !ld.symbols_set.contains(&sym))
+                    {
+                        tokens.extend(node_token(node, rope, TYPE, INVALID));
+                    } else {
+                        tokens.extend(node_token(node, rope, TYPE, 0));
+                    }
+                }
+                "field" => {
+                    let modifiers = if language_data
+                        .as_ref()
+                        .is_some_and(|ld| !ld.fields_set.contains(&node_text))
+                    {
+                        INVALID
+                    } else {
+                        0
+                    };
+                    tokens.extend(node_token(node, rope, PROPERTY, modifiers));
+                }
+                "string" => {
+                    let sym = SymbolInfo {
+                        label: node
+                            .named_child(0)
+                            .map(|content| content.text(rope))
+                            .unwrap_or_default(),
+                        named: false,
+                    };
+                    let modifiers = if language_data
+                        .as_ref()
+                        .is_some_and(|ld| !ld.symbols_set.contains(&sym))
+                    {
+                        INVALID
+                    } else {
+                        0
+                    };
+                    // Split the string around its escape sequences, which have their own tokens
+                    let range = node.lsp_range(rope);
+                    if range.start.line != range.end.line {
+                        continue;
+                    }
+                    let mut start = range.start.character;
+                    let escapes = node
+                        .named_child(0)
+                        .map(|content| {
+                            let mut cursor = content.walk();
+                            content
+                                .named_children(&mut cursor)
+                                .map(|escape| escape.lsp_range(rope))
+                                .collect::<Vec<_>>()
                         })
+                        .unwrap_or_default();
+                    for end in escapes
+                        .iter()
+                        .map(|escape| (escape.start.character, escape.end.character))
+                        .chain([(range.end.character, range.end.character)])
+                    {
+                        if end.0 > start {
+                            tokens.push(AbsoluteToken {
+                                line: range.start.line,
+                                start,
+                                length: end.0 - start,
+                                token_type: STRING,
+                                modifiers,
+                            });
+                        }
+                        start = end.1;
+                    }
+                }
+                "escape" => tokens.extend(node_token(node, rope, ESCAPE_SEQUENCE, 0)),
+                "capture" => {
+                    let modifiers = if query_name.as_ref().is_some_and(|query_name| {
+                        options
+                            .resolve_capture(query_name, &node_text[1..])
+                            .is_some()
                     }) {
-                        tokens.push(SemanticToken {
-                            delta_line,
-                            delta_start,
-                            length,
-                            token_type: 0,
-                            token_modifiers_bitset: 0,
-                        });
-                        prev_line = start_row;
-                        prev_col = start_col;
+                        DEFAULT_LIBRARY
+                    } else {
+                        0
+                    };
+                    tokens.extend(node_token(node, rope, VARIABLE, modifiers));
+                }
+                "predicate" => {
+                    let mut cursor = node.walk();
+                    let mut names = node.children_by_field_name("name", &mut cursor);
+                    let (Some(prefix), Some(name), Some(predicate_type)) =
+                        (names.next(), names.next(), node.child_by_field_name("type"))
+                    else {
+                        continue;
+                    };
+                    let start = prefix.lsp_range(rope).start;
+                    let end = predicate_type.lsp_range(rope).end;
+                    if start.line != end.line {
+                        continue;
                     }
+                    let is_directive = predicate_type.text(rope) == "!";
+                    let valid_predicates = if is_directive {
+                        &options.valid_directives
+                    } else {
+                        &options.valid_predicates
+                    };
+                    let modifiers = valid_predicates.get(&name.text(rope)).map_or(0, |pred| {
+                        if pred.deprecated {
+                            DEFAULT_LIBRARY | DEPRECATED
+                        } else {
+                            DEFAULT_LIBRARY
+                        }
+                    });
+                    tokens.push(AbsoluteToken {
+                        line: start.line,
+                        start: start.character,
+                        length: end.character - start.character,
+                        token_type: if is_directive { MACRO } else { FUNCTION },
+                        modifiers,
+                    });
                 }
                 // Highlight special comments (inherits, format ignore)
                 "comment" => {
+                    let start = node.lsp_range(rope).start;
                     if let Some(fmt_ignore) = FORMAT_IGNORE_REGEX
                         .captures(&node_text)
+                        .or_else(|| FORMAT_IGNORE_REGION_REGEX.captures(&node_text))
                         .and_then(|c| c.get(1))
                     {
-                        const FMT_IGNORE_LEN: u32 = 13;
-                        let offset = fmt_ignore.start() as u32;
-                        tokens.push(SemanticToken {
-                            delta_line,
-                            delta_start: delta_start + offset,
-                            length: FMT_IGNORE_LEN,
-                            token_type: 3,
-                            token_modifiers_bitset: 0,
+                        tokens.push(AbsoluteToken {
+                            line: start.line,
+                            start: start.character + fmt_ignore.start() as u32,
+                            length: fmt_ignore.len() as u32,
+                            token_type: KEYWORD,
+                            modifiers: 0,
                         });
-                        prev_line = start_row;
-                        prev_col = start_col + offset;
                         continue;
                     }
-                    if start_row != 0 {
+                    if start.line != 0 {
                         continue;
                     }
                     let Some(mods) = INHERITS_REGEX.captures(&node_text).and_then(|c| c.get(1))
                     else {
                         continue;
                     };
-                    let offset = mods.start() as u32;
-                    let mods = mods.as_str().split(',');
 
-                    // Add a token for `inherits:`
+                    // Add a token for `inherits:`. We assert that the comment prefix and modules
+                    // are valid ASCII characters, so we can index them by byte count.
                     const INHERITS_LEN: u32 = 9;
-                    let mut delta_start = delta_start + offset - INHERITS_LEN - 1;
-                    tokens.push(SemanticToken {
-                        delta_line: 0,
-                        delta_start,
+                    let mut start_col = start.character + mods.start() as u32;
+                    tokens.push(AbsoluteToken {
+                        line: 0,
+                        start: start_col - INHERITS_LEN - 1,
                         length: INHERITS_LEN,
-                        token_type: 3,
-                        token_modifiers_bitset: 0,
+                        token_type: KEYWORD,
+                        modifiers: 0,
                     });
-                    delta_start = INHERITS_LEN + 1;
-
-                    let mut start_col = start_col + offset;
-                    let mut delta_line = delta_line;
-                    for module in mods {
-                        // We assert that modules are valid ASCII characters, so we can index them
-                        // by byte count.
+                    for module in mods.as_str().split(',') {
                         let length = module.len() as u32;
-                        tokens.push(SemanticToken {
-                            delta_line,
-                            delta_start,
+                        tokens.push(AbsoluteToken {
+                            line: 0,
+                            start: start_col,
                             length,
-                            token_type: 2,
-                            token_modifiers_bitset: 0,
+                            token_type: NAMESPACE,
+                            modifiers: 0,
                         });
                         start_col += length + 1;
-                        delta_start = length + 1;
-                        delta_line = 0;
                     }
-                    prev_line = start_row;
-                    prev_col = start_col;
                 }
                 _ => {}
             }
         }
     }
 
-    Some(SemanticTokens {
-        result_id: None,
-        data: tokens,
-    })
+    tokens.sort_unstable_by_key(|token| (token.line, token.start));
+    let mut prev_line = 0;
+    let mut prev_col = 0;
+    Some(
+        tokens
+            .into_iter()
+            .map(|token| {
+                let delta_line = token.line - prev_line;
+                let delta_start = if delta_line == 0 {
+                    token.start - prev_col
+                } else {
+                    token.start
+                };
+                prev_line = token.line;
+                prev_col = token.start;
+                SemanticToken {
+                    delta_line,
+                    delta_start,
+                    length: token.length,
+                    token_type: token.token_type,
+                    token_modifiers_bitset: token.modifiers,
+                }
+            })
+            .collect(),
+    )
 }
 
 #[cfg(test)]
 mod test {
+    use std::collections::{BTreeMap, HashMap};
+
     use pretty_assertions::assert_eq;
+    use rstest::rstest;
     use tower_lsp::lsp_types::{
-        PartialResultParams, SemanticToken, SemanticTokens, SemanticTokensParams,
-        SemanticTokensResult, TextDocumentIdentifier, WorkDoneProgressParams,
-        request::SemanticTokensFullRequest,
+        DidChangeTextDocumentParams, PartialResultParams, Position, Range, SemanticToken,
+        SemanticTokens, SemanticTokensDelta, SemanticTokensDeltaParams, SemanticTokensEdit,
+        SemanticTokensFullDeltaResult, SemanticTokensParams, SemanticTokensResult,
+        TextDocumentContentChangeEvent, TextDocumentIdentifier, VersionedTextDocumentIdentifier,
+        WorkDoneProgressParams,
+        notification::DidChangeTextDocument,
+        request::{SemanticTokensFullDeltaRequest, SemanticTokensFullRequest},
     };
+    use ts_query_ls::{Options, Predicate};
 
-    use crate::{
-        Options,
-        test_helpers::helpers::{QUERY_TEST_URI, TestService, initialize_server},
-    };
+    use crate::test_helpers::helpers::{QUERY_TEST_URI, TestService, initialize_server};
 
-    #[tokio::test(flavor = "current_thread")]
-    async fn semantic_tokens_full() {
-        // Arrange
-        let source = r"; inherits: c,cuda
+    const fn token(
+        delta_line: u32,
+        delta_start: u32,
+        length: u32,
+        token_type: u32,
+        token_modifiers_bitset: u32,
+    ) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type,
+            token_modifiers_bitset,
+        }
+    }
+
+    #[rstest]
+    #[case(r"; inherits: c,cuda
 
 (ERROR) @error (definition) @node (definition) @node
 
@@ -212,9 +442,80 @@ mod test {
 
 ;;;format-ignore
 (foo)
-        ";
-        let mut service =
-            initialize_server(&[(QUERY_TEST_URI.clone(), source)], &Options::default()).await;
+        ", vec![
+        // ; inherits:
+        token(0, 2, 9, 3, 0),
+        token(0, 10, 1, 2, 0),
+        token(0, 2, 4, 2, 0),
+        // ERROR, Supertypes, captures
+        token(2, 1, 5, 1, 1),
+        token(0, 7, 6, 1, 0),
+        token(0, 8, 10, 0, 8),
+        token(0, 12, 5, 1, 0),
+        token(0, 7, 10, 0, 8),
+        token(0, 12, 5, 1, 0),
+        token(2, 1, 10, 0, 8),
+        token(0, 12, 5, 1, 0),
+        token(3, 9, 5, 1, 1),
+        token(0, 7, 13, 1, 0),
+        token(2, 9, 10, 0, 8),
+        token(0, 12, 17, 1, 0),
+        // format-ignore
+        token(2, 3, 13, 3, 0),
+        // Invalid node
+        token(1, 1, 3, 4, 4),
+    ])]
+    #[case(r#"(named_node
+  name: (identifier) @variable.builtin
+  !bad_field
+  "\"\n" @punctuation
+  (#eq? @variable.builtin "self")
+  (#match? @variable.builtin "self")
+  (#set! priority 105))"#, vec![
+        // Nodes and fields
+        token(0, 1, 10, 4, 0),
+        token(1, 2, 4, 5, 0),
+        token(0, 7, 10, 4, 0),
+        token(0, 12, 17, 1, 1),
+        token(1, 3, 9, 5, 4),
+        // Strings and escapes
+        token(1, 2, 1, 8, 4),
+        token(0, 1, 2, 9, 0),
+        token(0, 2, 2, 9, 0),
+        token(0, 2, 1, 8, 4),
+        token(0, 2, 12, 1, 0),
+        // Predicates and directives
+        token(1, 3, 4, 6, 1),
+        token(0, 5, 17, 1, 1),
+        token(1, 3, 7, 6, 3),
+        token(0, 8, 17, 1, 1),
+        token(1, 3, 5, 7, 0),
+    ])]
+    #[case("; format-ignore-start\n;; format-ignore-end", vec![
+        token(0, 2, 19, 3, 0),
+        token(1, 3, 17, 3, 0),
+    ])]
+    #[tokio::test(flavor = "current_thread")]
+    async fn semantic_tokens_full(#[case] source: &str, #[case] expected: Vec<SemanticToken>) {
+        // Arrange
+        let options = Options {
+            valid_captures: HashMap::from([(
+                String::from("test"),
+                BTreeMap::from([(String::from("variable.builtin"), String::new())]),
+            )]),
+            valid_predicates: BTreeMap::from([
+                (String::from("eq"), Predicate::default()),
+                (
+                    String::from("match"),
+                    Predicate {
+                        deprecated: true,
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let mut service = initialize_server(&[(QUERY_TEST_URI.clone(), source)], &options).await;
 
         // Act
         let actual_tokens = service
@@ -230,84 +531,103 @@ mod test {
             .await;
 
         // Assert
-        let expected_tokens = Some(SemanticTokensResult::Tokens(SemanticTokens {
-            result_id: None,
-            data: vec![
-                // ; inherits:
-                SemanticToken {
-                    delta_line: 0,
-                    delta_start: 2,
-                    length: 9,
-                    token_type: 3,
-                    token_modifiers_bitset: 0,
-                },
-                SemanticToken {
-                    delta_line: 0,
-                    delta_start: 10,
-                    length: 1,
-                    token_type: 2,
-                    token_modifiers_bitset: 0,
-                },
-                SemanticToken {
-                    delta_line: 0,
-                    delta_start: 2,
-                    length: 4,
-                    token_type: 2,
-                    token_modifiers_bitset: 0,
-                },
-                // ERROR, Supertypes
-                SemanticToken {
-                    delta_line: 2,
-                    delta_start: 1,
-                    length: 5,
-                    token_type: 1,
-                    token_modifiers_bitset: 1,
-                },
-                SemanticToken {
-                    delta_line: 0,
-                    delta_start: 15,
-                    length: 10,
-                    token_type: 0,
-                    token_modifiers_bitset: 0,
-                },
-                SemanticToken {
-                    delta_line: 0,
-                    delta_start: 19,
-                    length: 10,
-                    token_type: 0,
-                    token_modifiers_bitset: 0,
-                },
-                SemanticToken {
-                    delta_line: 2,
-                    delta_start: 1,
-                    length: 10,
-                    token_type: 0,
-                    token_modifiers_bitset: 0,
-                },
-                SemanticToken {
-                    delta_line: 3,
-                    delta_start: 9,
-                    length: 5,
-                    token_type: 1,
-                    token_modifiers_bitset: 1,
-                },
-                SemanticToken {
-                    delta_line: 2,
-                    delta_start: 9,
-                    length: 10,
-                    token_type: 0,
-                    token_modifiers_bitset: 0,
+        assert_eq!(
+            actual_tokens,
+            Some(SemanticTokensResult::Tokens(SemanticTokens {
+                result_id: Some(String::from("0")),
+                data: expected,
+            }))
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn semantic_tokens_full_delta() {
+        // Arrange
+        let mut service = initialize_server(
+            &[(
+                QUERY_TEST_URI.clone(),
+                "(definition) @node\n\n(named_node) @node",
+            )],
+            &Options::default(),
+        )
+        .await;
+        let delta_params = |previous_result_id: &str| SemanticTokensDeltaParams {
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            text_document: TextDocumentIdentifier {
+                uri: QUERY_TEST_URI.clone(),
+            },
+            previous_result_id: String::from(previous_result_id),
+        };
+        service
+            .request::<SemanticTokensFullRequest>(SemanticTokensParams {
+                partial_result_params: PartialResultParams::default(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                text_document: TextDocumentIdentifier {
+                    uri: QUERY_TEST_URI.clone(),
                 },
-                // format-ignore
-                SemanticToken {
-                    delta_line: 2,
-                    delta_start: 3,
-                    length: 13,
-                    token_type: 3,
-                    token_modifiers_bitset: 0,
+            })
+            .await;
+        service
+            .notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: QUERY_TEST_URI.clone(),
+                    version: 1,
                 },
-            ],
-        }));
-        assert_eq!(expected_tokens, actual_tokens);
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: Some(Range::new(Position::new(1, 0), Position::new(1, 0))),
+                    range_length: None,
+                    text: String::from("(grouping)\n"),
+                }],
+            })
+            .await;
+
+        // Act
+        let delta = service
+            .request::<SemanticTokensFullDeltaRequest>(delta_params("0"))
+            .await;
+        let unchanged = service
+            .request::<SemanticTokensFullDeltaRequest>(delta_params("1"))
+            .await;
+        let stale = service
+            .request::<SemanticTokensFullDeltaRequest>(delta_params("0"))
+            .await;
+
+        // Assert
+        assert_eq!(
+            delta,
+            Some(SemanticTokensFullDeltaResult::TokensDelta(
+                SemanticTokensDelta {
+                    result_id: Some(String::from("1")),
+                    edits: vec![SemanticTokensEdit {
+                        start: 10,
+                        delete_count: 0,
+                        data: Some(vec![token(1, 1, 8, 4, 0)]),
+                    }],
+                }
+            ))
+        );
+        assert_eq!(
+            unchanged,
+            Some(SemanticTokensFullDeltaResult::TokensDelta(
+                SemanticTokensDelta {
+                    result_id: Some(String::from("2")),
+                    edits: vec![],
+                }
+            ))
+        );
+        assert_eq!(
+            stale,
+            Some(SemanticTokensFullDeltaResult::Tokens(SemanticTokens {
+                result_id: Some(String::from("3")),
+                data: vec![
+                    token(0, 1, 10, 0, 8),
+                    token(0, 12, 5, 1, 0),
+                    token(1, 1, 8, 4, 0),
+                    token(2, 1, 10, 4, 0),
+                    token(0, 12, 5, 1, 0),
+                ],
+            }))
+        );
     }
 }
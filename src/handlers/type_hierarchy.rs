@@ -0,0 +1,295 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{
+    SymbolKind, TypeHierarchyItem, TypeHierarchyPrepareParams, TypeHierarchySubtypesParams,
+    TypeHierarchySupertypesParams,
+};
+use tracing::warn;
+
+use crate::{
+    Backend, LanguageData, LspClient, SymbolInfo,
+    util::{NodeUtil, PosUtil},
+};
+
+/// Data preserved between the prepare request and subsequent supertype/subtype requests.
+#[derive(Serialize, Deserialize)]
+struct TypeHierarchyData {
+    language: String,
+    label: String,
+    named: bool,
+}
+
+pub fn prepare_type_hierarchy<C: LspClient>(
+    backend: &Backend<C>,
+    params: &TypeHierarchyPrepareParams,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    let Some(doc) = backend.document_map.get(uri) else {
+        warn!("No document found for URI: {uri} when handling prepare_type_hierarchy");
+        return None;
+    };
+    let rope = &doc.rope;
+    let point = params
+        .text_document_position_params
+        .position
+        .to_ts_point(rope);
+    let node = doc
+        .tree
+        .root_node()
+        .named_descendant_for_point_range(point, point)?;
+    if node.kind() != "identifier"
+        || !node
+            .parent()
+            .is_some_and(|parent| matches!(parent.kind(), "named_node" | "missing_node"))
+    {
+        return None;
+    }
+    let language_data = doc
+        .language_name
+        .as_ref()
+        .and_then(|name| backend.language_map.get(name))?;
+    let sym = SymbolInfo {
+        label: node.text(rope),
+        named: true,
+    };
+    if !language_data.symbols_set.contains(&sym) {
+        return None;
+    }
+
+    let range = node.lsp_range(rope);
+    Some(vec![type_hierarchy_item(
+        &language_data,
+        &sym,
+        TypeHierarchyItem {
+            name: String::new(),
+            kind: SymbolKind::CLASS,
+            tags: None,
+            detail: None,
+            uri: uri.clone(),
+            range,
+            selection_range: range,
+            data: None,
+        },
+    )])
+}
+
+pub fn supertypes<C: LspClient>(
+    backend: &Backend<C>,
+    params: &TypeHierarchySupertypesParams,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let (language_data, sym) = get_item_symbol(backend, &params.item)?;
+    let supertypes = language_data
+        .supertype_map
+        .iter()
+        .filter(|(_, subtypes)| subtypes.contains(&sym))
+        .map(|(supertype, _)| supertype)
+        .collect::<BTreeSet<_>>();
+    Some(
+        supertypes
+            .into_iter()
+            .map(|supertype| type_hierarchy_item(&language_data, supertype, params.item.clone()))
+            .collect(),
+    )
+}
+
+pub fn subtypes<C: LspClient>(
+    backend: &Backend<C>,
+    params: &TypeHierarchySubtypesParams,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let (language_data, sym) = get_item_symbol(backend, &params.item)?;
+    let subtypes = language_data.supertype_map.get(&sym)?;
+    Some(
+        subtypes
+            .iter()
+            .map(|subtype| type_hierarchy_item(&language_data, subtype, params.item.clone()))
+            .collect(),
+    )
+}
+
+/// Retrieve the language data and symbol that a type hierarchy item was created for.
+fn get_item_symbol<C: LspClient>(
+    backend: &Backend<C>,
+    item: &TypeHierarchyItem,
+) -> Option<(std::sync::Arc<LanguageData>, SymbolInfo)> {
+    let data = serde_json::from_value::<TypeHierarchyData>(item.data.clone()?).ok()?;
+    let language_data = backend.language_map.get(&data.language)?.clone();
+    Some((
+        language_data,
+        SymbolInfo {
+            label: data.label,
+            named: data.named,
+        },
+    ))
+}
+
+/// Create a type hierarchy item for the given symbol. Node kinds have no location of their own, so
+/// the item keeps the location of the item it was derived from.
+fn type_hierarchy_item(
+    language_data: &LanguageData,
+    sym: &SymbolInfo,
+    item: TypeHierarchyItem,
+) -> TypeHierarchyItem {
+    let kind = if language_data.supertype_map.contains_key(sym) {
+        SymbolKind::INTERFACE
+    } else if sym.named {
+        SymbolKind::CLASS
+    } else {
+        SymbolKind::STRING
+    };
+    TypeHierarchyItem {
+        name: sym.to_string(),
+        kind,
+        detail: Some(language_data.name.clone()),
+        data: serde_json::to_value(TypeHierarchyData {
+            language: language_data.name.clone(),
+            label: sym.label.clone(),
+            named: sym.named,
+        })
+        .ok(),
+        ..item
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+    use tower_lsp::lsp_types::{
+        PartialResultParams, Position, Range, SymbolKind, TextDocumentIdentifier,
+        TextDocumentPositionParams, TypeHierarchyItem, TypeHierarchyPrepareParams,
+        TypeHierarchySubtypesParams, TypeHierarchySupertypesParams, WorkDoneProgressParams,
+        request::{TypeHierarchyPrepare, TypeHierarchySubtypes, TypeHierarchySupertypes},
+    };
+
+    use crate::{
+        Options,
+        test_helpers::helpers::{QUERY_TEST_URI, TestService, initialize_server},
+    };
+
+    fn item(name: &str, kind: SymbolKind, label: &str, range: Range) -> TypeHierarchyItem {
+        TypeHierarchyItem {
+            name: String::from(name),
+            kind,
+            tags: None,
+            detail: Some(String::from("query")),
+            uri: QUERY_TEST_URI.clone(),
+            range,
+            selection_range: range,
+            data: Some(json!({ "language": "query", "label": label, "named": true })),
+        }
+    }
+
+    #[rstest]
+    #[case("(definition/named_node)", Position::new(0, 3), Some(vec![item(
+        "(definition)",
+        SymbolKind::INTERFACE,
+        "definition",
+        Range::new(Position::new(0, 1), Position::new(0, 11)),
+    )]))]
+    #[case("(definition/named_node)", Position::new(0, 15), Some(vec![item(
+        "(named_node)",
+        SymbolKind::CLASS,
+        "named_node",
+        Range::new(Position::new(0, 12), Position::new(0, 22)),
+    )]))]
+    #[case("(MISSING grouping)", Position::new(0, 11), Some(vec![item(
+        "(grouping)",
+        SymbolKind::CLASS,
+        "grouping",
+        Range::new(Position::new(0, 9), Position::new(0, 17)),
+    )]))]
+    #[case("(named_node name: (identifier))", Position::new(0, 13), None)]
+    #[case("(named_node) @capture", Position::new(0, 15), None)]
+    #[case("(not_a_node)", Position::new(0, 3), None)]
+    #[tokio::test(flavor = "current_thread")]
+    async fn prepare_type_hierarchy(
+        #[case] source: &str,
+        #[case] position: Position,
+        #[case] expected: Option<Vec<TypeHierarchyItem>>,
+    ) {
+        // Arrange
+        let mut service =
+            initialize_server(&[(QUERY_TEST_URI.clone(), source)], &Options::default()).await;
+
+        // Act
+        let items = service
+            .request::<TypeHierarchyPrepare>(TypeHierarchyPrepareParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier {
+                        uri: QUERY_TEST_URI.clone(),
+                    },
+                    position,
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await;
+
+        // Assert
+        assert_eq!(items, expected);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn type_hierarchy_supertypes_and_subtypes() {
+        // Arrange
+        let range = Range::new(Position::new(0, 1), Position::new(0, 11));
+        let mut service = initialize_server(
+            &[(QUERY_TEST_URI.clone(), "(named_node)")],
+            &Options::default(),
+        )
+        .await;
+
+        // Act
+        let supertypes = service
+            .request::<TypeHierarchySupertypes>(TypeHierarchySupertypesParams {
+                item: item("(named_node)", SymbolKind::CLASS, "named_node", range),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await;
+        let subtypes = service
+            .request::<TypeHierarchySubtypes>(TypeHierarchySubtypesParams {
+                item: item("(definition)", SymbolKind::INTERFACE, "definition", range),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await;
+        let leaf_subtypes = service
+            .request::<TypeHierarchySubtypes>(TypeHierarchySubtypesParams {
+                item: item("(named_node)", SymbolKind::CLASS, "named_node", range),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await;
+
+        // Assert
+        assert_eq!(
+            supertypes,
+            Some(vec![item(
+                "(definition)",
+                SymbolKind::INTERFACE,
+                "definition",
+                range
+            )])
+        );
+        assert_eq!(
+            subtypes,
+            Some(
+                [
+                    "anonymous_node",
+                    "field_definition",
+                    "grouping",
+                    "list",
+                    "missing_node",
+                    "named_node",
+                    "predicate",
+                ]
+                .into_iter()
+                .map(|label| item(&format!("({label})"), SymbolKind::CLASS, label, range))
+                .collect()
+            )
+        );
+        assert_eq!(leaf_subtypes, None);
+    }
+}
@@ -1,33 +1,122 @@
-use std::fs;
+use std::{
+    fs,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
+use dashmap::{DashMap, mapref::one::Ref};
 use ropey::Rope;
+use tokio::sync::OnceCell;
 use tower_lsp::{
     jsonrpc::Result,
     lsp_types::{
-        Location, ProgressParams, ProgressParamsValue, SymbolInformation, SymbolKind, Url,
-        WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressEnd, WorkDoneProgressReport,
-        WorkspaceSymbolParams, notification::Progress,
+        DocumentSymbol, Location, NumberOrString, Position, Range, SymbolInformation, SymbolKind,
+        Url, WorkspaceSymbolParams,
     },
 };
-use tree_sitter::{QueryCursor, StreamingIterator};
+use tree_sitter::Tree;
 
 use crate::{
     Backend, LspClient,
     util::{
-        CAPTURES_QUERY, NodeUtil, TextProviderRope, get_scm_files, get_work_done_token,
-        is_subsequence, parse,
+        FileProgress, fuzzy_score, get_inherited_modules, get_scm_files, get_work_done_token, parse,
     },
 };
 
+use super::document_symbol::get_document_symbols;
+
+/// An in-memory index of the symbols in each query file of the workspace. It is built once, on the
+/// first workspace symbol request, and then kept up to date as documents change.
+#[derive(Default)]
+pub struct SymbolIndex {
+    files: DashMap<Url, IndexedFile>,
+    /// Set when the index starts being built, after which document changes are applied to it.
+    started: AtomicBool,
+    built: OnceCell<()>,
+}
+
+/// The contents of an indexed file, kept so that workspace-wide requests need not parse it again.
+pub struct IndexedFile {
+    pub rope: Rope,
+    pub tree: Tree,
+    symbols: Vec<SymbolInformation>,
+}
+
+/// Build the symbol index, unless it is already built. Concurrent callers wait for the same build to
+/// finish instead of starting their own.
+pub async fn ensure_index<C: LspClient>(
+    backend: &Backend<C>,
+    work_done_token: Option<NumberOrString>,
+) {
+    backend
+        .symbol_index
+        .built
+        .get_or_init(|| async {
+            let token = get_work_done_token(backend, work_done_token).await;
+            build_index(backend, token).await;
+        })
+        .await;
+}
+
+/// Get the indexed contents of a workspace file.
+pub fn get_indexed_file<'a, C: LspClient>(
+    backend: &'a Backend<C>,
+    uri: &Url,
+) -> Option<Ref<'a, Url, IndexedFile>> {
+    backend.symbol_index.files.get(uri)
+}
+
+/// Get the URIs of every indexed workspace file.
+pub fn get_indexed_uris<C: LspClient>(backend: &Backend<C>) -> Vec<Url> {
+    backend
+        .symbol_index
+        .files
+        .iter()
+        .map(|entry| entry.key().clone())
+        .collect()
+}
+
 pub async fn symbol<C: LspClient>(
     backend: &Backend<C>,
     params: WorkspaceSymbolParams,
 ) -> Result<Option<Vec<SymbolInformation>>> {
-    let mut symbols = Vec::new();
+    ensure_index(backend, params.work_done_progress_params.work_done_token).await;
+
     let query = params.query;
-    let token =
-        get_work_done_token(backend, params.work_done_progress_params.work_done_token).await;
+    let mut symbols = backend
+        .symbol_index
+        .files
+        .iter()
+        .flat_map(|entry| {
+            entry
+                .value()
+                .symbols
+                .iter()
+                .filter_map(|symbol| {
+                    fuzzy_score(&query, &symbol.name).map(|score| (score, symbol.clone()))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    symbols.sort_by(|(score_a, symbol_a), (score_b, symbol_b)| {
+        score_a
+            .cmp(score_b)
+            .then_with(|| symbol_a.location.uri.cmp(&symbol_b.location.uri))
+            .then_with(|| {
+                symbol_a
+                    .location
+                    .range
+                    .start
+                    .cmp(&symbol_b.location.range.start)
+            })
+    });
 
+    Ok(Some(
+        symbols.into_iter().map(|(_, symbol)| symbol).collect(),
+    ))
+}
+
+async fn build_index<C: LspClient>(backend: &Backend<C>, token: Option<NumberOrString>) {
     let dirs = backend
         .workspace_paths
         .read()
@@ -36,203 +125,275 @@ pub async fn symbol<C: LspClient>(
         .unwrap_or_default();
 
     let files = get_scm_files(&dirs).collect::<Vec<_>>();
-    let file_count = files.len();
-    let file_count_div_100 = file_count as f64 * 0.01;
-    let mut num_processed_files = 0;
-    let mut progress_percent = 0;
+    backend.symbol_index.started.store(true, Ordering::Release);
+    let mut progress = FileProgress::begin(
+        backend,
+        token,
+        "Indexing workspace symbols",
+        "indexed",
+        files.len(),
+    )
+    .await;
+
+    for path in files {
+        if let Ok(uri) = Url::from_file_path(&path) {
+            // Open documents may have unsaved changes, so prefer them to the file on disk
+            let indexed = match backend
+                .document_map
+                .get(&uri)
+                .filter(|doc| doc.version.is_some())
+            {
+                Some(doc) => Some(index_document(&uri, &doc.rope, &doc.tree)),
+                None => index_file(&uri, &path),
+            };
+            // Documents which changed since the build started are already up to date
+            if let Some(indexed) = indexed {
+                backend.symbol_index.files.entry(uri).or_insert(indexed);
+            }
+        }
+        progress.advance().await;
+    }
+    progress.end().await;
+}
 
-    if let Some(token) = token.clone() {
+/// Update the index entry of a workspace document whose contents have changed. Does nothing if the
+/// index has not started being built yet, since it will pick up the change when it is.
+pub fn update_index<C: LspClient>(backend: &Backend<C>, uri: &Url, rope: &Rope, tree: &Tree) {
+    if backend.symbol_index.started.load(Ordering::Acquire) && is_workspace_file(backend, uri) {
         backend
-            .client
-            .send_notification::<Progress>(ProgressParams {
-                token,
-                value: ProgressParamsValue::WorkDone(WorkDoneProgress::Begin(
-                    WorkDoneProgressBegin {
-                        title: "Finding workspace symbols".into(),
-                        percentage: Some(0),
-                        message: Some(format!("0/{file_count} files indexed")),
-                        cancellable: Some(false),
-                    },
-                )),
-            })
-            .await;
+            .symbol_index
+            .files
+            .insert(uri.clone(), index_document(uri, rope, tree));
     }
+}
 
-    for path in files {
-        let container_name = path
-            .file_name()
-            .and_then(|f| f.to_str())
-            .map(ToString::to_string);
-        if let (Ok(content), Ok(uri)) = (fs::read_to_string(&path), Url::from_file_path(path)) {
-            let rope = &Rope::from_str(&content);
-            let tree = parse(rope, None);
-
-            let provider = &TextProviderRope(rope);
-            let mut cursor = QueryCursor::new();
-            let mut matches = cursor.matches(&CAPTURES_QUERY, tree.root_node(), provider);
-
-            while let Some(match_) = matches.next() {
-                for capture in match_.captures {
-                    let capture_node = capture.node;
-                    let node_text = capture_node.text(rope);
-
-                    if is_subsequence(&query, &node_text) {
-                        symbols.push(SymbolInformation {
-                            name: node_text,
-                            kind: SymbolKind::VARIABLE,
-                            location: Location::new(uri.clone(), capture_node.lsp_range(rope)),
-                            container_name: container_name.clone(),
-                            tags: None,
-                            #[allow(deprecated)]
-                            deprecated: None,
-                        });
-                    }
-                }
-            }
+/// Update the index entry of a workspace file from its contents on disk, removing it if the file no
+/// longer exists.
+pub fn update_index_from_disk<C: LspClient>(backend: &Backend<C>, uri: &Url) {
+    if !backend.symbol_index.started.load(Ordering::Acquire) || !is_workspace_file(backend, uri) {
+        return;
+    }
+    match uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| index_file(uri, &path))
+    {
+        Some(indexed) => {
+            backend.symbol_index.files.insert(uri.clone(), indexed);
         }
-        num_processed_files += 1;
-        let percentage = (num_processed_files as f64 / file_count_div_100).floor() as u32;
-        if percentage > progress_percent + 4 {
-            progress_percent = percentage;
-            if let Some(token) = token.clone() {
-                backend
-                    .client
-                    .send_notification::<Progress>(ProgressParams {
-                        token,
-                        value: ProgressParamsValue::WorkDone(WorkDoneProgress::Report(
-                            WorkDoneProgressReport {
-                                percentage: Some(progress_percent),
-                                message: Some(format!(
-                                    "{num_processed_files}/{file_count} files indexed"
-                                )),
-                                cancellable: Some(false),
-                            },
-                        )),
-                    })
-                    .await;
-            }
+        None => {
+            backend.symbol_index.files.remove(uri);
         }
     }
+}
 
-    if let Some(token) = token.clone() {
-        backend
-            .client
-            .send_notification::<Progress>(ProgressParams {
-                token,
-                value: ProgressParamsValue::WorkDone(WorkDoneProgress::End(WorkDoneProgressEnd {
-                    message: Some(format!("{file_count}/{file_count} files indexed")),
-                })),
-            })
-            .await;
+/// Remove a deleted file from the index.
+pub fn remove_from_index<C: LspClient>(backend: &Backend<C>, uri: &Url) {
+    backend.symbol_index.files.remove(uri);
+}
+
+fn is_workspace_file<C: LspClient>(backend: &Backend<C>, uri: &Url) -> bool {
+    let Ok(path) = uri.to_file_path() else {
+        return false;
+    };
+    path.extension().is_some_and(|ext| ext == "scm")
+        && backend
+            .workspace_paths
+            .read()
+            .is_ok_and(|dirs| dirs.iter().any(|dir| path.starts_with(dir)))
+}
+
+fn index_file(uri: &Url, path: &Path) -> Option<IndexedFile> {
+    let content = fs::read_to_string(path).ok()?;
+    let rope = Rope::from_str(&content);
+    let tree = parse(&rope, None);
+    Some(index_document(uri, &rope, &tree))
+}
+
+fn index_document(uri: &Url, rope: &Rope, tree: &Tree) -> IndexedFile {
+    IndexedFile {
+        rope: rope.clone(),
+        tree: tree.clone(),
+        symbols: get_symbols(uri, rope, tree),
     }
-    Ok(Some(symbols))
+}
+
+/// Collect the symbols of a document: its inherited modules, top-level patterns, captures,
+/// predicates, and directives.
+fn get_symbols(uri: &Url, rope: &Rope, tree: &Tree) -> Vec<SymbolInformation> {
+    let container_name = uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| path.file_name()?.to_str().map(ToString::to_string));
+    let new_symbol = |name: String, kind: SymbolKind, range: Range| SymbolInformation {
+        name,
+        kind,
+        location: Location::new(uri.clone(), range),
+        container_name: container_name.clone(),
+        tags: None,
+        #[allow(deprecated)]
+        deprecated: None,
+    };
+
+    let mut symbols = get_inherited_modules(rope, tree)
+        .into_iter()
+        .filter(|(_, _, module)| !module.is_empty())
+        .map(|(start, end, module)| {
+            new_symbol(
+                module,
+                SymbolKind::MODULE,
+                Range::new(Position::new(0, start), Position::new(0, end)),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    fn flatten(
+        document_symbols: Vec<DocumentSymbol>,
+        top_level: bool,
+        symbols: &mut Vec<SymbolInformation>,
+        new_symbol: &impl Fn(String, SymbolKind, Range) -> SymbolInformation,
+    ) {
+        for document_symbol in document_symbols {
+            if top_level
+                || document_symbol.kind == SymbolKind::VARIABLE
+                || document_symbol.kind == SymbolKind::FUNCTION
+            {
+                symbols.push(new_symbol(
+                    document_symbol.name,
+                    document_symbol.kind,
+                    document_symbol.range,
+                ));
+            }
+            if let Some(children) = document_symbol.children {
+                flatten(children, false, symbols, new_symbol);
+            }
+        }
+    }
+    flatten(
+        get_document_symbols(rope, tree),
+        true,
+        &mut symbols,
+        &new_symbol,
+    );
+
+    symbols
 }
 
 #[cfg(test)]
 mod test {
     use pretty_assertions::assert_eq;
+    use rstest::rstest;
     use tower_lsp::lsp_types::{
+        DidChangeTextDocumentParams, DidChangeWatchedFilesParams, FileChangeType, FileEvent,
         Location, NumberOrString, PartialResultParams, Position, ProgressParams,
-        ProgressParamsValue, Range, SymbolInformation, SymbolKind, Url, WorkDoneProgress,
-        WorkDoneProgressCreateParams, WorkDoneProgressEnd, WorkDoneProgressParams,
-        WorkDoneProgressReport, WorkspaceSymbolParams, WorkspaceSymbolResponse,
-        notification::Progress,
+        ProgressParamsValue, Range, SymbolInformation, SymbolKind, TextDocumentContentChangeEvent,
+        Url, VersionedTextDocumentIdentifier, WorkDoneProgress, WorkDoneProgressCreateParams,
+        WorkDoneProgressEnd, WorkDoneProgressParams, WorkDoneProgressReport, WorkspaceSymbolParams,
+        WorkspaceSymbolResponse,
+        notification::{DidChangeTextDocument, DidChangeWatchedFiles, Progress},
         request::{WorkDoneProgressCreate, WorkspaceSymbolRequest},
     };
 
     use crate::{
         Options,
-        test_helpers::helpers::{MockRequest, TestService, initialize_server},
+        test_helpers::helpers::{
+            CPP_HIGHLIGHTS_WS_URI, MockRequest, TestService, initialize_server,
+        },
     };
 
+    fn make_range(start_line: u32, start_col: u32, end_line: u32, end_col: u32) -> Range {
+        Range::new(
+            Position::new(start_line, start_col),
+            Position::new(end_line, end_col),
+        )
+    }
+
+    fn make_symbol(
+        name: &str,
+        kind: SymbolKind,
+        uri: &Url,
+        range: Range,
+        container_name: &str,
+    ) -> SymbolInformation {
+        #[allow(deprecated)]
+        SymbolInformation {
+            name: String::from(name),
+            kind,
+            location: Location {
+                uri: uri.clone(),
+                range,
+            },
+            deprecated: None,
+            tags: None,
+            container_name: Some(String::from(container_name)),
+        }
+    }
+
+    fn workspace_uri(path: &str) -> Url {
+        Url::from_file_path(format!(
+            "{}/tests/fixtures/test_workspace/queries/{path}",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    fn symbol_params(query: &str) -> WorkspaceSymbolParams {
+        WorkspaceSymbolParams {
+            query: String::from(query),
+            partial_result_params: PartialResultParams::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        }
+    }
+
     #[tokio::test(flavor = "current_thread")]
     async fn workspace_symbol() {
         // Arrange
         let mut service = initialize_server(&[], &Options::default()).await;
-        fn make_range(start_line: u32, start_col: u32, end_line: u32, end_col: u32) -> Range {
-            Range::new(
-                Position::new(start_line, start_col),
-                Position::new(end_line, end_col),
-            )
-        }
 
         // Act
         let actual_tokens = service
-            .request::<WorkspaceSymbolRequest>(WorkspaceSymbolParams {
-                query: String::new(),
-                partial_result_params: PartialResultParams::default(),
-                work_done_progress_params: WorkDoneProgressParams::default(),
-            })
+            .request::<WorkspaceSymbolRequest>(symbol_params("@"))
             .await;
 
         // Assert
-        let cpp_folds_uri = Url::from_file_path(concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/tests/fixtures/test_workspace/queries/cpp/folds.scm"
-        ))
-        .unwrap();
-        let other_highlights_uri = Url::from_file_path(concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/tests/fixtures/test_workspace/queries/other/highlights.scm"
-        ))
-        .unwrap();
-        #[allow(deprecated)]
+        let cpp_folds_uri = workspace_uri("cpp/folds.scm");
+        let other_highlights_uri = workspace_uri("other/highlights.scm");
         let expected_tokens = Some(WorkspaceSymbolResponse::Flat(vec![
-            SymbolInformation {
-                name: String::from("@fold.region"),
-                kind: SymbolKind::VARIABLE,
-                location: Location {
-                    uri: cpp_folds_uri.clone(),
-                    range: make_range(0, 22, 0, 34),
-                },
-                deprecated: None,
-                tags: None,
-                container_name: Some(String::from("folds.scm")),
-            },
-            SymbolInformation {
-                name: String::from("@fold.imports"),
-                kind: SymbolKind::VARIABLE,
-                location: Location {
-                    uri: cpp_folds_uri,
-                    range: make_range(2, 20, 2, 33),
-                },
-                deprecated: None,
-                tags: None,
-                container_name: Some(String::from("folds.scm")),
-            },
-            SymbolInformation {
-                name: String::from("@variable"),
-                kind: SymbolKind::VARIABLE,
-                location: Location {
-                    uri: other_highlights_uri.clone(),
-                    range: make_range(5, 2, 5, 11),
-                },
-                deprecated: None,
-                tags: None,
-                container_name: Some(String::from("highlights.scm")),
-            },
-            SymbolInformation {
-                name: String::from("@function"),
-                kind: SymbolKind::VARIABLE,
-                location: Location {
-                    uri: other_highlights_uri.clone(),
-                    range: make_range(7, 11, 7, 20),
-                },
-                deprecated: None,
-                tags: None,
-                container_name: Some(String::from("highlights.scm")),
-            },
-            SymbolInformation {
-                name: String::from("@constant"),
-                kind: SymbolKind::VARIABLE,
-                location: Location {
-                    uri: other_highlights_uri,
-                    range: make_range(13, 2, 13, 11),
-                },
-                deprecated: None,
-                tags: None,
-                container_name: Some(String::from("highlights.scm")),
-            },
+            make_symbol(
+                "@variable",
+                SymbolKind::VARIABLE,
+                &other_highlights_uri,
+                make_range(5, 2, 5, 11),
+                "highlights.scm",
+            ),
+            make_symbol(
+                "@function",
+                SymbolKind::VARIABLE,
+                &other_highlights_uri,
+                make_range(7, 11, 7, 20),
+                "highlights.scm",
+            ),
+            make_symbol(
+                "@constant",
+                SymbolKind::VARIABLE,
+                &other_highlights_uri,
+                make_range(13, 2, 13, 11),
+                "highlights.scm",
+            ),
+            make_symbol(
+                "@fold.region",
+                SymbolKind::VARIABLE,
+                &cpp_folds_uri,
+                make_range(0, 22, 0, 34),
+                "folds.scm",
+            ),
+            make_symbol(
+                "@fold.imports",
+                SymbolKind::VARIABLE,
+                &cpp_folds_uri,
+                make_range(2, 20, 2, 33),
+                "folds.scm",
+            ),
         ]));
         assert_eq!(expected_tokens, actual_tokens);
         assert_eq!(
@@ -261,5 +422,160 @@ mod test {
                 }))
             })
         ));
+
+        // The index is only built once
+        service
+            .request::<WorkspaceSymbolRequest>(symbol_params("@"))
+            .await;
+        assert_eq!(service.inner().client.get_requests().len(), 1);
+    }
+
+    #[rstest]
+    #[case("foo", vec![
+        make_symbol(
+            "foo",
+            SymbolKind::MODULE,
+            &workspace_uri("other/highlights.scm"),
+            make_range(0, 12, 0, 15),
+            "highlights.scm",
+        ),
+        make_symbol(
+            "@fold.imports",
+            SymbolKind::VARIABLE,
+            &workspace_uri("cpp/folds.scm"),
+            make_range(2, 20, 2, 33),
+            "folds.scm",
+        ),
+        make_symbol(
+            "@fold.region",
+            SymbolKind::VARIABLE,
+            &workspace_uri("cpp/folds.scm"),
+            make_range(0, 22, 0, 34),
+            "folds.scm",
+        ),
+        make_symbol(
+            "(function_definition)",
+            SymbolKind::CLASS,
+            &workspace_uri("cpp/folds.scm"),
+            make_range(0, 0, 0, 34),
+            "folds.scm",
+        ),
+    ])]
+    #[case("function", vec![
+        make_symbol(
+            "@function",
+            SymbolKind::VARIABLE,
+            &workspace_uri("other/highlights.scm"),
+            make_range(7, 11, 7, 20),
+            "highlights.scm",
+        ),
+        make_symbol(
+            "(function)",
+            SymbolKind::CLASS,
+            &workspace_uri("other/highlights.scm"),
+            make_range(7, 0, 7, 20),
+            "highlights.scm",
+        ),
+        make_symbol(
+            "(function_definition)",
+            SymbolKind::CLASS,
+            &workspace_uri("cpp/folds.scm"),
+            make_range(0, 0, 0, 34),
+            "folds.scm",
+        ),
+        make_symbol(
+            "[(identifier) (function)]",
+            SymbolKind::ENUM,
+            &workspace_uri("other/highlights.scm"),
+            make_range(2, 0, 5, 11),
+            "highlights.scm",
+        ),
+    ])]
+    #[case("fdef", vec![make_symbol(
+        "(function_definition)",
+        SymbolKind::CLASS,
+        &workspace_uri("cpp/folds.scm"),
+        make_range(0, 0, 0, 34),
+        "folds.scm",
+    )])]
+    #[tokio::test(flavor = "current_thread")]
+    async fn workspace_symbol_ranking(
+        #[case] query: &str,
+        #[case] expected: Vec<SymbolInformation>,
+    ) {
+        // Arrange
+        let mut service = initialize_server(&[], &Options::default()).await;
+
+        // Act
+        let actual = service
+            .request::<WorkspaceSymbolRequest>(symbol_params(query))
+            .await;
+
+        // Assert
+        assert_eq!(actual, Some(WorkspaceSymbolResponse::Flat(expected)));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn workspace_symbol_index_updates() {
+        // Arrange
+        let mut service = initialize_server(
+            &[(CPP_HIGHLIGHTS_WS_URI.clone(), "; test query\n\n(squid)")],
+            &Options::default(),
+        )
+        .await;
+        service
+            .request::<WorkspaceSymbolRequest>(symbol_params(""))
+            .await;
+
+        // Act
+        service
+            .notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: CPP_HIGHLIGHTS_WS_URI.clone(),
+                    version: 1,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: Some(make_range(2, 7, 2, 7)),
+                    range_length: None,
+                    text: String::from(" @squid.capture"),
+                }],
+            })
+            .await;
+        let changed = service
+            .request::<WorkspaceSymbolRequest>(symbol_params("squid"))
+            .await;
+        service
+            .notify::<DidChangeWatchedFiles>(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent {
+                    uri: workspace_uri("cpp/folds.scm"),
+                    typ: FileChangeType::DELETED,
+                }],
+            })
+            .await;
+        let deleted = service
+            .request::<WorkspaceSymbolRequest>(symbol_params("fold"))
+            .await;
+
+        // Assert
+        assert_eq!(
+            changed,
+            Some(WorkspaceSymbolResponse::Flat(vec![
+                make_symbol(
+                    "(squid)",
+                    SymbolKind::CLASS,
+                    &CPP_HIGHLIGHTS_WS_URI,
+                    make_range(2, 0, 2, 22),
+                    "test.scm",
+                ),
+                make_symbol(
+                    "@squid.capture",
+                    SymbolKind::VARIABLE,
+                    &CPP_HIGHLIGHTS_WS_URI,
+                    make_range(2, 8, 2, 22),
+                    "test.scm",
+                ),
+            ]))
+        );
+        assert_eq!(deleted, Some(WorkspaceSymbolResponse::Flat(vec![])));
     }
 }
@@ -29,6 +29,9 @@ struct PredicateAux {
     /// Whether this predicate supports a `any-` prefixed variant. Defaults to `false`.
     #[serde(default)]
     any: bool,
+    /// Whether this predicate is deprecated. Defaults to `false`.
+    #[serde(default)]
+    deprecated: bool,
 }
 
 const fn default_true() -> bool {
@@ -49,6 +52,7 @@ where
                 parameters: pred.parameters.clone(),
                 not: pred.not,
                 any: false,
+                deprecated: pred.deprecated,
             },
         );
         if pred.any {
@@ -63,6 +67,7 @@ where
                     parameters: pred.parameters,
                     not: pred.not,
                     any: false,
+                    deprecated: pred.deprecated,
                 },
             );
         }
@@ -78,6 +83,8 @@ where
                         "The inverse of `#{name}?`, which is defined as follows:\n\n{}",
                         pred.description
                     ),
+                    deprecated: pred.deprecated,
+                    keys: BTreeMap::new(),
                 };
                 vec![
                     (
@@ -85,6 +92,8 @@ where
                         Predicate {
                             description: pred.description,
                             parameters: pred.parameters,
+                            deprecated: pred.deprecated,
+                            keys: BTreeMap::new(),
                         },
                     ),
                     (pref_name, pref_pred),
@@ -95,6 +104,8 @@ where
                     Predicate {
                         description: pred.description,
                         parameters: pred.parameters,
+                        deprecated: pred.deprecated,
+                        keys: BTreeMap::new(),
                     },
                 )]
             };
@@ -124,12 +135,34 @@ pub struct Options {
     #[serde(default = "default_regexes", deserialize_with = "add_default_regexes")]
     pub language_retrieval_patterns: Vec<SerializableRegex>,
 
+    /// A list of directories to search for grammar sources, which are used to find the definitions
+    /// and structure of nodes and fields, and to complete only the children that a node permits.
+    /// Each directory should contain grammar repositories named `tree-sitter-<lang>` or `<lang>`,
+    /// holding a `grammar.js`, `src/grammar.json`, or `src/node-types.json` file. The grammar
+    /// repository containing a query file (e.g. `tree-sitter-<lang>/queries/highlights.scm`) is
+    /// always searched first.
+    ///
+    /// Supports environment variable expansion of the form `${VAR}`.
+    #[serde(default, deserialize_with = "deserialize_and_expand")]
+    pub grammar_directories: Vec<String>,
+
     /// A map from query file name to valid captures. Valid captures are represented as a map from
     /// capture name (sans `@`) to a short (markdown format) description. Note that captures
     /// prefixed with an underscore are always permissible.
     #[serde(default)]
     pub valid_captures: HashMap<String, BTreeMap<String, String>>,
 
+    /// A map from query file name to patterns matching additional valid capture names (sans `@`),
+    /// e.g. `^injection\\..*`.
+    #[serde(default)]
+    pub valid_capture_patterns: HashMap<String, Vec<SerializableRegex>>,
+
+    /// Whether a capture is also valid when any of its dot-separated prefixes is a valid capture,
+    /// e.g. `@function.method.call.rust` when `function.method.call` is declared. This mirrors how
+    /// Neovim and Helix fall back to less specific highlight groups.
+    #[serde(default)]
+    pub hierarchical_captures: bool,
+
     /// A map of predicate names (sans `#` and `?`) to parameter specifications.
     #[serde(default, deserialize_with = "add_prefixes")]
     #[cfg_attr(feature = "schema", schemars(schema_with = "prefixes_schema"))]
@@ -143,6 +176,10 @@ pub struct Options {
     #[serde(default)]
     pub diagnostic_options: DiagnosticOptions,
 
+    /// Options related to formatting
+    #[serde(default)]
+    pub format_options: FormatOptions,
+
     /// An inclusive range of ABI versions supported by your tool. The end of the range must be
     /// greater than or equal to the start.
     pub supported_abi_versions: Option<std::ops::RangeInclusive<u32>>,
@@ -155,14 +192,64 @@ impl Default for Options {
             valid_predicates: BTreeMap::default(),
             valid_directives: BTreeMap::default(),
             valid_captures: HashMap::default(),
+            valid_capture_patterns: HashMap::default(),
+            hierarchical_captures: false,
             diagnostic_options: DiagnosticOptions::default(),
+            format_options: FormatOptions::default(),
             parser_aliases: BTreeMap::default(),
             parser_install_directories: Vec::default(),
+            grammar_directories: Vec::default(),
             supported_abi_versions: Option::default(),
         }
     }
 }
 
+/// The declared capture or capture pattern that a capture name resolved to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CaptureResolution<'a> {
+    /// A capture declared in `valid_captures`, along with its description. This is a dot-separated
+    /// prefix of the capture name if it was resolved hierarchically.
+    Declared { name: &'a str, description: &'a str },
+    /// A pattern from `valid_capture_patterns`.
+    Pattern(&'a SerializableRegex),
+}
+
+impl Options {
+    /// Whether capture names are validated in query files of the given name.
+    #[must_use]
+    pub fn validates_captures(&self, query_name: &str) -> bool {
+        self.valid_captures.contains_key(query_name)
+            || self.valid_capture_patterns.contains_key(query_name)
+    }
+
+    /// Resolve a capture name (sans `@`) to the declared capture or capture pattern that makes it
+    /// valid in query files of the given name.
+    #[must_use]
+    pub fn resolve_capture(
+        &self,
+        query_name: &str,
+        capture: &str,
+    ) -> Option<CaptureResolution<'_>> {
+        if let Some(captures) = self.valid_captures.get(query_name) {
+            let mut name = capture;
+            loop {
+                if let Some((name, description)) = captures.get_key_value(name) {
+                    return Some(CaptureResolution::Declared { name, description });
+                }
+                match name.rsplit_once('.') {
+                    Some((prefix, _)) if self.hierarchical_captures => name = prefix,
+                    _ => break,
+                }
+            }
+        }
+        self.valid_capture_patterns
+            .get(query_name)?
+            .iter()
+            .find(|pattern| pattern.is_match(capture))
+            .map(CaptureResolution::Pattern)
+    }
+}
+
 #[cfg(feature = "schema")]
 fn prefixes_schema(gen_: &mut schemars::r#gen::SchemaGenerator) -> schemars::schema::Schema {
     let raw = <BTreeMap<String, PredicateAux>>::json_schema(gen_).into_object();
@@ -180,6 +267,10 @@ pub struct DiagnosticOptions {
     /// (default `true`)
     #[serde(default = "default_true")]
     pub warn_unused_underscore_captures: bool,
+    /// Which of several patterns matching the same node takes effect, used to warn about patterns
+    /// which are shadowed by an unconditional pattern
+    #[serde(default)]
+    pub pattern_precedence: PatternPrecedence,
 }
 
 impl Default for DiagnosticOptions {
@@ -187,10 +278,56 @@ impl Default for DiagnosticOptions {
         Self {
             string_argument_style: StringArgumentStyle::default(),
             warn_unused_underscore_captures: true,
+            pattern_precedence: PatternPrecedence::default(),
+        }
+    }
+}
+
+/// Options related to formatting
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct FormatOptions {
+    /// The number of spaces per indentation level. When unset, the tab size requested by the editor
+    /// is used, or `2` for the `format` and `check --format` commands
+    pub indent_width: Option<u32>,
+    /// The line width past which predicate arguments are wrapped (default `100`)
+    #[serde(default = "default_max_line_width")]
+    pub max_line_width: usize,
+    /// Whether top-level patterns are separated by a blank line
+    #[serde(default)]
+    pub blank_lines_between_patterns: BlankLinePolicy,
+    /// The number of predicate arguments past which each argument is put on its own line, except
+    /// for a leading capture. When unset, arguments are only wrapped past the maximum line width
+    pub max_inline_predicate_arguments: Option<usize>,
+}
+
+const fn default_max_line_width() -> usize {
+    100
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: Option::default(),
+            max_line_width: default_max_line_width(),
+            blank_lines_between_patterns: BlankLinePolicy::default(),
+            max_inline_predicate_arguments: Option::default(),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BlankLinePolicy {
+    /// Top-level patterns are always separated by a blank line (default)
+    #[default]
+    Always,
+    /// Top-level patterns are separated by a blank line only where they already were, collapsing
+    /// consecutive blank lines into one
+    Preserve,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone)]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(rename_all = "snake_case")]
@@ -204,6 +341,19 @@ pub enum StringArgumentStyle {
     PreferUnquoted,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PatternPrecedence {
+    /// Shadowed patterns are not reported (default)
+    #[default]
+    None,
+    /// The earliest matching pattern takes effect, as in Helix
+    FirstWins,
+    /// The latest matching pattern takes effect, as in Neovim
+    LastWins,
+}
+
 /// A type specification for a directive.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone)]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
@@ -212,6 +362,24 @@ pub struct Predicate {
     pub description: String,
     /// The list of valid parameter types.
     pub parameters: Vec<PredicateParameter>,
+    /// Whether this predicate is deprecated.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+    /// The known keys of a key/value directive such as `#set!`, whose first string parameter is a
+    /// key, optionally followed by a value. When given, unrecognized keys are reported.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, DirectiveKey>,
+}
+
+/// A known key of a key/value directive.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct DirectiveKey {
+    /// An optional description of this key (in Markdown format).
+    pub description: Option<String>,
+    /// An optional constraint for the value of this key.
+    #[serde(default)]
+    pub value: ParameterConstraint,
 }
 
 /// A parameter type reference.
@@ -249,6 +417,18 @@ pub enum ParameterConstraint {
     Integer,
     /// Enforce that the parameter one of the given values.
     Enum(Vec<String>),
+    /// Enforce that the parameter is a valid regular expression, as accepted by the Rust `regex`
+    /// crate.
+    Regex,
+    /// Enforce that the parameter is a valid Lua pattern.
+    LuaPattern,
+    /// Enforce that the parameter is a valid Vim regular expression.
+    VimRegex,
+    /// Enforce that the parameter is an integer within the given inclusive range.
+    IntegerRange(std::ops::RangeInclusive<i64>),
+    /// Enforce that the parameter is the name of a language whose parser can be found, or of a
+    /// parser alias.
+    Language,
 }
 
 impl Display for ParameterConstraint {
@@ -258,6 +438,13 @@ impl Display for ParameterConstraint {
             Self::NamedNode => write!(f, "named node"),
             Self::Integer => write!(f, "integer"),
             Self::Enum(values) => write!(f, "`{values:?}`"),
+            Self::Regex => write!(f, "regular expression"),
+            Self::LuaPattern => write!(f, "Lua pattern"),
+            Self::VimRegex => write!(f, "Vim regular expression"),
+            Self::IntegerRange(range) => {
+                write!(f, "integer from {} to {}", range.start(), range.end())
+            }
+            Self::Language => write!(f, "language name"),
         }
     }
 }
@@ -365,8 +552,12 @@ where
 {
     let mut raw = Vec::<SerializableRegex>::deserialize(deserializer)?;
 
-    // Always provide these defaults
-    raw.append(&mut default_regexes());
+    // Always provide these defaults, unless they are already given
+    for regex in default_regexes() {
+        if !raw.contains(&regex) {
+            raw.push(regex);
+        }
+    }
     Ok(raw)
 }
 
@@ -378,6 +569,16 @@ impl SerializableRegex {
     pub fn captures<'h>(&self, haystack: &'h str) -> Option<regex::Captures<'h>> {
         self.0.captures_at(haystack, 0)
     }
+
+    #[must_use]
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.0.is_match(haystack)
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
 }
 
 impl From<Regex> for SerializableRegex {
@@ -24,33 +24,46 @@ use tower_lsp::{
         ClientCapabilities, CodeActionKind, CodeActionOptions, CodeActionParams,
         CodeActionProviderCapability, CodeActionResponse, CompletionOptions, CompletionParams,
         CompletionResponse, DiagnosticOptions, DiagnosticServerCapabilities,
-        DidChangeConfigurationParams, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
-        DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentDiagnosticParams,
-        DocumentDiagnosticReportResult, DocumentFormattingParams, DocumentHighlight,
-        DocumentHighlightParams, DocumentRangeFormattingParams, DocumentSymbolParams,
-        DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverParams,
-        HoverProviderCapability, InitializeParams, InitializeResult, Location, OneOf,
-        ReferenceParams, RenameParams, SelectionRange, SelectionRangeParams,
-        SelectionRangeProviderCapability, SemanticTokenModifier, SemanticTokenType,
-        SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions,
-        SemanticTokensParams, SemanticTokensRangeParams, SemanticTokensRangeResult,
-        SemanticTokensResult, SemanticTokensServerCapabilities, ServerCapabilities,
-        SymbolInformation, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
-        WorkDoneProgressOptions, WorkspaceEdit, WorkspaceSymbolOptions, WorkspaceSymbolParams,
+        DidChangeConfigurationParams, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+        DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+        DocumentDiagnosticParams, DocumentDiagnosticReportResult, DocumentFormattingParams,
+        DocumentHighlight, DocumentHighlightParams, DocumentLink, DocumentLinkOptions,
+        DocumentLinkParams, DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams,
+        DocumentRangeFormattingParams, DocumentSymbolParams, DocumentSymbolResponse,
+        ExecuteCommandOptions, ExecuteCommandParams, GotoDefinitionParams, GotoDefinitionResponse,
+        Hover, HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
+        InitializedParams, Location, OneOf, ReferenceParams, RenameParams, SelectionRange,
+        SelectionRangeParams, SelectionRangeProviderCapability, SemanticTokenModifier,
+        SemanticTokenType, SemanticTokens, SemanticTokensDeltaParams,
+        SemanticTokensFullDeltaResult, SemanticTokensFullOptions, SemanticTokensLegend,
+        SemanticTokensOptions, SemanticTokensParams, SemanticTokensRangeParams,
+        SemanticTokensRangeResult, SemanticTokensResult, SemanticTokensServerCapabilities,
+        ServerCapabilities, SymbolInformation, TextDocumentSyncCapability, TextDocumentSyncKind,
+        TextEdit, TypeHierarchyItem, TypeHierarchyPrepareParams, TypeHierarchySubtypesParams,
+        TypeHierarchySupertypesParams, Url, WorkDoneProgressOptions, WorkspaceEdit,
+        WorkspaceSymbolOptions, WorkspaceSymbolParams,
     },
 };
 use tree_sitter::{Language, Tree, wasmtime::Engine};
 
+use handlers::execute_command::{FIX_ALL_WORKSPACE_COMMAND, FORMAT_WORKSPACE_COMMAND};
+use handlers::workspace_symbol::SymbolIndex;
 use handlers::{
-    code_action, completion, diagnostic, did_change, did_change_configuration, did_close, did_open,
-    did_save, document_highlight, document_symbol, formatting, goto_definition, hover, initialize,
-    references, rename, selection_range, semantic_tokens, shutdown, workspace_symbol,
+    code_action, completion, diagnostic, did_change, did_change_configuration,
+    did_change_watched_files, did_close, did_open, did_save, document_highlight, document_link,
+    document_symbol, execute_command, formatting, goto_definition, hover, initialize, initialized,
+    references, rename, selection_range, semantic_tokens, shutdown, type_hierarchy,
+    workspace_symbol,
 };
 use logging::LspLogLayer;
+use node_types::NodeTypesCache;
+use util::ParserNamesCache;
 
 mod cli;
 mod handlers;
 mod logging;
+mod node_types;
+mod pattern_syntax;
 mod test_helpers;
 mod util;
 
@@ -64,14 +77,31 @@ static SERVER_CAPABILITIES: LazyLock<ServerCapabilities> = LazyLock::new(|| Serv
         ..Default::default()
     })),
     code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
-        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+        code_action_kinds: Some(vec![
+            CodeActionKind::QUICKFIX,
+            CodeActionKind::REFACTOR_REWRITE,
+            CodeActionKind::SOURCE_FIX_ALL,
+        ]),
         ..Default::default()
     })),
+    execute_command_provider: Some(ExecuteCommandOptions {
+        commands: vec![
+            String::from(FIX_ALL_WORKSPACE_COMMAND),
+            String::from(FORMAT_WORKSPACE_COMMAND),
+        ],
+        work_done_progress_options: WorkDoneProgressOptions {
+            work_done_progress: Some(true),
+        },
+    }),
     references_provider: Some(OneOf::Left(true)),
     rename_provider: Some(OneOf::Left(true)),
     definition_provider: Some(OneOf::Left(true)),
     document_formatting_provider: Some(OneOf::Left(true)),
     document_range_formatting_provider: Some(OneOf::Left(true)),
+    document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+        first_trigger_character: String::from(")"),
+        more_trigger_character: Some(vec![String::from("]"), String::from("\n")]),
+    }),
     completion_provider: Some(CompletionOptions {
         trigger_characters: Some(
             ["@", "\"", "\\", "(", "/", ".", "#", "!"]
@@ -81,6 +111,10 @@ static SERVER_CAPABILITIES: LazyLock<ServerCapabilities> = LazyLock::new(|| Serv
         ..CompletionOptions::default()
     }),
     document_highlight_provider: Some(OneOf::Left(true)),
+    document_link_provider: Some(DocumentLinkOptions {
+        resolve_provider: Some(false),
+        work_done_progress_options: WorkDoneProgressOptions::default(),
+    }),
     semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
         SemanticTokensOptions {
             legend: SemanticTokensLegend {
@@ -89,10 +123,21 @@ static SERVER_CAPABILITIES: LazyLock<ServerCapabilities> = LazyLock::new(|| Serv
                     SemanticTokenType::VARIABLE,
                     SemanticTokenType::NAMESPACE,
                     SemanticTokenType::KEYWORD,
+                    SemanticTokenType::TYPE,
+                    SemanticTokenType::PROPERTY,
+                    SemanticTokenType::FUNCTION,
+                    SemanticTokenType::MACRO,
+                    SemanticTokenType::STRING,
+                    SemanticTokenType::new("escapeSequence"),
+                ],
+                token_modifiers: vec![
+                    SemanticTokenModifier::DEFAULT_LIBRARY,
+                    SemanticTokenModifier::DEPRECATED,
+                    SemanticTokenModifier::new("invalid"),
+                    SemanticTokenModifier::new("supertype"),
                 ],
-                token_modifiers: vec![SemanticTokenModifier::DEFAULT_LIBRARY],
             },
-            full: Some(SemanticTokensFullOptions::Bool(true)),
+            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
             range: Some(true),
             ..Default::default()
         },
@@ -218,6 +263,11 @@ struct Backend<C: LspClient> {
     dependents: DashMap<Url, HashSet<Url>>,
     options: Arc<tokio::sync::RwLock<Options>>,
     workspace_paths: Arc<RwLock<Vec<PathBuf>>>,
+    symbol_index: SymbolIndex,
+    /// The most recent full semantic tokens sent for each document, used to compute deltas
+    semantic_tokens_map: DashMap<Url, SemanticTokens>,
+    node_types_map: NodeTypesCache,
+    parser_names_map: ParserNamesCache,
 }
 
 #[tower_lsp::async_trait]
@@ -226,6 +276,10 @@ impl<C: LspClient> LanguageServer for Backend<C> {
         initialize::initialize(self, params).await
     }
 
+    async fn initialized(&self, params: InitializedParams) {
+        initialized::initialized(self, params).await;
+    }
+
     async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
         did_change_configuration::did_change_configuration(self, params).await;
     }
@@ -238,7 +292,7 @@ impl<C: LspClient> LanguageServer for Backend<C> {
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
-        Ok(goto_definition::goto_definition(self, &params))
+        Ok(goto_definition::goto_definition(self, &params).await)
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
@@ -257,8 +311,12 @@ impl<C: LspClient> LanguageServer for Backend<C> {
         did_change::did_change(self, params).await;
     }
 
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        did_change_watched_files::did_change_watched_files(self, &params);
+    }
+
     async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
-        Ok(references::references(self, &params))
+        Ok(references::references(self, &params).await)
     }
 
     async fn document_highlight(
@@ -268,8 +326,12 @@ impl<C: LspClient> LanguageServer for Backend<C> {
         Ok(document_highlight::document_highlight(self, &params))
     }
 
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        Ok(document_link::document_link(self, &params))
+    }
+
     async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
-        rename::rename(self, &params)
+        rename::rename(self, &params).await
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
@@ -277,28 +339,42 @@ impl<C: LspClient> LanguageServer for Backend<C> {
     }
 
     async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
-        Ok(formatting::formatting(self, &params))
+        Ok(formatting::formatting(self, &params).await)
     }
 
     async fn range_formatting(
         &self,
         params: DocumentRangeFormattingParams,
     ) -> Result<Option<Vec<TextEdit>>> {
-        Ok(formatting::range_formatting(self, &params))
+        Ok(formatting::range_formatting(self, &params).await)
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        Ok(formatting::on_type_formatting(self, &params).await)
     }
 
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
-        Ok(semantic_tokens::semantic_tokens_full(self, &params))
+        Ok(semantic_tokens::semantic_tokens_full(self, &params).await)
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        Ok(semantic_tokens::semantic_tokens_full_delta(self, &params).await)
     }
 
     async fn semantic_tokens_range(
         &self,
         params: SemanticTokensRangeParams,
     ) -> Result<Option<SemanticTokensRangeResult>> {
-        Ok(semantic_tokens::semantic_tokens_range(self, &params))
+        Ok(semantic_tokens::semantic_tokens_range(self, &params).await)
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
@@ -327,7 +403,14 @@ impl<C: LspClient> LanguageServer for Backend<C> {
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
-        Ok(code_action::code_action(self, params))
+        Ok(code_action::code_action(self, params).await)
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        execute_command::execute_command(self, params).await
     }
 
     async fn selection_range(
@@ -336,6 +419,27 @@ impl<C: LspClient> LanguageServer for Backend<C> {
     ) -> Result<Option<Vec<SelectionRange>>> {
         Ok(selection_range::selection_range(self, &params))
     }
+
+    async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(type_hierarchy::prepare_type_hierarchy(self, &params))
+    }
+
+    async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(type_hierarchy::supertypes(self, &params))
+    }
+
+    async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(type_hierarchy::subtypes(self, &params))
+    }
 }
 
 #[derive(Parser)]
@@ -356,6 +460,11 @@ enum Commands {
         /// List of directories to format.
         directories: Vec<PathBuf>,
 
+        /// String representing server's JSON configuration. Defaults to the contents of
+        /// `.tsqueryrc.json`, if present.
+        #[arg(long)]
+        config: Option<String>,
+
         /// Only check that formatting is valid, do not write.
         #[arg(long, short)]
         check: bool,
@@ -438,8 +547,22 @@ fn get_config_str(config: Option<String>) -> String {
 async fn main() {
     let args = Arguments::parse();
     match args.commands {
-        Some(Commands::Format { directories, check }) => {
-            std::process::exit(format_directories(&directories, check).await);
+        Some(Commands::Format {
+            directories,
+            config,
+            check,
+        }) => {
+            // Unlike the other commands, formatting does not require a configuration
+            let config_str = config
+                .or_else(|| fs::read_to_string(".tsqueryrc.json").ok())
+                .unwrap_or_else(|| String::from("{}"));
+            let Ok(options) = serde_json::from_str::<Options>(&config_str) else {
+                eprintln!("Could not parse the provided configuration");
+                std::process::exit(1);
+            };
+            std::process::exit(
+                format_directories(&directories, &options.format_options, check).await,
+            );
         }
         Some(Commands::Check {
             directories,
@@ -490,6 +613,10 @@ async fn main() {
             client_capabilities: Arc::default(),
             dependents: DashMap::default(),
             options,
+            symbol_index: SymbolIndex::default(),
+            semantic_tokens_map: DashMap::default(),
+            node_types_map: DashMap::default(),
+            parser_names_map: DashMap::default(),
         }
     })
     .finish();
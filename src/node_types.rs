@@ -0,0 +1,140 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Arc};
+
+use dashmap::DashMap;
+use serde::Deserialize;
+use tower_lsp::lsp_types::Url;
+use ts_query_ls::Options;
+
+use crate::{SymbolInfo, util::get_grammar_directories};
+
+/// The description of a node kind in a grammar's `node-types.json` file.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NodeInfo {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub named: bool,
+    #[serde(default)]
+    pub fields: BTreeMap<String, ChildInfo>,
+    pub children: Option<ChildInfo>,
+    #[serde(default)]
+    pub subtypes: Vec<NodeTypeRef>,
+}
+
+/// The node kinds allowed for a field, or for the children which are not associated with a field.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChildInfo {
+    pub multiple: bool,
+    pub required: bool,
+    pub types: Vec<NodeTypeRef>,
+}
+
+/// A reference to a node kind.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NodeTypeRef {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub named: bool,
+}
+
+impl NodeTypeRef {
+    pub fn symbol(&self) -> SymbolInfo {
+        SymbolInfo {
+            label: self.kind.clone(),
+            named: self.named,
+        }
+    }
+}
+
+impl ChildInfo {
+    /// The quantifier a pattern would use to match every allowed count of these children.
+    pub const fn quantifier(&self) -> &'static str {
+        match (self.required, self.multiple) {
+            (true, false) => "",
+            (false, false) => "?",
+            (true, true) => "+",
+            (false, true) => "*",
+        }
+    }
+
+    /// Render the allowed node kinds as a pattern, using an alternation if there are several.
+    pub fn pattern(&self) -> String {
+        let types = self
+            .types
+            .iter()
+            .map(|node_type| node_type.symbol().to_string())
+            .collect::<Vec<_>>();
+        if types.len() == 1 {
+            format!("{}{}", types[0], self.quantifier())
+        } else {
+            format!("[{}]{}", types.join(" "), self.quantifier())
+        }
+    }
+}
+
+impl NodeInfo {
+    /// Render the structure of the node as a query pattern, listing each field followed by the
+    /// children that are not associated with a field.
+    pub fn structure(&self) -> String {
+        let mut lines = self
+            .fields
+            .iter()
+            .map(|(name, field)| format!("{name}: {}", field.pattern()))
+            .collect::<Vec<_>>();
+        if let Some(children) = &self.children {
+            lines.push(children.pattern());
+        }
+        let node = self.symbol();
+        if lines.is_empty() {
+            return node.to_string();
+        }
+        format!("({}\n  {})", node.label, lines.join("\n  "))
+    }
+
+    pub fn symbol(&self) -> SymbolInfo {
+        SymbolInfo {
+            label: self.kind.clone(),
+            named: self.named,
+        }
+    }
+}
+
+/// The parsed `node-types.json` files which have been read, keyed by path. Files which could not be
+/// read or parsed are stored as `None`. The cache is cleared whenever the configuration changes.
+pub type NodeTypesCache = DashMap<PathBuf, Option<Arc<Vec<NodeInfo>>>>;
+
+/// Read the `node-types.json` file of a language from its grammar sources, if they can be found.
+pub fn get_node_types(
+    cache: &NodeTypesCache,
+    uri: &Url,
+    language_name: &str,
+    options: &Options,
+) -> Option<Arc<Vec<NodeInfo>>> {
+    get_grammar_directories(uri, language_name, options)
+        .into_iter()
+        .find_map(|dir| {
+            let path = dir.join("src/node-types.json");
+            cache
+                .entry(path.clone())
+                .or_insert_with(|| {
+                    fs::read_to_string(path)
+                        .ok()
+                        .and_then(|contents| serde_json::from_str(&contents).ok())
+                        .map(Arc::new)
+                })
+                .clone()
+        })
+}
+
+/// Get the description of a node kind from a language's `node-types.json` file.
+pub fn get_node_info(
+    cache: &NodeTypesCache,
+    uri: &Url,
+    language_name: &str,
+    options: &Options,
+    sym: &SymbolInfo,
+) -> Option<NodeInfo> {
+    get_node_types(cache, uri, language_name, options)?
+        .iter()
+        .find(|node| node.symbol() == *sym)
+        .cloned()
+}
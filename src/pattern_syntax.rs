@@ -0,0 +1,302 @@
+use std::ops::Range;
+
+use regex::Regex;
+use ts_query_ls::ParameterConstraint;
+
+/// A syntax error in a regular expression or pattern, with the byte range it applies to.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PatternError {
+    pub message: String,
+    pub range: Range<usize>,
+}
+
+impl PatternError {
+    fn new(message: impl Into<String>, range: Range<usize>) -> Self {
+        Self {
+            message: message.into(),
+            range,
+        }
+    }
+}
+
+/// Check the syntax of a parameter which is constrained to be a regular expression or pattern.
+pub fn get_pattern_error(constraint: &ParameterConstraint, pattern: &str) -> Option<PatternError> {
+    let error = match constraint {
+        ParameterConstraint::Regex => get_regex_error(pattern),
+        ParameterConstraint::LuaPattern => get_lua_pattern_error(pattern),
+        ParameterConstraint::VimRegex => get_vim_regex_error(pattern),
+        _ => None,
+    }?;
+    // Avoid empty ranges, which are hard to see in most editors
+    let mut range = error.range;
+    if range.is_empty()
+        && let Some(c) = pattern[range.start..].chars().next()
+    {
+        range.end += c.len_utf8();
+    }
+    Some(PatternError { range, ..error })
+}
+
+/// Get the string matched by a pattern which uses no features beyond anchoring itself at both
+/// ends, such as `^foo$`. Such patterns are better expressed as equality checks.
+pub fn get_fixed_string(constraint: &ParameterConstraint, pattern: &str) -> Option<String> {
+    let inner = pattern.strip_prefix('^')?.strip_suffix('$')?;
+    let mut fixed = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = match constraint {
+            ParameterConstraint::Regex => match c {
+                '\\' => chars
+                    .next()
+                    .filter(|c| regex_syntax::is_meta_character(*c))?,
+                c if regex_syntax::is_meta_character(c) => return None,
+                c => c,
+            },
+            ParameterConstraint::LuaPattern => match c {
+                '%' => chars.next().filter(|c| !c.is_alphanumeric())?,
+                '.' | '[' | ']' | '(' | ')' | '*' | '+' | '-' | '?' => return None,
+                c => c,
+            },
+            ParameterConstraint::VimRegex => match c {
+                '\\' => chars.next().filter(|c| {
+                    matches!(c, '\\' | '.' | '*' | '[' | ']' | '~' | '^' | '$' | '/')
+                })?,
+                '.' | '*' | '[' | '~' => return None,
+                c => c,
+            },
+            _ => return None,
+        };
+        fixed.push(c);
+    }
+    Some(fixed)
+}
+
+fn get_regex_error(pattern: &str) -> Option<PatternError> {
+    let error = Regex::new(pattern).err()?;
+    Some(match regex_syntax::Parser::new().parse(pattern) {
+        Err(regex_syntax::Error::Parse(error)) => PatternError::new(
+            error.kind().to_string(),
+            error.span().start.offset..error.span().end.offset,
+        ),
+        Err(regex_syntax::Error::Translate(error)) => PatternError::new(
+            error.kind().to_string(),
+            error.span().start.offset..error.span().end.offset,
+        ),
+        _ => PatternError::new(
+            match error {
+                regex::Error::CompiledTooBig(limit) => {
+                    format!("compiled regex exceeds size limit of {limit} bytes")
+                }
+                error => error.to_string(),
+            },
+            0..pattern.len(),
+        ),
+    })
+}
+
+/// Check a Lua pattern for the errors that Lua reports when matching it.
+fn get_lua_pattern_error(pattern: &str) -> Option<PatternError> {
+    let bytes = pattern.as_bytes();
+    let len = bytes.len();
+    // The start offset of each capture, and whether it has been closed
+    let mut captures: Vec<(usize, bool)> = Vec::new();
+    let mut i = usize::from(pattern.starts_with('^'));
+    while i < len {
+        match bytes[i] {
+            b'(' => {
+                captures.push((i, false));
+                i += 1;
+            }
+            b')' => {
+                let Some(capture) = captures.iter_mut().rev().find(|(_, closed)| !closed) else {
+                    return Some(PatternError::new("invalid pattern capture", i..i + 1));
+                };
+                capture.1 = true;
+                i += 1;
+            }
+            b'%' => match bytes.get(i + 1) {
+                None => {
+                    return Some(PatternError::new(
+                        "malformed pattern (ends with '%')",
+                        i..len,
+                    ));
+                }
+                Some(b'b') => {
+                    if i + 4 > len {
+                        return Some(PatternError::new(
+                            "malformed pattern (missing arguments to '%b')",
+                            i..len,
+                        ));
+                    }
+                    i += 4;
+                }
+                Some(b'f') => {
+                    if bytes.get(i + 2) != Some(&b'[') {
+                        return Some(PatternError::new(
+                            "missing '[' after '%f' in pattern",
+                            i..i + 2,
+                        ));
+                    }
+                    match lua_set_end(bytes, i + 2) {
+                        Ok(end) => i = end,
+                        Err(error) => return Some(error),
+                    }
+                }
+                Some(digit @ b'0'..=b'9') => {
+                    let index = usize::from(digit - b'0');
+                    if index == 0 || !captures.get(index - 1).is_some_and(|(_, closed)| *closed) {
+                        return Some(PatternError::new(
+                            format!("invalid capture index %{index}"),
+                            i..i + 2,
+                        ));
+                    }
+                    i += 2;
+                }
+                Some(_) => i += 1 + utf8_len(bytes, i + 1),
+            },
+            b'[' => match lua_set_end(bytes, i) {
+                Ok(end) => i = end,
+                Err(error) => return Some(error),
+            },
+            _ => i += utf8_len(bytes, i),
+        }
+    }
+    captures
+        .iter()
+        .find(|(_, closed)| !closed)
+        .map(|(start, _)| PatternError::new("unfinished capture", *start..start + 1))
+}
+
+/// Get the offset past the end of the Lua character set starting at `start`.
+fn lua_set_end(bytes: &[u8], start: usize) -> Result<usize, PatternError> {
+    let mut i = start + 1;
+    if bytes.get(i) == Some(&b'^') {
+        i += 1;
+    }
+    // The first character of a set is never its end, so that `[]]` is valid
+    loop {
+        let Some(c) = bytes.get(i) else {
+            return Err(PatternError::new(
+                "malformed pattern (missing ']')",
+                start..bytes.len(),
+            ));
+        };
+        i += 1;
+        if *c == b'%' && i < bytes.len() {
+            i += 1;
+        }
+        if bytes.get(i) == Some(&b']') {
+            return Ok(i + 1);
+        }
+    }
+}
+
+/// Check a Vim regular expression for unbalanced groups and malformed `\{}` multis, taking its
+/// magic mode into account.
+fn get_vim_regex_error(pattern: &str) -> Option<PatternError> {
+    let bytes = pattern.as_bytes();
+    let len = bytes.len();
+    let mut very_magic = false;
+    let mut magic = true;
+    let mut groups = Vec::new();
+    let mut i = 0;
+    while i < len {
+        let escaped = bytes[i] == b'\\';
+        let start = i;
+        if escaped {
+            i += 1;
+            if i == len {
+                return Some(PatternError::new("trailing backslash", start..len));
+            }
+        }
+        let c = bytes[i];
+        i += utf8_len(bytes, i);
+        // Whether the character has its special meaning, which depends on the magic mode
+        let special = escaped != very_magic;
+        match c {
+            b'v' | b'm' | b'M' | b'V' if escaped => {
+                very_magic = c == b'v';
+                magic = matches!(c, b'v' | b'm');
+            }
+            b'%' if special && bytes.get(i) == Some(&b'(') => {
+                groups.push(start);
+                i += 1;
+            }
+            b'z' if escaped && bytes.get(i) == Some(&b'(') => {
+                groups.push(start);
+                i += 1;
+            }
+            b'(' if special => groups.push(start),
+            b')' if special && groups.pop().is_none() => {
+                let message = if very_magic {
+                    "unmatched )"
+                } else {
+                    "unmatched \\)"
+                };
+                return Some(PatternError::new(message, start..i));
+            }
+            b'{' if special => {
+                let rest = &pattern[i..];
+                let Some(end) = rest.find('}') else {
+                    return Some(PatternError::new("syntax error in \\{...}", start..len));
+                };
+                let count = rest[..end].strip_suffix('\\').unwrap_or(&rest[..end]);
+                let count = count.strip_prefix('-').unwrap_or(count);
+                if count.bytes().filter(|b| *b == b',').count() > 1
+                    || !count.bytes().all(|b| b.is_ascii_digit() || b == b',')
+                {
+                    return Some(PatternError::new(
+                        "syntax error in \\{...}",
+                        start..i + end + 1,
+                    ));
+                }
+                i += end + 1;
+            }
+            // Skip collections, in which groups have no special meaning. Unterminated collections
+            // match a literal `[`.
+            b'[' if escaped != magic => {
+                if let Some(end) = vim_collection_end(bytes, i) {
+                    i = end;
+                }
+            }
+            _ => {}
+        }
+    }
+    groups.first().map(|start| {
+        let message = if very_magic {
+            "unmatched ("
+        } else {
+            "unmatched \\("
+        };
+        PatternError::new(message, *start..start + 1)
+    })
+}
+
+/// Get the offset past the end of the Vim collection whose contents start at `start`.
+fn vim_collection_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start;
+    if bytes.get(i) == Some(&b'^') {
+        i += 1;
+    }
+    // A `]` at the start of a collection is literal
+    if bytes.get(i) == Some(&b']') {
+        i += 1;
+    }
+    while let Some(c) = bytes.get(i) {
+        match c {
+            b']' => return Some(i + 1),
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn utf8_len(bytes: &[u8], i: usize) -> usize {
+    match bytes.get(i) {
+        Some(b) if *b >= 0xF0 => 4,
+        Some(b) if *b >= 0xE0 => 3,
+        Some(b) if *b >= 0xC0 => 2,
+        _ => 1,
+    }
+}
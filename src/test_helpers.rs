@@ -17,7 +17,7 @@ pub mod helpers {
         },
     };
 
-    use crate::{Backend, LspClient, Options};
+    use crate::{Backend, LspClient, Options, handlers::workspace_symbol::SymbolIndex};
 
     pub static TEST_URI: LazyLock<Url> =
         LazyLock::new(|| Url::parse("file:///tmp/queries/js/test.scm").unwrap());
@@ -63,7 +63,7 @@ pub mod helpers {
 
     #[derive(Clone, Debug, Eq, PartialEq)]
     pub struct MockRequest {
-        method: String,
+        pub method: String,
         params: Value,
     }
 
@@ -149,6 +149,10 @@ pub mod helpers {
             workspace_paths: Arc::default(),
             dependents: DashMap::default(),
             options: Arc::default(),
+            symbol_index: SymbolIndex::default(),
+            semantic_tokens_map: DashMap::default(),
+            node_types_map: DashMap::default(),
+            parser_names_map: DashMap::default(),
         })
         .finish();
 
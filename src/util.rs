@@ -1,10 +1,13 @@
 use std::{
     cell::RefCell,
+    collections::BTreeSet,
     fs::{self},
     path::{Path, PathBuf},
     sync::LazyLock,
+    time::SystemTime,
 };
 
+use dashmap::DashMap;
 use regex::Regex;
 use ropey::Rope;
 use serde_json::Value;
@@ -14,9 +17,13 @@ use tower_lsp::{
     lsp_types::{
         DocumentDiagnosticParams, DocumentDiagnosticReport, DocumentDiagnosticReportKind,
         DocumentDiagnosticReportResult, NumberOrString, PartialResultParams, Position,
-        ProgressToken, PublishDiagnosticsParams, Range, RelatedFullDocumentDiagnosticReport,
-        TextDocumentContentChangeEvent, TextDocumentIdentifier, Url, WorkDoneProgressCreateParams,
-        WorkDoneProgressParams, notification::PublishDiagnostics, request::WorkDoneProgressCreate,
+        ProgressParams, ProgressParamsValue, ProgressToken, PublishDiagnosticsParams, Range,
+        RelatedFullDocumentDiagnosticReport, TextDocumentContentChangeEvent,
+        TextDocumentIdentifier, Url, WorkDoneProgress, WorkDoneProgressBegin,
+        WorkDoneProgressCreateParams, WorkDoneProgressEnd, WorkDoneProgressParams,
+        WorkDoneProgressReport,
+        notification::{Progress, PublishDiagnostics},
+        request::WorkDoneProgressCreate,
     },
 };
 use tracing::{error, warn};
@@ -32,7 +39,9 @@ pub static CAPTURES_QUERY: LazyLock<Query> =
 pub static INHERITS_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^;+\s*inherits: ([a-zA-Z0-9\-_,]+)").unwrap());
 pub static FORMAT_IGNORE_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"^;+\s*(format-ignore)").unwrap());
+    LazyLock::new(|| Regex::new(r"^;+\s*(format-ignore)([^-]|$)").unwrap());
+pub static FORMAT_IGNORE_REGION_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^;+\s*(format-ignore-(start|end))").unwrap());
 
 thread_local! {
     static QUERY_PARSER: RefCell<Parser> = {
@@ -202,6 +211,44 @@ pub fn get_references<'a>(
         .flatten()
 }
 
+/// Get the regions delimited by `; format-ignore-start` and `; format-ignore-end` comments with the
+/// same parent, as pairs of start and end comments. Unterminated regions, which have no end
+/// comment, extend to the end of their parent. Regions are returned in document order.
+pub fn get_format_ignore_regions<'a>(
+    rope: &Rope,
+    root: Node<'a>,
+) -> Vec<(Node<'a>, Option<Node<'a>>)> {
+    let mut regions = Vec::new();
+    let mut nodes = vec![root];
+    while let Some(node) = nodes.pop() {
+        let mut start = None;
+        for child in node.children(&mut node.walk()) {
+            if child.kind() == "comment" {
+                let text = child.text(rope);
+                let marker = FORMAT_IGNORE_REGION_REGEX
+                    .captures(&text)
+                    .and_then(|c| c.get(2));
+                match marker.map(|m| m.as_str()) {
+                    Some("start") if start.is_none() => start = Some(child),
+                    Some("end") => {
+                        if let Some(start) = start.take() {
+                            regions.push((start, Some(child)));
+                        }
+                    }
+                    _ => {}
+                }
+            } else if start.is_none() && child.child_count() > 0 {
+                nodes.push(child);
+            }
+        }
+        if let Some(start) = start {
+            regions.push((start, None));
+        }
+    }
+    regions.sort_unstable_by_key(|(start, _)| start.start_byte());
+    regions
+}
+
 pub fn node_is_or_has_ancestor(root: Node, node: Node, kind: &str) -> bool {
     let mut optional_current_node = root.child_with_descendant(node);
     while let Some(unwrapped_current_node) = optional_current_node {
@@ -338,6 +385,64 @@ pub fn get_language(name: &str, options: &Options) -> Option<Language> {
     None
 }
 
+/// The names of the parsers found in each parser install directory, along with the modification
+/// time of the directory when it was read, so that parsers installed later are found. The names
+/// are `None` if the directory could not be read.
+pub type ParserNamesCache = DashMap<String, (Option<SystemTime>, Option<BTreeSet<String>>)>;
+
+/// Get the names of all languages whose parsers can be found in the parser install directories,
+/// along with the language aliases and their targets. `None` if no parser install directory could
+/// be read, in which case the available languages are unknown.
+pub fn get_available_languages(
+    cache: &ParserNamesCache,
+    options: &Options,
+) -> Option<BTreeSet<String>> {
+    let mut languages: Option<BTreeSet<String>> = None;
+    for directory in &options.parser_install_directories {
+        let modified = fs::metadata(directory)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let mut entry = cache
+            .entry(directory.clone())
+            .or_insert_with(|| (modified, get_parser_names(directory)));
+        if entry.0 != modified {
+            *entry = (modified, get_parser_names(directory));
+        }
+        if let Some(names) = &entry.1 {
+            languages
+                .get_or_insert_default()
+                .extend(names.iter().cloned());
+        }
+    }
+    let mut languages = languages?;
+    for (alias, target) in &options.parser_aliases {
+        languages.insert(alias.clone());
+        languages.insert(target.clone());
+    }
+    Some(languages)
+}
+
+fn get_parser_names(directory: &str) -> Option<BTreeSet<String>> {
+    let entries = fs::read_dir(directory).ok()?;
+    let mut languages = BTreeSet::new();
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        let name = DYLIB_EXTENSIONS
+            .iter()
+            .find_map(|extension| file_name.strip_suffix(extension))
+            .or_else(|| {
+                file_name
+                    .strip_prefix("tree-sitter-")
+                    .and_then(|name| name.strip_suffix(".wasm"))
+            });
+        if let Some(name) = name {
+            languages.insert(name.to_owned());
+        }
+    }
+    Some(languages)
+}
+
 fn get_language_object_wasm(name: &str, directory: &String) -> Option<Language> {
     let object_name = format!("tree-sitter-{name}.wasm");
     let mut language_store = WasmStore::new(&ENGINE).ok()?;
@@ -375,23 +480,25 @@ impl NodeUtil for Node<'_> {
     }
 }
 
-fn get_first_valid_file_config(workspace_uris: Vec<PathBuf>) -> Option<Options> {
-    for mut path in workspace_uris {
-        let mut config_path = path.join(".tsqueryrc.json");
+/// Find the first valid configuration file in the given workspace directories or their ancestors,
+/// returning its path along with the options it holds.
+pub fn get_first_valid_file_config(workspace_uris: &[PathBuf]) -> Option<(PathBuf, Options)> {
+    for workspace_uri in workspace_uris {
+        let mut path = workspace_uri.as_path();
         loop {
+            let config_path = path.join(".tsqueryrc.json");
             if config_path.is_file()
                 && let Some(options) = fs::read_to_string(&config_path)
                     .ok()
                     .and_then(|data| serde_json::from_str(&data).ok())
             {
-                return options;
+                return Some((config_path, options));
             }
             // Traverse up the file tree in search of a config file
             path = match path.parent() {
-                Some(parent) => parent.into(),
+                Some(parent) => parent,
                 None => break,
             };
-            config_path = path.join(".tsqueryrc.json");
         }
     }
     None
@@ -400,10 +507,12 @@ fn get_first_valid_file_config(workspace_uris: Vec<PathBuf>) -> Option<Options>
 pub async fn set_configuration_options<C: LspClient>(
     backend: &Backend<C>,
     init_options: Option<Value>,
-    workspace_uris: Vec<PathBuf>,
+    workspace_uris: &[PathBuf],
 ) {
     let mut options = backend.options.write().await;
     *options = Options::default();
+    backend.node_types_map.clear();
+    backend.parser_names_map.clear();
 
     if let Some(init_options) = init_options {
         if let Ok(parsed_options) = serde_json::from_value::<Options>(init_options) {
@@ -413,7 +522,7 @@ pub async fn set_configuration_options<C: LspClient>(
         }
     }
 
-    if let Some(mut file_options) = get_first_valid_file_config(workspace_uris) {
+    if let Some((_, mut file_options)) = get_first_valid_file_config(workspace_uris) {
         // Merge parser_install_directories, since these are dependent on the local user's
         // installation paths
         let mut config_file_install_dirs = options.parser_install_directories.clone();
@@ -511,43 +620,87 @@ pub fn get_file_uris(
     urls
 }
 
-/// Returns a list of URIs corresponding to the modules in the `; inherits: ` chain. `None` if the
-/// module could not be found.
-pub fn get_imported_uris(
-    workspace_dirs: &[PathBuf],
-    options: &Options,
-    uri: &Url,
-    rope: &Rope,
-    tree: &Tree,
-) -> Vec<ImportedUri> {
-    let mut uris = Vec::new();
+/// Get the directories which may hold the grammar sources of a language, in order of precedence:
+/// the `tree-sitter-<lang>` grammar repository that the query file lives in, followed by the
+/// `tree-sitter-<lang>` and `<lang>` directories within the configured grammar directories. Other
+/// `queries` directories (e.g. in editor configurations) are not mistaken for grammar repositories.
+pub fn get_grammar_directories(uri: &Url, language_name: &str, options: &Options) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(path) = uri.to_file_path() {
+        dirs.extend(
+            path.ancestors()
+                .filter(|ancestor| ancestor.file_name().is_some_and(|name| name == "queries"))
+                .filter_map(Path::parent)
+                .filter(|parent| {
+                    parent
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with("tree-sitter-"))
+                })
+                .map(Path::to_path_buf),
+        );
+    }
+    for directory in &options.grammar_directories {
+        let directory = Path::new(directory);
+        dirs.push(directory.join(format!("tree-sitter-{language_name}")));
+        dirs.push(directory.join(language_name));
+    }
+    dirs.retain(|dir| dir.is_dir());
+    dirs
+}
+
+/// Returns the start column, end column, and name of each module in the `; inherits: ` comment on
+/// the first line of the document. Columns are byte offsets.
+pub fn get_inherited_modules(rope: &Rope, tree: &Tree) -> Vec<(u32, u32, String)> {
+    let mut modules = Vec::new();
     let Some(start_comment) = tree
         .root_node()
         .child(0)
         .filter(|node| node.kind() == "comment" && node.start_position().row == 0)
     else {
-        return uris;
+        return modules;
     };
     let comment_text = start_comment.text(rope);
-    let Some(modules) = INHERITS_REGEX
+    let Some(module_list) = INHERITS_REGEX
         .captures(&comment_text)
         .and_then(|c| c.get(1))
     else {
-        return uris;
+        return modules;
     };
+
+    let mut byte_offset = (start_comment.start_byte() + module_list.start()) as u32;
+    for module in module_list.as_str().split(',') {
+        let (start, end) = (byte_offset, byte_offset + module.len() as u32);
+        byte_offset = end + 1;
+        modules.push((start, end, module.to_string()));
+    }
+
+    modules
+}
+
+/// Returns a list of URIs corresponding to the modules in the `; inherits: ` chain. `None` if the
+/// module could not be found.
+pub fn get_imported_uris(
+    workspace_dirs: &[PathBuf],
+    options: &Options,
+    uri: &Url,
+    rope: &Rope,
+    tree: &Tree,
+) -> Vec<ImportedUri> {
+    let mut uris = Vec::new();
+    let modules = get_inherited_modules(rope, tree);
+    if modules.is_empty() {
+        return uris;
+    }
     let Some(query_name) = uri_to_basename(uri) else {
         return uris;
     };
 
-    let mut byte_offset = (start_comment.start_byte() + modules.start()) as u32;
-    for module in modules.as_str().split(',') {
-        let (start, end) = (byte_offset, byte_offset + module.len() as u32);
-        byte_offset = end + 1;
+    for (start, end, module) in modules {
         if module.is_empty() {
-            uris.push(ImportedUri::new(start, end, module.to_string(), None));
+            uris.push(ImportedUri::new(start, end, module, None));
             continue;
         }
-        let module_uris = get_file_uris(workspace_dirs, options, module, &query_name);
+        let module_uris = get_file_uris(workspace_dirs, options, &module, &query_name);
         if module_uris.len() > 1 {
             warn!(
                 "Imported module {module} has more than one associated file location, analyzing the first one"
@@ -556,7 +709,7 @@ pub fn get_imported_uris(
         uris.push(ImportedUri::new(
             start,
             end,
-            module.to_string(),
+            module,
             module_uris.first().cloned(),
         ));
     }
@@ -564,23 +717,40 @@ pub fn get_imported_uris(
     uris
 }
 
-/// Check if a string is a subsequence of another string; in order words, it is contained in the
-/// other string with possible gaps between characters.
-pub fn is_subsequence(sub: &str, main: &str) -> bool {
-    let mut sub_iter = sub.chars().peekable();
-    let mut main_iter = main.chars();
-
-    while let Some(&sub_char) = sub_iter.peek() {
-        match main_iter.next() {
-            Some(main_char) if main_char == sub_char => {
-                sub_iter.next();
+/// Rank how well a query fuzzily matches a symbol name, ignoring case and the symbol's sigils (e.g.
+/// the `@` of a capture). Lower scores are better: exact matches rank above prefix matches, which
+/// rank above substring matches, which rank above subsequence matches (tighter ones first). Returns
+/// `None` if the query is not a subsequence of the name.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<(u8, usize, usize)> {
+    if query.is_empty() {
+        return Some((0, 0, 0));
+    }
+    let query = query.to_lowercase();
+    let name_lower = name.to_lowercase();
+    let stripped = name_lower
+        .trim_start_matches(['@', '#', '(', '"', '['])
+        .trim_end_matches([')', '"', ']']);
+
+    let tier = if stripped == query || name_lower == query {
+        0
+    } else if stripped.starts_with(&query) || name_lower.starts_with(&query) {
+        1
+    } else if stripped.contains(&query) {
+        2
+    } else {
+        let mut query_chars = query.chars().peekable();
+        let mut span = (None, 0);
+        for (i, char) in stripped.chars().enumerate() {
+            if query_chars.next_if_eq(&char).is_some() {
+                span = (span.0.or(Some(i)), i);
             }
-            None => return false,
-            _ => {}
         }
-    }
-
-    true
+        if query_chars.peek().is_some() {
+            return None;
+        }
+        return Some((3, span.1 - span.0.unwrap_or_default(), name.len()));
+    };
+    Some((tier, 0, name.len()))
 }
 
 pub fn get_imported_module_under_cursor(
@@ -728,6 +898,129 @@ pub async fn get_work_done_token<C: LspClient>(
     }
 }
 
+/// Reports the progress of processing a number of files to the client, if it provided a work done
+/// token. A report is sent whenever the progress increases by at least 5 percent.
+pub struct FileProgress<'a, C: LspClient> {
+    backend: &'a Backend<C>,
+    token: Option<ProgressToken>,
+    /// The past participle describing a processed file, e.g. "indexed".
+    verb: &'static str,
+    file_count: usize,
+    processed_count: usize,
+    percentage: u32,
+}
+
+impl<'a, C: LspClient> FileProgress<'a, C> {
+    pub async fn begin(
+        backend: &'a Backend<C>,
+        token: Option<ProgressToken>,
+        title: &str,
+        verb: &'static str,
+        file_count: usize,
+    ) -> Self {
+        let progress = Self {
+            backend,
+            token,
+            verb,
+            file_count,
+            processed_count: 0,
+            percentage: 0,
+        };
+        progress
+            .send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: title.into(),
+                percentage: Some(0),
+                message: Some(format!("0/{file_count} files {verb}")),
+                cancellable: Some(false),
+            }))
+            .await;
+        progress
+    }
+
+    /// Count a processed file.
+    pub async fn advance(&mut self) {
+        self.processed_count += 1;
+        let file_count_div_100 = self.file_count as f64 * 0.01;
+        let percentage = (self.processed_count as f64 / file_count_div_100).floor() as u32;
+        if percentage > self.percentage + 4 {
+            self.percentage = percentage;
+            self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+                percentage: Some(percentage),
+                message: Some(format!(
+                    "{}/{} files {}",
+                    self.processed_count, self.file_count, self.verb
+                )),
+                cancellable: Some(false),
+            }))
+            .await;
+        }
+    }
+
+    pub async fn end(self) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd {
+            message: Some(format!("{0}/{0} files {1}", self.file_count, self.verb)),
+        }))
+        .await;
+    }
+
+    async fn send(&self, progress: WorkDoneProgress) {
+        if let Some(token) = self.token.clone() {
+            self.backend
+                .client
+                .send_notification::<Progress>(ProgressParams {
+                    token,
+                    value: ProgressParamsValue::WorkDone(progress),
+                })
+                .await;
+        }
+    }
+}
+
+/// Interpret the escape sequences of the given string content, as tree-sitter does when parsing a
+/// query. Along with the value, returns the offset in the input of each byte of the value,
+/// followed by the length of the input.
+pub fn unescape_string_content(input: &str) -> (String, Vec<usize>) {
+    let mut value = String::with_capacity(input.len());
+    let mut offsets = Vec::with_capacity(input.len() + 1);
+    let mut chars = input.char_indices();
+
+    while let Some((offset, c)) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some((_, 'n')) => '\n',
+                Some((_, 'r')) => '\r',
+                Some((_, 't')) => '\t',
+                Some((_, '0')) => '\0',
+                Some((_, c)) => c,
+                None => break,
+            }
+        } else {
+            c
+        };
+        offsets.extend(std::iter::repeat_n(offset, c.len_utf8()));
+        value.push(c);
+    }
+    offsets.push(input.len());
+
+    (value, offsets)
+}
+
+/// Escape text so that it can be placed between the quotes of a query string.
+pub fn escape_string_content(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 /// Remove unnecessary backslashes from the given string content.
 pub fn remove_unnecessary_escapes(input: &str) -> String {
     let mut result = String::new();
@@ -752,3 +1045,60 @@ pub fn remove_unnecessary_escapes(input: &str) -> String {
 
     result
 }
+
+/// The number of single-character insertions, deletions, substitutions and transpositions of
+/// adjacent characters needed to turn one string into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // The last two rows of the distance matrix, since transpositions look two rows back
+    let mut prev_prev = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (prev[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(prev_prev[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut prev_prev, &mut prev);
+        std::mem::swap(&mut prev, &mut current);
+    }
+    prev[b.len()]
+}
+
+const MAX_SUGGESTIONS: usize = 3;
+/// The largest edit distance of a suggestion. This is kept small because a single suggestion is
+/// applied by the fix-all actions, and should only correct typos rather than drop whole suffixes.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Get the candidates which are closest to the given name, if they are close enough to suggest
+/// that the name is a typo of one of them.
+pub fn get_suggestions<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).clamp(1, MAX_SUGGESTION_DISTANCE);
+    let mut best_distance = max_distance + 1;
+    let mut suggestions = Vec::new();
+    for candidate in candidates {
+        let distance = edit_distance(name, candidate);
+        if distance > best_distance || candidate == name {
+            continue;
+        }
+        if distance < best_distance {
+            best_distance = distance;
+            suggestions.clear();
+        }
+        suggestions.push(candidate.to_owned());
+    }
+    suggestions.sort_unstable();
+    suggestions.dedup();
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
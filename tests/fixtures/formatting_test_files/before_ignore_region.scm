@@ -0,0 +1,22 @@
+; format-ignore-start
+(  (identifier)   @variable.builtin
+   (#any-of? @variable.builtin "self" "this"))
+
+[ "if"   "else" ] @keyword
+; format-ignore-end
+(   (identifier)   @constant
+  (#match? @constant   "^[A-Z]"))
+(call_expression
+  ; format-ignore-start
+  function:   (identifier)    @function
+  arguments:     (arguments)
+  ; format-ignore-end
+  (#eq?   @function "foo"))
+(function_definition
+  ; format-ignore-start
+  name:   (identifier)    @function.def
+  body:  (block))
+(  (comment)   @comment)
+; format-ignore-start
+(string)    @string
+(  number)   @number
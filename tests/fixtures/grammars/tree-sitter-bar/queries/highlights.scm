@@ -0,0 +1,3 @@
+(pair
+  key: (key) @property
+  value: (value) @string)
@@ -0,0 +1,20 @@
+module.exports = grammar({
+  name: 'foo',
+
+  externals: $ => [$.comment],
+
+  rules: {
+    source_file: $ => repeat($._statement),
+
+    _statement: $ => choice($.call_expression, $.identifier),
+
+    call_expression: $ => seq(
+      field('function', $.identifier),
+      field('arguments', $.arguments),
+    ),
+
+    arguments: $ => seq('(', optional($.identifier), ')'),
+
+    identifier: _ => /[a-z]+/,
+  },
+});
@@ -21,6 +21,7 @@ mod test {
                         arity: ts_query_ls::PredicateParameterArity::Variadic,
                         ..Default::default()
                     }],
+                    ..Default::default()
                 },
             ),
             (
@@ -33,6 +34,7 @@ mod test {
                         arity: ts_query_ls::PredicateParameterArity::Variadic,
                         ..Default::default()
                     }],
+                    ..Default::default()
                 },
             ),
         ]),